@@ -1,9 +1,7 @@
 use std::time::Duration;
 
-use purrmint::{new_request_id, OperationMethod, OperationRequest};
+use purrmint::{new_request_id, Nip74Client, Nip74Error, OperationMethod, OperationRequest};
 use nostr::prelude::*;
-use nostr_sdk::{Client, Options, RelayPoolNotification};
-use tokio::time::timeout;
 
 /// Build and send an OperationRequest, then wait for the reply.
 ///
@@ -12,8 +10,12 @@ use tokio::time::timeout;
 ///
 /// `MINT_NPUB`  – mint public key (npub...)
 /// `relay_url`  – optional, default to ws://127.0.0.1:7777
-/// `operation`  – optional, one of: info, get_mint_quote, check_mint_quote, mint, get_melt_quote, check_melt_quote, melt
+/// `operation`  – optional, one of: info, get_mint_quote, check_mint_quote, mint, get_melt_quote, check_melt_quote, melt,
+///                swap, check_state, restore, keys, keysets, batch
 ///                default: info
+/// `--gift-wrap` – optional, send the request NIP-59 gift-wrapped
+///
+/// Requests are mined to the NIP-13 difficulty advertised in the mint's kind:37400 announcement.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
@@ -24,7 +26,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // args[1] = optional mint pubkey
     // args[2] = optional relay url
     // args[3] = optional operation type
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let gift_wrap = args.iter().any(|a| a == "--gift-wrap");
+    args.retain(|a| a != "--gift-wrap");
     
     // Show help if no arguments or help requested
     if args.len() == 1 || args.contains(&"--help".to_string()) || args.contains(&"-h".to_string()) {
@@ -38,6 +42,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("    relay_url    optional relay URL, default: ws://127.0.0.1:7777");
         println!("    operation    optional operation type, default: info");
         println!();
+        println!("OPTIONS:");
+        println!("    --gift-wrap  send the request NIP-59 gift-wrapped");
+        println!();
+        println!("Requests are mined to the proof of work advertised by the mint announcement.");
+        println!();
         println!("OPERATIONS:");
         println!("    info              get mint information");
         println!("    get_mint_quote    request mint quote for 1000 sats");
@@ -46,6 +55,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("    get_melt_quote    request melt quote for Lightning invoice");
         println!("    check_melt_quote  check melt quote status (uses dummy UUID)");
         println!("    melt              melt tokens (uses dummy data)");
+        println!("    swap              swap proofs (uses dummy data)");
+        println!("    check_state       check proof states (uses dummy data)");
+        println!("    restore           restore blind signatures (uses dummy data)");
+        println!("    keys              get active keyset public keys");
+        println!("    keysets           list all keysets");
+        println!("    batch             info, keysets and keys in a single request");
         println!();
         println!("EXAMPLES:");
         println!("    cargo run --example client_demo");
@@ -67,23 +82,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let keys = Keys::parse("5b710e6de48418b70182584fdf06c692bc422478be42729939203b4c2aa496c1")?;
     println!("Client public key: {}", keys.public_key());
     
-    let client = Client::builder()
-        .signer(keys.clone())   
-        .opts(Options::default())
-        .build();
-
-    client.add_relay(relay).await?;
-    client.connect().await;
-    // Wait up to 5 seconds for the relay connection to establish.
-    client.wait_for_connection(Duration::from_secs(5)).await;
-    println!("Connected to relay");
+    let client = Nip74Client::connect(keys.clone(), mint_pubkey, &[relay])
+        .await?
+        .with_timeout(Duration::from_secs(30))
+        .with_retries(1)
+        .with_gift_wrap(gift_wrap);
+    println!("Connected to relay and subscribed to 27402 events");
+    if gift_wrap {
+        println!("Requests are gift-wrapped (NIP-59)");
+    }
 
-    // Subscribe for OperationResult events addressed to us
-    let sub_filter = Filter::new()
-        .kind(Kind::from(27402u16))
-        .pubkey(keys.public_key()); // 'p' tag contains our pubkey
-    client.subscribe(sub_filter, None).await?;
-    println!("Subscribed to 27402 events");
+    let pow = client.fetch_required_pow().await.unwrap_or(0);
+    let client = client.with_pow(pow);
+    if pow > 0 {
+        println!("Mint requires {} bits of proof of work (NIP-13)", pow);
+    }
 
     // Compose request based on operation type
     let request_id = new_request_id();
@@ -137,8 +150,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "inputs": []
             })),
         },
+        "swap" => OperationRequest {
+            method: OperationMethod::Swap,
+            request_id: request_id,
+            data: Some(serde_json::json!({
+                "inputs": [],
+                "outputs": []
+            })),
+        },
+        "check_state" => OperationRequest {
+            method: OperationMethod::CheckState,
+            request_id: request_id,
+            data: Some(serde_json::json!({
+                "Ys": []
+            })),
+        },
+        "restore" => OperationRequest {
+            method: OperationMethod::Restore,
+            request_id: request_id,
+            data: Some(serde_json::json!({
+                "outputs": []
+            })),
+        },
+        "keys" => OperationRequest {
+            method: OperationMethod::Keys,
+            request_id: request_id,
+            data: None,
+        },
+        "keysets" => OperationRequest {
+            method: OperationMethod::Keysets,
+            request_id: request_id,
+            data: None,
+        },
+        "batch" => OperationRequest {
+            method: OperationMethod::Batch,
+            request_id: request_id,
+            data: Some(serde_json::json!([
+                OperationRequest::new(OperationMethod::Info, None),
+                OperationRequest::new(OperationMethod::Keysets, None),
+                OperationRequest::new(OperationMethod::Keys, None),
+            ])),
+        },
         _ => {
-            eprintln!("Unknown operation: {}. Available operations: info, get_mint_quote, check_mint_quote, mint, get_melt_quote, check_melt_quote, melt", operation);
+            eprintln!("Unknown operation: {}. Available operations: info, get_mint_quote, check_mint_quote, mint, get_melt_quote, check_melt_quote, melt, swap, check_state, restore, keys, keysets, batch", operation);
             eprintln!("Run with --help for usage information");
             std::process::exit(1);
         }
@@ -146,51 +200,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("Request content: {}", serde_json::to_string_pretty(&request)?);
 
-    // Serialize and encrypt payload with NIP-44.
-    let plaintext = serde_json::to_string(&request)?;
-    // Use NIP-44 encryption
-    let ciphertext = nostr::nips::nip44::encrypt(keys.secret_key(), &mint_pubkey, plaintext, Default::default())?;
-
-    // Build event.
-    let event = EventBuilder::new(Kind::from(27401u16), ciphertext)
-        .tag(Tag::public_key(mint_pubkey))
-        .sign_with_keys(&keys)?;
-
-    println!("Event ID: {}", event.id);
-    println!("Event tags: {:?}", event.tags);
-
-    let output = client.send_event(&event).await?;
-    println!("27401 sent, success relays: {:?}, failed: {:?}", output.success, output.failed);
-
-    // Wait up to 30 seconds for the result.
-    let mut notifications = client.notifications();
-    match timeout(Duration::from_secs(30), async move {
-        while let Ok(notif) = notifications.recv().await {
-            if let RelayPoolNotification::Event { event, .. } = notif {
-                println!("Received event: kind={}, id={}", event.kind, event.id);
-                if event.kind == Kind::from(27402u16) {
-                    println!("Received 27402 response event, tags: {:?}", event.tags);
-                    // Decrypt with NIP-44
-                    if let Ok(decrypted) = nostr::nips::nip44::decrypt(
-                        keys.secret_key(),
-                        &mint_pubkey,
-                        &event.content,
-                    ) {
-                        println!("Decryption successful! OperationResult: {decrypted}");
-                        return Some(());
-                    } else {
-                        println!("Decryption failed!");
-                    }
-                }
-            }
+    match client.send(&request).await {
+        Ok(result) => {
+            println!("OperationResult: {}", serde_json::to_string_pretty(&result)?);
+            println!("Done");
         }
-        None
-    })
-    .await
-    {
-        Ok(Some(_)) => println!("Done"),
-        _ => println!("No reply within timeout"),
+        Err(Nip74Error::Timeout(_)) => println!("No reply within timeout"),
+        Err(e) => println!("Request failed: {}", e),
     }
 
+    client.disconnect().await;
     Ok(())
-}
\ No newline at end of file
+}
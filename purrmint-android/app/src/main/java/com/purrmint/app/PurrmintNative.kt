@@ -71,4 +71,28 @@ class PurrmintNative {
     
     // Get onion address - matches Java_com_purrmint_app_PurrmintNative_getOnionAddress
     external fun getOnionAddress(): String?
+
+    // Announce maintenance (true) or running status over NIP-74 - matches Java_com_purrmint_app_PurrmintNative_setMaintenanceMode
+    external fun setMaintenanceMode(maintenance: Boolean): Int
+
+    // Add npub to allowlist/denylist, returns the accessMode/allowedNpubs/deniedNpubs JSON to save into the config - matches Java_com_purrmint_app_PurrmintNative_addAccessNpub
+    external fun addAccessNpub(npub: String, deny: Boolean): String?
+
+    // Remove npub from allowlist/denylist, returns the accessMode/allowedNpubs/deniedNpubs JSON to save into the config - matches Java_com_purrmint_app_PurrmintNative_removeAccessNpub
+    external fun removeAccessNpub(npub: String, deny: Boolean): String?
+
+    // Get access policy JSON - matches Java_com_purrmint_app_PurrmintNative_getAccessPolicy
+    external fun getAccessPolicy(): String?
+
+    // Start NIP-74 to HTTP gateway (empty nsec uses fresh keys) - matches Java_com_purrmint_app_PurrmintNative_startGateway
+    external fun startGateway(configJson: String, nsec: String): Int
+
+    // Stop gateway - matches Java_com_purrmint_app_PurrmintNative_stopGateway
+    external fun stopGateway(): Int
+
+    // Get gateway status - matches Java_com_purrmint_app_PurrmintNative_getGatewayStatus
+    external fun getGatewayStatus(): String?
+
+    // Discover mints announced on relays (JSON array, empty for defaults) - matches Java_com_purrmint_app_PurrmintNative_discoverMints
+    external fun discoverMints(relaysJson: String, timeoutSecs: Int): String?
 } 
\ No newline at end of file
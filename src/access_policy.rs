@@ -0,0 +1,247 @@
+//! Client access policy for private NIP-74 mints
+//! Holds the allowlisted and denylisted client npubs and rejects requests
+//! from pubkeys the policy does not admit
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, RwLock};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use nostr::{FromBech32, PublicKey, ToBech32};
+use tracing::{info, warn};
+
+use crate::config::{AccessConfig, AccessMode};
+use crate::middleware::Layer;
+use crate::nip74_service::{
+    ErrorCode, Nip74Result, OperationRequest, OperationResult, RequestContext, RequestHandler, ResultError,
+};
+use crate::nostr::is_valid_npub;
+
+/// Parse a client npub, rejecting anything that is not a valid bech32 public key
+fn parse_npub(npub: &str) -> Result<PublicKey> {
+    let npub = npub.trim();
+    if !is_valid_npub(npub) {
+        return Err(anyhow!("Invalid npub: {}", npub));
+    }
+    PublicKey::from_bech32(npub).map_err(|e| anyhow!("Invalid npub {}: {}", npub, e))
+}
+
+#[derive(Debug, Default)]
+struct Lists {
+    mode: AccessMode,
+    allowed: BTreeSet<PublicKey>,
+    denied: BTreeSet<PublicKey>,
+}
+
+/// Shared, runtime-mutable client allowlist and denylist
+///
+/// Denylisted pubkeys are always rejected. In [`AccessMode::Allowlist`] only
+/// allowlisted pubkeys are served, so removing the last one closes the mint;
+/// in [`AccessMode::Open`] everyone else is served.
+///
+/// The policy only guards NIP-74 requests: in `MintdAndNip74` mode the HTTP
+/// API stays open to everyone who can reach it.
+#[derive(Debug, Clone, Default)]
+pub struct AccessPolicy {
+    lists: Arc<RwLock<Lists>>,
+}
+
+impl AccessPolicy {
+    /// Create a policy from configuration, skipping (and logging) invalid npubs
+    pub fn from_config(config: &AccessConfig) -> Self {
+        let mut lists = Lists {
+            mode: config.mode,
+            ..Default::default()
+        };
+        for (entries, target) in [(&config.allowlist, &mut lists.allowed), (&config.denylist, &mut lists.denied)] {
+            for npub in entries {
+                match parse_npub(npub) {
+                    Ok(pubkey) => {
+                        target.insert(pubkey);
+                    }
+                    Err(e) => warn!("Ignoring access policy entry: {}", e),
+                }
+            }
+        }
+
+        Self { lists: Arc::new(RwLock::new(lists)) }
+    }
+
+    /// Whether requests from `pubkey` are served
+    pub fn is_allowed(&self, pubkey: &PublicKey) -> bool {
+        match self.lists.read() {
+            Ok(lists) => {
+                !lists.denied.contains(pubkey)
+                    && match lists.mode {
+                        AccessMode::Open => true,
+                        AccessMode::Allowlist => lists.allowed.contains(pubkey),
+                    }
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Add `npub` to the allowlist
+    pub fn allow(&self, npub: &str) -> Result<()> {
+        let pubkey = parse_npub(npub)?;
+        self.update(|lists| {
+            lists.allowed.insert(pubkey);
+        })?;
+        info!("Allowlisted {}", npub);
+        Ok(())
+    }
+
+    /// Remove `npub` from the allowlist, returning whether it was listed
+    pub fn remove_allowed(&self, npub: &str) -> Result<bool> {
+        let pubkey = parse_npub(npub)?;
+        self.update(|lists| lists.allowed.remove(&pubkey))
+    }
+
+    /// Add `npub` to the denylist
+    pub fn deny(&self, npub: &str) -> Result<()> {
+        let pubkey = parse_npub(npub)?;
+        self.update(|lists| {
+            lists.denied.insert(pubkey);
+        })?;
+        info!("Denylisted {}", npub);
+        Ok(())
+    }
+
+    /// Remove `npub` from the denylist, returning whether it was listed
+    pub fn remove_denied(&self, npub: &str) -> Result<bool> {
+        let pubkey = parse_npub(npub)?;
+        self.update(|lists| lists.denied.remove(&pubkey))
+    }
+
+    /// Current lists as configuration (npubs)
+    pub fn to_config(&self) -> AccessConfig {
+        let to_npubs = |keys: &BTreeSet<PublicKey>| -> Vec<String> {
+            keys.iter().filter_map(|key| key.to_bech32().ok()).collect()
+        };
+        match self.lists.read() {
+            Ok(lists) => AccessConfig {
+                mode: lists.mode,
+                allowlist: to_npubs(&lists.allowed),
+                denylist: to_npubs(&lists.denied),
+            },
+            Err(_) => AccessConfig::default(),
+        }
+    }
+
+    /// Layer enforcing this policy
+    pub fn layer(&self) -> AccessPolicyLayer {
+        AccessPolicyLayer { policy: self.clone() }
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut Lists) -> T) -> Result<T> {
+        let mut lists = self
+            .lists
+            .write()
+            .map_err(|_| anyhow!("Access policy lock poisoned"))?;
+        Ok(f(&mut lists))
+    }
+}
+
+/// Answers requests from pubkeys not admitted by an [`AccessPolicy`] with `unauthorized`
+#[derive(Debug, Clone)]
+pub struct AccessPolicyLayer {
+    policy: AccessPolicy,
+}
+
+impl Layer for AccessPolicyLayer {
+    fn layer(&self, inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+        Arc::new(Enforce { inner, policy: self.policy.clone() })
+    }
+}
+
+struct Enforce {
+    inner: Arc<dyn RequestHandler>,
+    policy: AccessPolicy,
+}
+
+#[async_trait]
+impl RequestHandler for Enforce {
+    async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        if self.policy.is_allowed(&ctx.author) {
+            return self.inner.handle(ctx, req).await;
+        }
+
+        info!("Rejected NIP-74 request {} from {}", req.request_id, ctx.author);
+        Ok(OperationResult::failure(
+            req.request_id,
+            ResultError::new(ErrorCode::Unauthorized, "This mint does not serve your pubkey"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn npub() -> String {
+        nostr::Keys::generate().public_key().to_bech32().unwrap()
+    }
+
+    #[test]
+    fn test_empty_policy_is_public() {
+        let policy = AccessPolicy::default();
+        assert!(policy.is_allowed(&nostr::Keys::generate().public_key()));
+    }
+
+    #[test]
+    fn test_allowlist_and_denylist() {
+        let member = npub();
+        let banned = npub();
+        let policy = AccessPolicy::from_config(&AccessConfig {
+            mode: AccessMode::Allowlist,
+            allowlist: vec![member.clone(), "npub1invalid".to_string()],
+            denylist: vec![],
+        });
+
+        let member_key = PublicKey::from_bech32(&member).unwrap();
+        let banned_key = PublicKey::from_bech32(&banned).unwrap();
+        assert!(policy.is_allowed(&member_key));
+        assert!(!policy.is_allowed(&banned_key));
+        assert_eq!(policy.to_config().allowlist, vec![member.clone()]);
+
+        // Denylist wins over allowlist
+        policy.allow(&banned).unwrap();
+        assert!(policy.is_allowed(&banned_key));
+        policy.deny(&banned).unwrap();
+        assert!(!policy.is_allowed(&banned_key));
+
+        assert!(policy.remove_denied(&banned).unwrap());
+        assert!(policy.remove_allowed(&member).unwrap());
+        assert!(!policy.remove_allowed(&member).unwrap());
+        assert!(!policy.is_allowed(&member_key));
+    }
+
+    #[test]
+    fn test_removing_last_allowlisted_npub_keeps_mint_private() {
+        let member = npub();
+        let policy = AccessPolicy::from_config(&AccessConfig {
+            mode: AccessMode::Allowlist,
+            allowlist: vec![member.clone()],
+            denylist: vec![],
+        });
+
+        assert!(policy.remove_allowed(&member).unwrap());
+        assert!(!policy.is_allowed(&PublicKey::from_bech32(&member).unwrap()));
+        assert!(!policy.is_allowed(&nostr::Keys::generate().public_key()));
+        assert_eq!(policy.to_config().mode, AccessMode::Allowlist);
+
+        // An open mint serves everyone who is not denylisted
+        let policy = AccessPolicy::from_config(&AccessConfig {
+            mode: AccessMode::Open,
+            allowlist: vec![member.clone()],
+            denylist: vec![],
+        });
+        assert!(policy.is_allowed(&nostr::Keys::generate().public_key()));
+    }
+
+    #[test]
+    fn test_rejects_invalid_npub() {
+        let policy = AccessPolicy::default();
+        assert!(policy.allow("invalid").is_err());
+        assert!(policy.deny(&nostr::Keys::generate().public_key().to_hex()).is_err());
+    }
+}
@@ -1,8 +1,12 @@
 // Path import removed - not needed for basic Android functionality
-use cdk::nuts::{CurrencyUnit, PublicKey};
+use cdk::nuts::{CurrencyUnit, PaymentMethod, PublicKey};
 use cdk::Amount;
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::nip74_service::{OperationMethod, DEFAULT_MAX_CONCURRENT_REQUESTS};
 
 // =============================================================================
 // Tor Configuration
@@ -139,6 +143,319 @@ impl TorConfig {
     }
 }
 
+// =============================================================================
+// Nostr Relay Configuration
+// =============================================================================
+
+/// Relays used when no relay set is configured
+pub const DEFAULT_RELAYS: &[&str] = &[
+    "wss://relay.damus.io",
+    "wss://nos.lol",
+    "wss://relay.primal.net",
+];
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_network() -> String {
+    "mainnet".to_string()
+}
+
+fn default_reconnect_max_delay() -> u64 {
+    300
+}
+
+fn default_announcement_interval() -> u64 {
+    3600
+}
+
+fn default_replay_window() -> u64 {
+    300
+}
+
+fn default_max_concurrent_requests() -> usize {
+    DEFAULT_MAX_CONCURRENT_REQUESTS
+}
+
+/// A single Nostr relay and the directions it is used for
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RelayEntry {
+    /// Relay websocket URL
+    pub url: String,
+    /// Subscribe to incoming requests on this relay
+    #[serde(default = "default_true")]
+    pub read: bool,
+    /// Publish replies and announcements to this relay
+    #[serde(default = "default_true")]
+    pub write: bool,
+}
+
+impl RelayEntry {
+    /// Create a relay entry used for both reading and writing
+    pub fn read_write(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            read: true,
+            write: true,
+        }
+    }
+}
+
+/// Nostr relay set and reconnect policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayConfig {
+    /// Relays to connect to
+    pub relays: Vec<RelayEntry>,
+    /// Delay before the first reconnect attempt in seconds
+    pub reconnect_initial_delay: u64,
+    /// Upper bound for the reconnect delay in seconds
+    pub reconnect_max_delay: u64,
+    /// Interval between relay health checks in seconds
+    pub health_check_interval: u64,
+}
+
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            relays: DEFAULT_RELAYS.iter().map(|url| RelayEntry::read_write(url)).collect(),
+            reconnect_initial_delay: 2,
+            reconnect_max_delay: default_reconnect_max_delay(),
+            health_check_interval: 15,
+        }
+    }
+}
+
+impl RelayConfig {
+    /// Relays used for subscriptions
+    pub fn read_relays(&self) -> impl Iterator<Item = &RelayEntry> {
+        self.relays.iter().filter(|r| r.read)
+    }
+
+    /// Relays used for publishing
+    pub fn write_relays(&self) -> impl Iterator<Item = &RelayEntry> {
+        self.relays.iter().filter(|r| r.write)
+    }
+
+    /// Reconnect delay after `failures` consecutive failed attempts (exponential backoff)
+    pub fn backoff_delay(&self, failures: u32) -> std::time::Duration {
+        let initial = self.reconnect_initial_delay.max(1);
+        let delay = initial.saturating_mul(1u64 << failures.min(16));
+        std::time::Duration::from_secs(delay.min(self.reconnect_max_delay.max(initial)))
+    }
+}
+
+// =============================================================================
+// NIP-74 Configuration
+// =============================================================================
+
+/// Shortest interval between kind:37400 announcement refreshes in seconds
+pub const MIN_ANNOUNCEMENT_INTERVAL: u64 = 60;
+
+/// NIP-74 runtime options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Nip74Config {
+    /// `d` identifier of the kind:37400 announcement (defaults to the mint pubkey)
+    pub identifier: Option<String>,
+    /// Interval between kind:37400 announcement refreshes in seconds, at least
+    /// [`MIN_ANNOUNCEMENT_INTERVAL`]
+    #[serde(default = "default_announcement_interval")]
+    pub announcement_interval: u64,
+    /// Maximum age (and clock skew) of accepted request events in seconds
+    #[serde(default = "default_replay_window")]
+    pub replay_window: u64,
+    /// Existing Cashu mint to front over NIP-74 instead of the in-process mint
+    pub upstream_url: Option<String>,
+    /// SOCKS5 proxy for reaching the upstream mint, required for onion URLs
+    pub upstream_proxy: Option<String>,
+    /// Minimum NIP-13 difficulty of request events, checked on the outer gift wrap
+    /// for wrapped requests (0 disables the check)
+    #[serde(default)]
+    pub min_pow: u8,
+    /// Requests processed at once; further requests are dropped until a slot frees up
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Also publish a NIP-87 (kind:38172) announcement for mints with an HTTP URL
+    #[serde(default = "default_true")]
+    pub nip87: bool,
+    /// Bitcoin network advertised in the NIP-87 announcement
+    #[serde(default = "default_network")]
+    pub network: String,
+}
+
+impl Default for Nip74Config {
+    fn default() -> Self {
+        Self {
+            identifier: None,
+            announcement_interval: default_announcement_interval(),
+            replay_window: default_replay_window(),
+            upstream_url: None,
+            upstream_proxy: None,
+            min_pow: 0,
+            max_concurrent_requests: default_max_concurrent_requests(),
+            nip87: true,
+            network: default_network(),
+        }
+    }
+}
+
+/// Minimal Nostr relay for NIP-74 traffic served by the mint's HTTP server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EmbeddedRelayConfig {
+    pub enabled: bool,
+    /// HTTP path of the relay websocket
+    pub path: String,
+    /// Maximum number of stored events; when full the oldest are evicted, except
+    /// those signed by the mint
+    pub max_events: usize,
+    /// Maximum number of stored events per author, the mint excepted. Gift wraps,
+    /// whose authors are one-time keys, are capped per recipient by dropping the
+    /// recipient's oldest wrap instead
+    pub max_events_per_pubkey: usize,
+    /// How long stored events are kept in seconds
+    pub retention_secs: u64,
+}
+
+impl Default for EmbeddedRelayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "/relay".to_string(),
+            max_events: 10_000,
+            max_events_per_pubkey: 500,
+            // Gift wraps are backdated by up to two days
+            retention_secs: 2 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Local HTTP gateway to a remote NIP-74 mint (JSON as passed over JNI)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GatewayConfig {
+    /// Host the `/v1/*` routes are served on
+    pub listen_host: String,
+    pub listen_port: u16,
+    /// Remote mint pubkey (npub or hex)
+    pub mint_npub: String,
+    /// Relays the remote mint listens on
+    pub relays: Vec<String>,
+    /// Send requests NIP-59 gift-wrapped
+    pub gift_wrap: bool,
+    /// Seconds to wait for a reply before re-sending a request
+    pub timeout_secs: u64,
+    /// Request difficulty (defaults to the one in the mint announcement)
+    pub min_pow: Option<u8>,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            listen_host: "127.0.0.1".to_string(),
+            listen_port: 3339,
+            mint_npub: String::new(),
+            relays: DEFAULT_RELAYS.iter().map(|url| url.to_string()).collect(),
+            gift_wrap: false,
+            timeout_secs: 30,
+            min_pow: None,
+        }
+    }
+}
+
+impl GatewayConfig {
+    /// Create GatewayConfig from JSON string
+    pub fn from_json(json_str: &str) -> Result<Self> {
+        serde_json::from_str(json_str)
+            .map_err(|e| anyhow!("Failed to parse gateway config JSON: {}", e))
+    }
+}
+
+/// Who a mint serves
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessMode {
+    /// Every client that is not denylisted
+    #[default]
+    Open,
+    /// Only allowlisted clients; nobody while the allowlist is empty
+    Allowlist,
+}
+
+/// Client access policy of a private mint (npubs)
+///
+/// Applies to NIP-74 requests only; the HTTP API of `MintdAndNip74` mode is not
+/// restricted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccessConfig {
+    #[serde(default)]
+    pub mode: AccessMode,
+    /// Only these clients are served in [`AccessMode::Allowlist`]
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+    /// These clients are never served
+    #[serde(default)]
+    pub denylist: Vec<String>,
+}
+
+/// Token bucket of a single operation: `capacity` requests in a burst,
+/// refilled by one request every `refill_secs` seconds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub refill_secs: u64,
+}
+
+impl RateLimit {
+    pub fn new(capacity: u32, refill_secs: u64) -> Self {
+        Self { capacity, refill_secs }
+    }
+}
+
+/// Per-author rate limits and quotas on NIP-74 operations
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RateLimitConfig {
+    /// Enforce the limits below; on unless explicitly disabled
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Limit for operations without an explicit entry in `methods`
+    pub default_limit: Option<RateLimit>,
+    /// Limits per operation
+    #[serde(default)]
+    pub methods: HashMap<OperationMethod, RateLimit>,
+    /// Maximum amount in sat an author may mint per UTC day, counted on BOLT11
+    /// quotes and on BOLT12 mints; amounts in msat are converted
+    pub daily_mint_amount: Option<u64>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        // Quote requests create a backend invoice and a database row each
+        let methods = HashMap::from([
+            (OperationMethod::GetMintQuote, RateLimit::new(10, 30)),
+            (OperationMethod::GetMeltQuote, RateLimit::new(10, 30)),
+            (OperationMethod::GetMintQuoteBolt12, RateLimit::new(10, 30)),
+            (OperationMethod::GetMeltQuoteBolt12, RateLimit::new(10, 30)),
+        ]);
+
+        Self {
+            enabled: true,
+            default_limit: Some(RateLimit::new(60, 1)),
+            methods,
+            daily_mint_amount: None,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Limit applied to `method`, if any
+    pub fn limit_for(&self, method: &OperationMethod) -> Option<RateLimit> {
+        self.methods.get(method).copied().or(self.default_limit)
+    }
+}
+
 // Lightning backend configuration removed - not needed for basic Android functionality
 
 // =============================================================================
@@ -163,6 +480,18 @@ impl Default for ServiceMode {
     }
 }
 
+impl ServiceMode {
+    /// Whether the mintd HTTP API should be served
+    pub fn runs_mintd(&self) -> bool {
+        matches!(self, ServiceMode::MintdOnly | ServiceMode::MintdAndNip74)
+    }
+
+    /// Whether the NIP-74 relay listener should be started
+    pub fn runs_nip74(&self) -> bool {
+        matches!(self, ServiceMode::Nip74Only | ServiceMode::MintdAndNip74)
+    }
+}
+
 // Display implementation removed - not needed for basic functionality
 
 // =============================================================================
@@ -180,7 +509,7 @@ pub struct Info {
     pub input_fee_ppk: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum LnBackend {
     #[default]
@@ -189,6 +518,8 @@ pub enum LnBackend {
     LNbits,
     Cln,
     Lnd,
+    Nwc,
+    Phoenixd,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -275,6 +606,161 @@ impl Default for Cln {
     }
 }
 
+/// LND REST API connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lnd {
+    /// REST endpoint, e.g. `https://127.0.0.1:8080`
+    pub address: String,
+    /// TLS certificate of the node (`tls.cert`); system roots are used when unset
+    pub cert_file: Option<String>,
+    /// Macaroon authorizing invoices and payments (`admin.macaroon`)
+    pub macaroon_file: String,
+    pub fee_percent: f32,
+    pub reserve_fee_min: Amount,
+}
+
+impl Default for Lnd {
+    fn default() -> Self {
+        Self {
+            address: "https://127.0.0.1:8080".to_string(),
+            cert_file: None,
+            macaroon_file: String::new(),
+            fee_percent: 0.02,
+            reserve_fee_min: 2.into(),
+        }
+    }
+}
+
+fn default_nwc_max_fee_base() -> Amount {
+    10.into()
+}
+
+fn default_nwc_max_fee_ppm() -> u64 {
+    10_000
+}
+
+/// Nostr Wallet Connect (NIP-47) wallet
+///
+/// NIP-47 payments carry no fee limit, so quotes reserve the most the wallet
+/// spends on fees per its fee policy (`max_fee_base` plus `max_fee_ppm`) and
+/// melts whose reserve is lower are refused. A wallet spending more than that
+/// is still paid by the mint; issue the connection with a tight budget.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Nwc {
+    /// `nostr+walletconnect://` connection string issued by the wallet
+    pub uri: String,
+    pub fee_percent: f32,
+    pub reserve_fee_min: Amount,
+    /// Base of the highest routing fee the wallet pays, in sat
+    #[serde(default = "default_nwc_max_fee_base")]
+    pub max_fee_base: Amount,
+    /// Proportional part of the highest routing fee the wallet pays, in parts per million
+    #[serde(default = "default_nwc_max_fee_ppm")]
+    pub max_fee_ppm: u64,
+}
+
+impl Default for Nwc {
+    fn default() -> Self {
+        Self {
+            uri: String::new(),
+            fee_percent: 0.02,
+            reserve_fee_min: 2.into(),
+            max_fee_base: default_nwc_max_fee_base(),
+            max_fee_ppm: default_nwc_max_fee_ppm(),
+        }
+    }
+}
+
+/// Phoenixd HTTP API connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Phoenixd {
+    /// HTTP API endpoint, e.g. `http://127.0.0.1:9740`
+    pub api_url: String,
+    /// `http-password` from phoenix.conf
+    pub api_password: String,
+    pub fee_percent: f32,
+    pub reserve_fee_min: Amount,
+}
+
+impl Default for Phoenixd {
+    fn default() -> Self {
+        Self {
+            api_url: "http://127.0.0.1:9740".to_string(),
+            api_password: String::new(),
+            fee_percent: 0.02,
+            reserve_fee_min: 2.into(),
+        }
+    }
+}
+
+fn default_payment_method() -> PaymentMethod {
+    PaymentMethod::Bolt11
+}
+
+/// Backends serving one currency unit and payment method
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackendRoute {
+    pub unit: CurrencyUnit,
+    #[serde(default = "default_payment_method")]
+    pub method: PaymentMethod,
+    /// Backend used while it passes its health check
+    pub primary: LnBackend,
+    /// Backends tried in order when the primary is unhealthy
+    #[serde(default)]
+    pub fallbacks: Vec<LnBackend>,
+}
+
+impl BackendRoute {
+    /// Primary followed by the fallbacks, without duplicates
+    pub fn chain(&self) -> Vec<LnBackend> {
+        let mut chain = vec![self.primary.clone()];
+        for backend in &self.fallbacks {
+            if !chain.contains(backend) {
+                chain.push(backend.clone());
+            }
+        }
+        chain
+    }
+}
+
+/// Routing of currency units and payment methods to Lightning backends
+///
+/// Without routes every configured backend is registered for the units it supports,
+/// and a unit served by several backends is an error until a route picks one.
+/// In a fallback chain, LND, phoenixd, NWC and LNbits backends are health-probed
+/// with a node info call at most once per `retry_interval`, when a request needs
+/// the chain, and skipped while the probe fails. Errors of client requests never
+/// fail a backend over. Backends without a probe count as healthy.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub routes: Vec<BackendRoute>,
+    /// Seconds between health probes of a backend
+    pub retry_interval: u64,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            routes: Vec::new(),
+            retry_interval: 30,
+        }
+    }
+}
+
+impl RoutingConfig {
+    /// Backends referenced by any route
+    pub fn backends(&self) -> Vec<LnBackend> {
+        let mut backends = Vec::new();
+        for backend in self.routes.iter().flat_map(BackendRoute::chain) {
+            if !backends.contains(&backend) {
+                backends.push(backend);
+            }
+        }
+        backends
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum DatabaseEngine {
@@ -308,9 +794,27 @@ pub struct Settings {
     pub fake_wallet: Option<FakeWallet>,
     pub lnbits: Option<LNbits>,
     pub cln: Option<Cln>,
+    #[serde(default)]
+    pub lnd: Option<Lnd>,
+    #[serde(default)]
+    pub nwc: Option<Nwc>,
+    #[serde(default)]
+    pub phoenixd: Option<Phoenixd>,
+    #[serde(default)]
+    pub routing: RoutingConfig,
     pub database: Database,
     pub service_mode: ServiceMode,
     pub tor: TorConfig,
+    #[serde(default)]
+    pub relays: RelayConfig,
+    #[serde(default)]
+    pub nip74: Nip74Config,
+    #[serde(default)]
+    pub access: AccessConfig,
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+    #[serde(default)]
+    pub embedded_relay: EmbeddedRelayConfig,
 }
 
 // =============================================================================
@@ -334,6 +838,15 @@ pub struct AndroidConfig {
     pub lnbits_api_url: Option<String>,
     pub cln_rpc_path: Option<String>,
     pub cln_bolt12: Option<bool>,
+    pub lnd_address: Option<String>,
+    pub lnd_cert_path: Option<String>,
+    pub lnd_macaroon_path: Option<String>,
+    pub nwc_uri: Option<String>,
+    pub phoenixd_api_url: Option<String>,
+    pub phoenixd_api_password: Option<String>,
+    // Backend routing and failover
+    pub ln_routes: Option<Vec<BackendRoute>>,
+    pub ln_retry_interval: Option<u64>,
     // Tor configuration
     pub tor_enabled: Option<bool>,
     pub tor_mode: Option<String>,
@@ -343,6 +856,29 @@ pub struct AndroidConfig {
     pub tor_num_intro_points: Option<u32>,
     pub tor_bridges: Option<Vec<String>>,
     pub tor_use_bridges: Option<bool>,
+    // Nostr relay configuration
+    pub nostr_relays: Option<Vec<RelayEntry>>,
+    pub relay_reconnect_delay: Option<u64>,
+    pub relay_reconnect_max_delay: Option<u64>,
+    // NIP-74 configuration
+    pub nip74_announcement_interval: Option<u64>,
+    pub nip74_replay_window: Option<u64>,
+    pub nip74_upstream_url: Option<String>,
+    pub nip74_upstream_proxy: Option<String>,
+    pub nip74_min_pow: Option<u8>,
+    pub nip87_enabled: Option<bool>,
+    pub bitcoin_network: Option<String>,
+    // Access policy (npubs)
+    pub access_mode: Option<AccessMode>,
+    pub allowed_npubs: Option<Vec<String>>,
+    pub denied_npubs: Option<Vec<String>>,
+    // Rate limits
+    pub rate_limit_enabled: Option<bool>,
+    pub rate_limits: Option<HashMap<OperationMethod, RateLimit>>,
+    pub daily_mint_amount: Option<u64>,
+    // Embedded relay
+    pub embedded_relay_enabled: Option<bool>,
+    pub embedded_relay_path: Option<String>,
 }
 
 impl Default for AndroidConfig {
@@ -361,6 +897,14 @@ impl Default for AndroidConfig {
             lnbits_api_url: None,
             cln_rpc_path: None,
             cln_bolt12: None,
+            lnd_address: None,
+            lnd_cert_path: None,
+            lnd_macaroon_path: None,
+            nwc_uri: None,
+            phoenixd_api_url: None,
+            phoenixd_api_password: None,
+            ln_routes: None,
+            ln_retry_interval: None,
             // Tor defaults
             tor_enabled: Some(false),
             tor_mode: Some("disabled".to_string()),
@@ -370,6 +914,26 @@ impl Default for AndroidConfig {
             tor_num_intro_points: Some(3),
             tor_bridges: None,
             tor_use_bridges: Some(false),
+            // Relay defaults
+            nostr_relays: None,
+            relay_reconnect_delay: None,
+            relay_reconnect_max_delay: None,
+            // NIP-74 defaults
+            nip74_announcement_interval: None,
+            nip74_replay_window: None,
+            nip74_upstream_url: None,
+            nip74_upstream_proxy: None,
+            nip74_min_pow: None,
+            nip87_enabled: None,
+            bitcoin_network: None,
+            access_mode: None,
+            allowed_npubs: None,
+            denied_npubs: None,
+            rate_limit_enabled: None,
+            rate_limits: None,
+            daily_mint_amount: None,
+            embedded_relay_enabled: None,
+            embedded_relay_path: None,
         }
     }
 }
@@ -408,6 +972,8 @@ impl Settings {
             engine: DatabaseEngine::Sqlite,
         };
         let tor = TorConfig::default();
+        let relays = RelayConfig::default();
+        let nip74 = Nip74Config::default();
 
         Settings {
             info,
@@ -416,9 +982,18 @@ impl Settings {
             fake_wallet: Some(FakeWallet::default()),
             lnbits: None,
             cln: None,
+            lnd: None,
+            nwc: None,
+            phoenixd: None,
+            routing: RoutingConfig::default(),
             database,
             service_mode: ServiceMode::default(),
             tor,
+            relays,
+            nip74,
+            access: AccessConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            embedded_relay: EmbeddedRelayConfig::default(),
         }
     }
 
@@ -441,6 +1016,9 @@ impl AndroidConfig {
             "fake" | "fakewallet" => LnBackend::FakeWallet,
             "lnbits" => LnBackend::LNbits,
             "cln" => LnBackend::Cln,
+            "lnd" => LnBackend::Lnd,
+            "nwc" => LnBackend::Nwc,
+            "phoenixd" => LnBackend::Phoenixd,
             _ => LnBackend::None,
         };
         
@@ -451,54 +1029,298 @@ impl AndroidConfig {
             }
             "lnbits" => {
                 // Set LNBits configuration
-                if let (Some(admin_key), Some(invoice_key), Some(api_url)) = (
-                    &self.lnbits_admin_api_key,
-                    &self.lnbits_invoice_api_key,
-                    &self.lnbits_api_url
-                ) {
-                    settings.lnbits = Some(LNbits {
-                        admin_api_key: admin_key.clone(),
-                        invoice_api_key: invoice_key.clone(),
-                        lnbits_api: api_url.clone(),
-                        fee_percent: 0.02,
-                        reserve_fee_min: 1.into(),
-                    });
+                if let Some(lnbits) = self.to_lnbits_config() {
+                    settings.lnbits = Some(lnbits);
                     // Clear fake wallet config when using LNBits
                     settings.fake_wallet = None;
                 }
             }
             "cln" => {
                 // Set CLN configuration
-                if let Some(rpc_path) = &self.cln_rpc_path {
-                    settings.cln = Some(Cln {
-                        rpc_path: rpc_path.clone(),
-                        bolt12: self.cln_bolt12.unwrap_or(false),
-                        fee_percent: 0.02,
-                        reserve_fee_min: 1.into(),
-                    });
+                if let Some(cln) = self.to_cln_config() {
+                    settings.cln = Some(cln);
                     // Clear fake wallet config when using CLN
                     settings.fake_wallet = None;
                 }
             }
+            "lnd" => {
+                // Set LND configuration
+                if let Some(lnd) = self.to_lnd_config() {
+                    settings.lnd = Some(lnd);
+                    // Clear fake wallet config when using LND
+                    settings.fake_wallet = None;
+                }
+            }
+            "nwc" => {
+                // Set NWC configuration
+                if let Some(nwc) = self.to_nwc_config() {
+                    settings.nwc = Some(nwc);
+                    // Clear fake wallet config when using NWC
+                    settings.fake_wallet = None;
+                }
+            }
+            "phoenixd" => {
+                // Set phoenixd configuration
+                if let Some(phoenixd) = self.to_phoenixd_config() {
+                    settings.phoenixd = Some(phoenixd);
+                    // Clear fake wallet config when using phoenixd
+                    settings.fake_wallet = None;
+                }
+            }
             _ => {
                 // Keep default fake wallet config for unrecognized backends
             }
         }
         
         // Set service mode
-        settings.service_mode = match self.mode.as_str() {
-            "MintdOnly" | "mintd_only" => ServiceMode::MintdOnly,
-            "Nip74Only" | "nip74_only" => ServiceMode::Nip74Only,
-            "MintdAndNip74" | "mintd_and_nip74" => ServiceMode::MintdAndNip74,
-            _ => ServiceMode::MintdOnly,
-        };
+        settings.service_mode = self.to_service_mode();
 
         // Set Tor configuration
         settings.tor = self.to_tor_config();
+
+        // Set relay configuration
+        settings.relays = self.to_relay_config();
+
+        // Set NIP-74 configuration
+        settings.nip74 = self.to_nip74_config();
+        settings.access = self.to_access_config();
+        settings.rate_limits = self.to_rate_limit_config();
+        settings.embedded_relay = self.to_embedded_relay_config();
+
+        // Set backend routing, configuring the fallback backends too
+        settings.routing = self.to_routing_config();
+        self.add_routed_backends(&mut settings);
         
         settings
     }
 
+    /// Convert AndroidConfig to LNbits, if the keys and API URL are configured
+    pub fn to_lnbits_config(&self) -> Option<LNbits> {
+        Some(LNbits {
+            admin_api_key: self.lnbits_admin_api_key.clone()?,
+            invoice_api_key: self.lnbits_invoice_api_key.clone()?,
+            lnbits_api: self.lnbits_api_url.clone()?,
+            fee_percent: 0.02,
+            reserve_fee_min: 1.into(),
+        })
+    }
+
+    /// Convert AndroidConfig to Cln, if an RPC socket is configured
+    pub fn to_cln_config(&self) -> Option<Cln> {
+        Some(Cln {
+            rpc_path: self.cln_rpc_path.clone()?,
+            bolt12: self.cln_bolt12.unwrap_or(false),
+            fee_percent: 0.02,
+            reserve_fee_min: 1.into(),
+        })
+    }
+
+    /// Convert AndroidConfig to RoutingConfig
+    pub fn to_routing_config(&self) -> RoutingConfig {
+        let mut routing_config = RoutingConfig::default();
+
+        if let Some(routes) = &self.ln_routes {
+            routing_config.routes = routes.clone();
+        }
+
+        if let Some(interval) = self.ln_retry_interval {
+            routing_config.retry_interval = interval;
+        }
+
+        routing_config
+    }
+
+    /// Configure the backends that `settings.routing` uses besides the selected one
+    pub fn add_routed_backends(&self, settings: &mut Settings) {
+        for backend in settings.routing.backends() {
+            match backend {
+                LnBackend::FakeWallet if settings.fake_wallet.is_none() => {
+                    settings.fake_wallet = Some(FakeWallet::default());
+                }
+                LnBackend::LNbits if settings.lnbits.is_none() => settings.lnbits = self.to_lnbits_config(),
+                LnBackend::Cln if settings.cln.is_none() => settings.cln = self.to_cln_config(),
+                LnBackend::Lnd if settings.lnd.is_none() => settings.lnd = self.to_lnd_config(),
+                LnBackend::Nwc if settings.nwc.is_none() => settings.nwc = self.to_nwc_config(),
+                LnBackend::Phoenixd if settings.phoenixd.is_none() => {
+                    settings.phoenixd = self.to_phoenixd_config();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Convert AndroidConfig to Lnd, if an address and macaroon are configured
+    pub fn to_lnd_config(&self) -> Option<Lnd> {
+        let non_empty = |value: &Option<String>| value.as_ref().map(|v| v.trim()).filter(|v| !v.is_empty()).map(str::to_string);
+
+        Some(Lnd {
+            address: non_empty(&self.lnd_address)?,
+            cert_file: non_empty(&self.lnd_cert_path),
+            macaroon_file: non_empty(&self.lnd_macaroon_path)?,
+            fee_percent: 0.02,
+            reserve_fee_min: 1.into(),
+        })
+    }
+
+    /// Convert AndroidConfig to Nwc, if a connection string is configured
+    pub fn to_nwc_config(&self) -> Option<Nwc> {
+        let uri = self.nwc_uri.as_ref().map(|v| v.trim()).filter(|v| !v.is_empty())?;
+
+        Some(Nwc {
+            uri: uri.to_string(),
+            fee_percent: 0.02,
+            reserve_fee_min: 1.into(),
+            ..Default::default()
+        })
+    }
+
+    /// Convert AndroidConfig to Phoenixd, if an API URL and password are configured
+    pub fn to_phoenixd_config(&self) -> Option<Phoenixd> {
+        let non_empty = |value: &Option<String>| value.as_ref().map(|v| v.trim()).filter(|v| !v.is_empty()).map(str::to_string);
+
+        Some(Phoenixd {
+            api_url: non_empty(&self.phoenixd_api_url)?,
+            api_password: non_empty(&self.phoenixd_api_password)?,
+            fee_percent: 0.02,
+            reserve_fee_min: 1.into(),
+        })
+    }
+
+    /// Convert AndroidConfig to Nip74Config
+    pub fn to_nip74_config(&self) -> Nip74Config {
+        let mut nip74_config = Nip74Config::default();
+
+        if let Some(interval) = self.nip74_announcement_interval {
+            if interval < MIN_ANNOUNCEMENT_INTERVAL {
+                warn!(
+                    "NIP-74 announcement interval {}s is below the {}s minimum, using {}s",
+                    interval, MIN_ANNOUNCEMENT_INTERVAL, MIN_ANNOUNCEMENT_INTERVAL
+                );
+            }
+            nip74_config.announcement_interval = interval.max(MIN_ANNOUNCEMENT_INTERVAL);
+        }
+
+        if let Some(window) = self.nip74_replay_window {
+            nip74_config.replay_window = window;
+        }
+
+        nip74_config.upstream_url = self
+            .nip74_upstream_url
+            .as_ref()
+            .filter(|url| !url.trim().is_empty())
+            .cloned();
+        nip74_config.upstream_proxy = self.nip74_upstream_proxy.clone();
+
+        if let Some(min_pow) = self.nip74_min_pow {
+            nip74_config.min_pow = min_pow;
+        }
+
+        if let Some(enabled) = self.nip87_enabled {
+            nip74_config.nip87 = enabled;
+        }
+
+        if let Some(network) = self.bitcoin_network.as_ref().filter(|network| !network.trim().is_empty()) {
+            nip74_config.network = network.trim().to_string();
+        }
+
+        nip74_config
+    }
+
+    /// Convert AndroidConfig to AccessConfig
+    pub fn to_access_config(&self) -> AccessConfig {
+        let clean = |npubs: &Option<Vec<String>>| -> Vec<String> {
+            npubs
+                .iter()
+                .flatten()
+                .map(|npub| npub.trim().to_string())
+                .filter(|npub| !npub.is_empty())
+                .collect()
+        };
+
+        let allowlist = clean(&self.allowed_npubs);
+        // Configs from before the explicit mode were private whenever they listed npubs
+        let mode = self.access_mode.unwrap_or(if allowlist.is_empty() {
+            AccessMode::Open
+        } else {
+            AccessMode::Allowlist
+        });
+
+        AccessConfig {
+            mode,
+            allowlist,
+            denylist: clean(&self.denied_npubs),
+        }
+    }
+
+    /// Convert AndroidConfig to RateLimitConfig
+    pub fn to_rate_limit_config(&self) -> RateLimitConfig {
+        let mut rate_limit_config = RateLimitConfig::default();
+
+        if let Some(enabled) = self.rate_limit_enabled {
+            rate_limit_config.enabled = enabled;
+        }
+
+        if let Some(limits) = &self.rate_limits {
+            rate_limit_config.methods.extend(limits.iter().map(|(method, limit)| (method.clone(), *limit)));
+        }
+
+        rate_limit_config.daily_mint_amount = self.daily_mint_amount;
+
+        rate_limit_config
+    }
+
+    /// Convert AndroidConfig to EmbeddedRelayConfig
+    pub fn to_embedded_relay_config(&self) -> EmbeddedRelayConfig {
+        let mut embedded_relay_config = EmbeddedRelayConfig::default();
+
+        if let Some(enabled) = self.embedded_relay_enabled {
+            embedded_relay_config.enabled = enabled;
+        }
+
+        if let Some(path) = self.embedded_relay_path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+            embedded_relay_config.path = if path.starts_with('/') {
+                path.to_string()
+            } else {
+                format!("/{}", path)
+            };
+        }
+
+        embedded_relay_config
+    }
+
+    /// Convert AndroidConfig to RelayConfig
+    pub fn to_relay_config(&self) -> RelayConfig {
+        let mut relay_config = RelayConfig::default();
+
+        if let Some(relays) = &self.nostr_relays {
+            relay_config.relays = relays
+                .iter()
+                .filter(|r| !r.url.trim().is_empty())
+                .cloned()
+                .collect();
+        }
+
+        if let Some(delay) = self.relay_reconnect_delay {
+            relay_config.reconnect_initial_delay = delay;
+        }
+
+        if let Some(max_delay) = self.relay_reconnect_max_delay {
+            relay_config.reconnect_max_delay = max_delay;
+        }
+
+        relay_config
+    }
+
+    /// Parse the `mode` string into a ServiceMode
+    pub fn to_service_mode(&self) -> ServiceMode {
+        match self.mode.as_str() {
+            "MintdOnly" | "mintd_only" => ServiceMode::MintdOnly,
+            "Nip74Only" | "nip74_only" => ServiceMode::Nip74Only,
+            "MintdAndNip74" | "mintd_and_nip74" => ServiceMode::MintdAndNip74,
+            _ => ServiceMode::MintdOnly,
+        }
+    }
+
     /// Convert AndroidConfig to TorConfig
     pub fn to_tor_config(&self) -> TorConfig {
         let startup_mode = if let Some(enabled) = self.tor_enabled {
@@ -577,6 +1399,120 @@ mod tests {
         assert_eq!(lnbits_config.lnbits_api, "https://lnbits.example.com");
     }
 
+    #[test]
+    fn test_lnd_config() {
+        let mut config = AndroidConfig::default();
+        config.lightning_backend = "lnd".to_string();
+        config.lnd_address = Some("https://127.0.0.1:8080".to_string());
+        config.lnd_macaroon_path = Some("/data/lnd/admin.macaroon".to_string());
+        config.lnd_cert_path = Some(" ".to_string());
+
+        let settings = config.to_settings(None);
+        assert_eq!(settings.ln.ln_backend, LnBackend::Lnd);
+        assert!(settings.fake_wallet.is_none());
+
+        let lnd_config = settings.lnd.unwrap();
+        assert_eq!(lnd_config.address, "https://127.0.0.1:8080");
+        assert_eq!(lnd_config.macaroon_file, "/data/lnd/admin.macaroon");
+        assert_eq!(lnd_config.cert_file, None);
+
+        // Without a macaroon LND is not configured
+        config.lnd_macaroon_path = None;
+        assert!(config.to_settings(None).lnd.is_none());
+    }
+
+    #[test]
+    fn test_nwc_config() {
+        let mut config = AndroidConfig::default();
+        config.lightning_backend = "nwc".to_string();
+        config.nwc_uri = Some(" nostr+walletconnect://b889ff5b?relay=wss://relay.example.com&secret=71a8c14c ".to_string());
+
+        let settings = config.to_settings(None);
+        assert_eq!(settings.ln.ln_backend, LnBackend::Nwc);
+        assert!(settings.fake_wallet.is_none());
+        assert_eq!(
+            settings.nwc.unwrap().uri,
+            "nostr+walletconnect://b889ff5b?relay=wss://relay.example.com&secret=71a8c14c"
+        );
+
+        // Settings written before the fee cap existed get the default one
+        let nwc: Nwc = serde_json::from_value(serde_json::json!({
+            "uri": "nostr+walletconnect://b889ff5b",
+            "fee_percent": 0.02,
+            "reserve_fee_min": 1,
+        }))
+        .unwrap();
+        assert_eq!(nwc.max_fee_base, Amount::from(10));
+        assert_eq!(nwc.max_fee_ppm, 10_000);
+
+        config.nwc_uri = Some(String::new());
+        assert!(config.to_settings(None).nwc.is_none());
+    }
+
+    #[test]
+    fn test_phoenixd_config() {
+        let mut config = AndroidConfig::default();
+        config.lightning_backend = "phoenixd".to_string();
+        config.phoenixd_api_url = Some("http://127.0.0.1:9740".to_string());
+        config.phoenixd_api_password = Some("secret".to_string());
+
+        let settings = config.to_settings(None);
+        assert_eq!(settings.ln.ln_backend, LnBackend::Phoenixd);
+        assert!(settings.fake_wallet.is_none());
+        let phoenixd_config = settings.phoenixd.unwrap();
+        assert_eq!(phoenixd_config.api_url, "http://127.0.0.1:9740");
+        assert_eq!(phoenixd_config.api_password, "secret");
+
+        // Without a password phoenixd is not configured
+        config.phoenixd_api_password = None;
+        assert!(config.to_settings(None).phoenixd.is_none());
+    }
+
+    #[test]
+    fn test_announcement_interval_minimum() {
+        let mut config = AndroidConfig::default();
+        config.nip74_announcement_interval = Some(5);
+        assert_eq!(config.to_nip74_config().announcement_interval, MIN_ANNOUNCEMENT_INTERVAL);
+
+        config.nip74_announcement_interval = Some(600);
+        assert_eq!(config.to_nip74_config().announcement_interval, 600);
+    }
+
+    #[test]
+    fn test_routing_config() {
+        let json_str = r#"{
+            "port": 3338,
+            "host": "0.0.0.0",
+            "mintName": "Test Mint",
+            "description": "Test Description",
+            "lightningBackend": "lnbits",
+            "mode": "mintd_only",
+            "databasePath": "/tmp/db",
+            "logsPath": "/tmp/logs",
+            "lnbitsAdminApiKey": "admin_key_123",
+            "lnbitsInvoiceApiKey": "invoice_key_456",
+            "lnbitsApiUrl": "https://lnbits.example.com",
+            "phoenixdApiUrl": "http://127.0.0.1:9740",
+            "phoenixdApiPassword": "secret",
+            "lnRoutes": [
+                { "unit": "sat", "primary": "lnbits", "fallbacks": ["phoenixd", "lnbits"] }
+            ],
+            "lnRetryInterval": 10
+        }"#;
+
+        let config = AndroidConfig::from_json(json_str).expect("Failed to parse JSON");
+        let settings = config.to_settings(None);
+        assert_eq!(settings.routing.retry_interval, 10);
+        let route = &settings.routing.routes[0];
+        assert_eq!(route.method, PaymentMethod::Bolt11);
+        assert_eq!(route.chain(), vec![LnBackend::LNbits, LnBackend::Phoenixd]);
+
+        // Fallback backends are configured next to the selected one
+        assert!(settings.lnbits.is_some());
+        assert!(settings.phoenixd.is_some());
+        assert!(settings.lnd.is_none());
+    }
+
     #[test]
     fn test_android_json_parsing() {
         let json_str = r#"{
@@ -627,6 +1563,81 @@ mod tests {
         assert_eq!(tor_config.socks_port, Some(9050));
     }
 
+    #[test]
+    fn test_service_mode_parsing() {
+        let mut config = AndroidConfig::default();
+        assert_eq!(config.to_service_mode(), ServiceMode::MintdOnly);
+
+        config.mode = "nip74_only".to_string();
+        let mode = config.to_settings(None).service_mode;
+        assert_eq!(mode, ServiceMode::Nip74Only);
+        assert!(mode.runs_nip74());
+        assert!(!mode.runs_mintd());
+
+        config.mode = "MintdAndNip74".to_string();
+        let mode = config.to_service_mode();
+        assert!(mode.runs_nip74());
+        assert!(mode.runs_mintd());
+    }
+
+    #[test]
+    fn test_relay_config() {
+        let json_str = r#"{
+            "port": 3338,
+            "host": "0.0.0.0",
+            "mintName": "Test Mint",
+            "description": "Test Description",
+            "lightningBackend": "fakewallet",
+            "mode": "nip74_only",
+            "databasePath": "/tmp/db",
+            "logsPath": "/tmp/logs",
+            "nostrRelays": [
+                {"url": "wss://relay.example.com"},
+                {"url": "wss://inbox.example.com", "write": false}
+            ],
+            "relayReconnectDelay": 5,
+            "relayReconnectMaxDelay": 60
+        }"#;
+
+        let config = AndroidConfig::from_json(json_str).expect("Failed to parse JSON");
+        let relay_config = config.to_settings(None).relays;
+        assert_eq!(relay_config.relays.len(), 2);
+        assert_eq!(relay_config.reconnect_initial_delay, 5);
+        assert_eq!(relay_config.reconnect_max_delay, 60);
+        assert_eq!(relay_config.read_relays().count(), 2);
+        assert_eq!(relay_config.write_relays().count(), 1);
+
+        // Relays fall back to defaults when not configured
+        let relay_config = AndroidConfig::default().to_relay_config();
+        assert_eq!(relay_config.relays.len(), DEFAULT_RELAYS.len());
+    }
+
+    #[test]
+    fn test_relay_backoff_delay() {
+        let relay_config = RelayConfig {
+            relays: Vec::new(),
+            reconnect_initial_delay: 2,
+            reconnect_max_delay: 30,
+            health_check_interval: 15,
+        };
+
+        assert_eq!(relay_config.backoff_delay(0).as_secs(), 2);
+        assert_eq!(relay_config.backoff_delay(1).as_secs(), 4);
+        assert_eq!(relay_config.backoff_delay(3).as_secs(), 16);
+        assert_eq!(relay_config.backoff_delay(4).as_secs(), 30);
+        assert_eq!(relay_config.backoff_delay(100).as_secs(), 30);
+    }
+
+    #[test]
+    fn test_partial_relay_section() {
+        let relay_config: RelayConfig = toml::from_str(r#"relays = [{ url = "wss://relay.example.com" }]"#).unwrap();
+        let defaults = RelayConfig::default();
+        assert_eq!(relay_config.relays, vec![RelayEntry::read_write("wss://relay.example.com")]);
+        assert_eq!(relay_config.reconnect_initial_delay, defaults.reconnect_initial_delay);
+        assert_eq!(relay_config.reconnect_max_delay, defaults.reconnect_max_delay);
+        assert_eq!(relay_config.health_check_interval, defaults.health_check_interval);
+    }
+
     #[test]
     fn test_tor_disabled() {
         let mut config = AndroidConfig::default();
@@ -637,5 +1648,68 @@ mod tests {
         assert!(!tor_config.is_enabled());
     }
 
+    #[test]
+    fn test_embedded_relay_config() {
+        let mut config = AndroidConfig::default();
+        assert!(!config.to_settings(None).embedded_relay.enabled);
+
+        config.embedded_relay_enabled = Some(true);
+        config.embedded_relay_path = Some("nostr".to_string());
+        let relay_config = config.to_embedded_relay_config();
+        assert!(relay_config.enabled);
+        assert_eq!(relay_config.path, "/nostr");
+    }
+
+    #[test]
+    fn test_access_mode() {
+        let mut config = AndroidConfig::default();
+        assert_eq!(config.to_access_config().mode, AccessMode::Open);
+
+        // Without an explicit mode, listed npubs keep the mint private
+        config.allowed_npubs = Some(vec!["npub1member".to_string()]);
+        assert_eq!(config.to_access_config().mode, AccessMode::Allowlist);
+
+        config.allowed_npubs = Some(vec![]);
+        config.access_mode = Some(AccessMode::Allowlist);
+        assert_eq!(config.to_access_config().mode, AccessMode::Allowlist);
+
+        let json = r#"{"mode": "allowlist", "allowlist": []}"#;
+        let access: AccessConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(access.mode, AccessMode::Allowlist);
+    }
 
-} 
\ No newline at end of file
+    #[test]
+    fn test_settings_without_new_sections() {
+        // Settings files written before the relay, NIP-74 and LND sections existed still load
+        let mut value = serde_json::to_value(Settings::default_with_mnemonic(None)).unwrap();
+        let sections = value.as_object_mut().unwrap();
+        for section in ["lnd", "relays", "nip74"] {
+            sections.remove(section);
+        }
+
+        let settings: Settings = serde_json::from_value(value).unwrap();
+        assert!(settings.lnd.is_none());
+        assert_eq!(settings.relays.relays.len(), DEFAULT_RELAYS.len());
+        assert_eq!(settings.nip74.min_pow, Nip74Config::default().min_pow);
+    }
+
+    #[test]
+    fn test_partial_nip74_section() {
+        let nip74: Nip74Config = toml::from_str("min_pow = 8").unwrap();
+        let defaults = Nip74Config::default();
+        assert_eq!(nip74.min_pow, 8);
+        assert_eq!(nip74.announcement_interval, defaults.announcement_interval);
+        assert_eq!(nip74.replay_window, defaults.replay_window);
+        assert_eq!(nip74.max_concurrent_requests, defaults.max_concurrent_requests);
+        assert!(nip74.nip87);
+    }
+
+    #[test]
+    fn test_partial_rate_limit_section() {
+        let rate_limits: RateLimitConfig = toml::from_str("daily_mint_amount = 100000").unwrap();
+        assert!(rate_limits.enabled);
+        assert_eq!(rate_limits.daily_mint_amount, Some(100_000));
+        assert!(rate_limits.default_limit.is_none());
+        assert!(rate_limits.methods.is_empty());
+    }
+}
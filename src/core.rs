@@ -6,10 +6,11 @@ use std::ffi::{CString, c_char};
 use serde_json::json;
 use tracing::{info, error};
 
-use crate::config::AndroidConfig;
+use crate::config::{AndroidConfig, GatewayConfig};
 use crate::nostr::{nsec_to_npub as nostr_nsec_to_npub};
 use crate::mintd_service::MintdService;
 use crate::tor_service::TorService;
+use crate::gateway_service::GatewayService;
 
 /// Global state for the mint service
 static mut MINT_SERVICE: Option<Arc<Mutex<Option<MintdService>>>> = None;
@@ -17,6 +18,9 @@ static mut MINT_SERVICE: Option<Arc<Mutex<Option<MintdService>>>> = None;
 /// Global state for the Tor service
 static mut TOR_SERVICE: Option<Arc<Mutex<Option<TorService>>>> = None;
 
+/// Global state for the NIP-74 gateway
+static mut GATEWAY_SERVICE: Option<Arc<Mutex<Option<GatewayService>>>> = None;
+
 /// Global runtime for service management
 static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
 
@@ -29,6 +33,9 @@ fn init_globals() {
         if TOR_SERVICE.is_none() {
             TOR_SERVICE = Some(Arc::new(Mutex::new(None)));
         }
+        if GATEWAY_SERVICE.is_none() {
+            GATEWAY_SERVICE = Some(Arc::new(Mutex::new(None)));
+        }
     }
     
     // Initialize runtime if not already done
@@ -170,6 +177,7 @@ pub fn start_android_service(config: &AndroidConfig, nsec: &str) -> Result<(), S
     }
     
     // Start Tor service if enabled
+    let mut onion_address: Option<String> = None;
     if config.tor_enabled.unwrap_or(false) {
         info!("Starting Tor service...");
         let tor_config = config.to_tor_config();
@@ -203,7 +211,7 @@ pub fn start_android_service(config: &AndroidConfig, nsec: &str) -> Result<(), S
         if config.tor_enable_hidden_services.unwrap_or(false) {
             info!("Creating Tor hidden service...");
             let rt = RUNTIME.get().unwrap();
-            rt.block_on(async {
+            let address = rt.block_on(async {
                 unsafe {
                     if let Some(tor_service_guard) = TOR_SERVICE.as_ref() {
                         if let Ok(guard) = tor_service_guard.lock() {
@@ -213,7 +221,7 @@ pub fn start_android_service(config: &AndroidConfig, nsec: &str) -> Result<(), S
                                 match tor_service.create_hidden_service(&nickname).await {
                                     Ok(info) => {
                                         info!("Hidden service created: {}", info.onion_address);
-                                        Ok(())
+                                        Ok(info.onion_address)
                                     }
                                     Err(e) => {
                                         error!("Failed to create hidden service: {}", e);
@@ -231,11 +239,13 @@ pub fn start_android_service(config: &AndroidConfig, nsec: &str) -> Result<(), S
                     }
                 }
             })?;
+            onion_address = Some(address);
         }
     }
     
     // Create and start mint service using global runtime
     let mut mint_service = MintdService::new_with_android_config(config_path, config, nsec.to_string());
+    mint_service.set_onion_url(onion_address.map(|address| format!("http://{}", address)));
     
     let rt = RUNTIME.get().unwrap();
     rt.block_on(async move {
@@ -301,10 +311,196 @@ pub fn get_service_status() -> String {
     
     json!({
         "running": false,
-        "details": "Service not initialized"
+        "details": "Service not initialized",
+        "relays": []
     }).to_string()
 }
 
+/// Toggle maintenance status in the NIP-74 mint announcement
+pub fn set_maintenance_mode(maintenance: bool) -> Result<(), String> {
+    init_globals();
+
+    unsafe {
+        if let Some(service_guard) = MINT_SERVICE.as_ref() {
+            if let Ok(guard) = service_guard.lock() {
+                if let Some(service) = guard.as_ref() {
+                    service.set_maintenance(maintenance);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    Err("Service not running".to_string())
+}
+
+/// Which access list of the running mint to modify
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessList {
+    Allow,
+    Deny,
+}
+
+/// Add an npub to the allowlist or denylist of the running mint
+///
+/// Runtime changes are not written back to the configuration; the returned
+/// `accessMode`/`allowedNpubs`/`deniedNpubs` JSON is meant to be saved into
+/// the app's AndroidConfig so the lists survive a restart.
+pub fn add_access_npub(list: AccessList, npub: &str) -> Result<String, String> {
+    with_access_policy(|policy| {
+        match list {
+            AccessList::Allow => policy.allow(npub)?,
+            AccessList::Deny => policy.deny(npub)?,
+        }
+        Ok(access_config_fields(&policy.to_config()))
+    })
+}
+
+/// Remove an npub from the allowlist or denylist of the running mint
+///
+/// Returns the updated lists like [`add_access_npub`].
+pub fn remove_access_npub(list: AccessList, npub: &str) -> Result<String, String> {
+    with_access_policy(|policy| {
+        match list {
+            AccessList::Allow => policy.remove_allowed(npub)?,
+            AccessList::Deny => policy.remove_denied(npub)?,
+        };
+        Ok(access_config_fields(&policy.to_config()))
+    })
+}
+
+/// Access lists as the AndroidConfig fields they are configured with
+fn access_config_fields(config: &crate::config::AccessConfig) -> String {
+    json!({
+        "accessMode": config.mode,
+        "allowedNpubs": config.allowlist,
+        "deniedNpubs": config.denylist,
+    })
+    .to_string()
+}
+
+/// Get the access policy of the running mint as JSON
+pub fn get_access_policy() -> Result<String, String> {
+    with_access_policy(|policy| Ok(json!(policy.to_config()).to_string()))
+}
+
+fn with_access_policy<T>(
+    f: impl FnOnce(&crate::access_policy::AccessPolicy) -> anyhow::Result<T>,
+) -> Result<T, String> {
+    init_globals();
+
+    unsafe {
+        if let Some(service_guard) = MINT_SERVICE.as_ref() {
+            if let Ok(guard) = service_guard.lock() {
+                if let Some(service) = guard.as_ref() {
+                    return f(service.access_policy()).map_err(|e| e.to_string());
+                }
+            }
+        }
+    }
+
+    Err("Service not running".to_string())
+}
+
+// =============================================================================
+// Gateway management
+// =============================================================================
+
+/// Start the local HTTP gateway to a remote NIP-74 mint
+///
+/// Requests are signed with `nsec`, or with fresh keys when it is empty.
+pub fn start_gateway(config_json: &str, nsec: &str) -> Result<(), String> {
+    let config = GatewayConfig::from_json(config_json).map_err(|e| e.to_string())?;
+    let keys = if nsec.is_empty() {
+        nostr::Keys::generate()
+    } else {
+        nostr::Keys::parse(nsec).map_err(|e| format!("Invalid nsec: {}", e))?
+    };
+
+    init_globals();
+    unsafe {
+        let gateway_guard = GATEWAY_SERVICE.as_ref().ok_or("Gateway state not initialized")?;
+        let mut guard = gateway_guard.lock().map_err(|_| "Failed to lock gateway".to_string())?;
+        if guard.as_ref().is_some_and(|gateway| gateway.is_running()) {
+            info!("Gateway is already running");
+            return Ok(());
+        }
+
+        let mut gateway = GatewayService::new(config, keys);
+        let rt = RUNTIME.get().unwrap();
+        rt.block_on(gateway.start())
+            .map_err(|e| format!("Failed to start gateway: {}", e))?;
+        *guard = Some(gateway);
+    }
+
+    Ok(())
+}
+
+/// Stop the NIP-74 gateway
+pub fn stop_gateway() -> Result<(), String> {
+    init_globals();
+
+    unsafe {
+        if let Some(gateway_guard) = GATEWAY_SERVICE.as_ref() {
+            if let Ok(mut guard) = gateway_guard.lock() {
+                if let Some(mut gateway) = guard.take() {
+                    let rt = RUNTIME.get().unwrap();
+                    return rt.block_on(gateway.stop())
+                        .map_err(|e| format!("Failed to stop gateway: {}", e));
+                }
+            }
+        }
+    }
+
+    info!("No running gateway found to stop");
+    Ok(())
+}
+
+/// Get gateway status as JSON
+pub fn get_gateway_status() -> String {
+    init_globals();
+
+    unsafe {
+        if let Some(gateway_guard) = GATEWAY_SERVICE.as_ref() {
+            if let Ok(guard) = gateway_guard.lock() {
+                if let Some(gateway) = guard.as_ref() {
+                    return gateway.get_status().to_string();
+                }
+            }
+        }
+    }
+
+    json!({ "running": false }).to_string()
+}
+
+// =============================================================================
+// Mint discovery
+// =============================================================================
+
+/// Discover NIP-74 mints announced on the relays in `relays_json`
+///
+/// Falls back to the default relays when the list is empty and returns the
+/// ranked mints as a JSON array.
+pub fn discover_mints(relays_json: &str, timeout_secs: u64) -> Result<String, String> {
+    let mut relays: Vec<String> = if relays_json.trim().is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_str(relays_json).map_err(|e| format!("Invalid relay list: {}", e))?
+    };
+    if relays.is_empty() {
+        relays = crate::config::DEFAULT_RELAYS.iter().map(|url| url.to_string()).collect();
+    }
+
+    init_globals();
+    let rt = RUNTIME.get().unwrap();
+    let mints = rt
+        .block_on(crate::discovery::discover_mints(&relays, std::time::Duration::from_secs(timeout_secs)))
+        .map_err(|e| format!("Failed to discover mints: {}", e))?;
+    info!("Discovered {} mints", mints.len());
+
+    serde_json::to_string(&mints).map_err(|e| format!("Failed to serialize mints: {}", e))
+}
+
 /// Get onion address if available
 pub fn get_onion_address() -> Option<String> {
     init_globals();
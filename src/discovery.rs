@@ -0,0 +1,220 @@
+//! Mint discovery
+//! Fetches kind:37400 announcements from relays and turns them into a ranked
+//! list of NIP-74 mints
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use nostr::{Event, Filter, Kind, ToBech32};
+use nostr_sdk::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::nip74_service::{required_pow, MintStatus, Nip74Error, Nip74Result, KIND_MINT_INFO};
+
+/// A mint described by a verified kind:37400 announcement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscoveredMint {
+    /// Mint public key (npub)
+    pub npub: String,
+    /// `d` identifier of the announcement
+    pub identifier: String,
+    /// NUT-06 mint information
+    pub info: cdk::nuts::MintInfo,
+    /// Relays where the mint listens for requests
+    pub relays: Vec<String>,
+    /// Advertised status (`running`, `maintenance`, `stopping`, ...)
+    pub status: String,
+    /// Clearnet HTTP URL, if served
+    pub url: Option<String>,
+    /// Onion HTTP URL, if served
+    pub onion_url: Option<String>,
+    /// Minimum NIP-13 difficulty of request events
+    pub min_pow: u8,
+    /// `created_at` of the announcement
+    pub created_at: u64,
+}
+
+impl DiscoveredMint {
+    /// Verify and parse a kind:37400 announcement
+    pub fn from_event(event: &Event) -> Nip74Result<Self> {
+        if event.kind != Kind::from(KIND_MINT_INFO) {
+            return Err(Nip74Error::InvalidAnnouncement(format!("unexpected kind {}", event.kind)));
+        }
+        event
+            .verify()
+            .map_err(|e| Nip74Error::InvalidAnnouncement(format!("invalid signature: {}", e)))?;
+
+        let identifier = event
+            .tags
+            .identifier()
+            .ok_or_else(|| Nip74Error::InvalidAnnouncement("missing d tag".to_string()))?
+            .to_string();
+        let info = serde_json::from_str(&event.content)
+            .map_err(|e| Nip74Error::InvalidAnnouncement(format!("invalid mint info: {}", e)))?;
+        let tag_values = |name: &str| -> Vec<String> {
+            event
+                .tags
+                .iter()
+                .map(|tag| tag.as_slice())
+                .find(|tag| tag.first().is_some_and(|kind| kind == name))
+                .map(|tag| tag[1..].to_vec())
+                .unwrap_or_default()
+        };
+        let npub = event
+            .pubkey
+            .to_bech32()
+            .map_err(|e| Nip74Error::InvalidAnnouncement(e.to_string()))?;
+
+        Ok(Self {
+            npub,
+            identifier,
+            info,
+            relays: tag_values("relays"),
+            status: tag_values("status").into_iter().next().unwrap_or_default(),
+            url: tag_values("url").into_iter().next(),
+            onion_url: tag_values("onion").into_iter().next(),
+            min_pow: required_pow(event),
+            created_at: event.created_at.as_u64(),
+        })
+    }
+
+    /// Whether the mint can be reached over NIP-74
+    pub fn is_reachable(&self) -> bool {
+        !self.relays.is_empty()
+    }
+
+    /// Sort key of the status: running mints first, stopping mints last
+    fn status_rank(&self) -> u8 {
+        match self.status.as_str() {
+            s if s == MintStatus::Running.as_str() => 0,
+            s if s == MintStatus::Maintenance.as_str() => 1,
+            s if s == MintStatus::Stopping.as_str() => 3,
+            _ => 2,
+        }
+    }
+}
+
+/// Parse announcements, keeping the newest per mint and `d` identifier
+///
+/// Mints are ranked by status, then reachability over NIP-74, then request
+/// difficulty, then freshness. Invalid announcements are skipped.
+pub fn rank_mints<'a>(events: impl IntoIterator<Item = &'a Event>) -> Vec<DiscoveredMint> {
+    let mut latest: HashMap<(String, String), DiscoveredMint> = HashMap::new();
+    for event in events {
+        let mint = match DiscoveredMint::from_event(event) {
+            Ok(mint) => mint,
+            Err(e) => {
+                debug!("Skipping announcement {}: {}", event.id, e);
+                continue;
+            }
+        };
+        let key = (mint.npub.clone(), mint.identifier.clone());
+        match latest.get(&key) {
+            Some(known) if known.created_at >= mint.created_at => {}
+            _ => {
+                latest.insert(key, mint);
+            }
+        }
+    }
+
+    let mut mints: Vec<DiscoveredMint> = latest.into_values().collect();
+    mints.sort_by(|a, b| {
+        a.status_rank()
+            .cmp(&b.status_rank())
+            .then(b.is_reachable().cmp(&a.is_reachable()))
+            .then(a.min_pow.cmp(&b.min_pow))
+            .then(b.created_at.cmp(&a.created_at))
+            .then(a.npub.cmp(&b.npub))
+    });
+    mints
+}
+
+/// Fetch kind:37400 announcements from `relays` and return the ranked mints
+pub async fn discover_mints(relays: &[String], timeout: Duration) -> Nip74Result<Vec<DiscoveredMint>> {
+    let client = Client::default();
+    for relay in relays {
+        if let Err(e) = client.add_relay(relay.as_str()).await {
+            warn!("Skipping discovery relay '{}': {}", relay, e);
+        }
+    }
+    if client.relays().await.is_empty() {
+        return Err(Nip74Error::Config("no valid discovery relays".to_string()));
+    }
+    client.connect().await;
+
+    let filter = Filter::new().kind(Kind::from(KIND_MINT_INFO));
+    let events = client.fetch_events(filter, timeout).await;
+    client.shutdown().await;
+
+    let events = events.map_err(|e| Nip74Error::Relay(e.to_string()))?;
+    debug!("Fetched {} mint announcements", events.len());
+    Ok(rank_mints(events.iter()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::event::tag::kind::TagKind;
+    use nostr::{EventBuilder, Keys, Tag, Timestamp};
+
+    fn announcement(keys: &Keys, status: MintStatus, relays: &[&str], created_at: u64) -> Event {
+        let info = cdk::nuts::MintInfo::default().name("test-mint");
+        EventBuilder::new(Kind::from(KIND_MINT_INFO), serde_json::to_string(&info).unwrap())
+            .tag(Tag::identifier(keys.public_key().to_hex()))
+            .tag(Tag::custom(TagKind::Relays, relays.to_vec()))
+            .tag(Tag::custom(TagKind::Status, [status.as_str()]))
+            .tag(Tag::custom(TagKind::custom("onion"), ["http://example.onion"]))
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_announcement() {
+        let keys = Keys::generate();
+        let event = announcement(&keys, MintStatus::Running, &["wss://relay.example.com"], 1_700_000_000);
+
+        let mint = DiscoveredMint::from_event(&event).unwrap();
+        assert_eq!(mint.npub, keys.public_key().to_bech32().unwrap());
+        assert_eq!(mint.identifier, keys.public_key().to_hex());
+        assert_eq!(mint.info.name.as_deref(), Some("test-mint"));
+        assert_eq!(mint.relays, vec!["wss://relay.example.com".to_string()]);
+        assert_eq!(mint.status, "running");
+        assert_eq!(mint.url, None);
+        assert_eq!(mint.onion_url.as_deref(), Some("http://example.onion"));
+        assert_eq!(mint.min_pow, 0);
+
+        // Tampered announcements fail verification
+        let mut tampered = event.clone();
+        tampered.content = "{}".to_string();
+        assert!(matches!(
+            DiscoveredMint::from_event(&tampered),
+            Err(Nip74Error::InvalidAnnouncement(_))
+        ));
+    }
+
+    #[test]
+    fn test_rank_mints_dedupes_and_orders() {
+        let relays = ["wss://relay.example.com"];
+        let stale = Keys::generate();
+        let stopping = Keys::generate();
+        let offline = Keys::generate();
+        let running = Keys::generate();
+
+        let events = vec![
+            announcement(&stale, MintStatus::Running, &relays, 1_700_000_000),
+            announcement(&stale, MintStatus::Maintenance, &relays, 1_700_000_100),
+            announcement(&stopping, MintStatus::Stopping, &relays, 1_700_000_000),
+            announcement(&offline, MintStatus::Running, &[], 1_700_000_000),
+            announcement(&running, MintStatus::Running, &relays, 1_700_000_000),
+        ];
+
+        let mints = rank_mints(events.iter());
+        let order: Vec<String> = mints.iter().map(|mint| mint.npub.clone()).collect();
+        let npub = |keys: &Keys| keys.public_key().to_bech32().unwrap();
+        assert_eq!(order, vec![npub(&running), npub(&offline), npub(&stale), npub(&stopping)]);
+        // Only the newest announcement of a mint is kept
+        assert_eq!(mints[2].status, "maintenance");
+    }
+}
@@ -0,0 +1,685 @@
+//! Embedded Nostr relay for NIP-74 traffic
+//! A minimal NIP-01 relay mounted on the mint's axum server (and therefore
+//! reachable through its onion service) that only stores and forwards NIP-74
+//! requests, results, NIP-74 and NIP-87 announcements and the gift wraps
+//! carrying them
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use nostr::{Event, EventId, Filter, Kind};
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, watch};
+use tracing::debug;
+
+use crate::config::EmbeddedRelayConfig;
+use crate::nip74_service::{
+    KIND_CASHU_MINT_ANNOUNCEMENT, KIND_MINT_INFO, KIND_OPERATION_REQUEST, KIND_OPERATION_RESULT,
+};
+
+/// Largest accepted websocket message in bytes
+const MAX_MESSAGE_SIZE: usize = 256 * 1024;
+
+/// Maximum number of open subscriptions per connection
+const MAX_SUBSCRIPTIONS: usize = 32;
+
+/// Whether the relay accepts events of `kind`
+fn is_accepted_kind(kind: Kind) -> bool {
+    kind == Kind::from(KIND_OPERATION_REQUEST)
+        || kind == Kind::from(KIND_OPERATION_RESULT)
+        || kind == Kind::from(KIND_MINT_INFO)
+        || kind == Kind::from(KIND_CASHU_MINT_ANNOUNCEMENT)
+        || kind == Kind::GiftWrap
+}
+
+/// Budget an event counts against for the per-pubkey cap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Quota {
+    /// Events signed by the pubkey
+    Author(nostr::PublicKey),
+    /// Gift wraps to the pubkey, whose authors are one-time keys
+    Recipient(nostr::PublicKey),
+}
+
+fn quota(event: &Event) -> Quota {
+    if event.kind == Kind::GiftWrap {
+        if let Some(recipient) = event.tags.public_keys().next() {
+            return Quota::Recipient(*recipient);
+        }
+    }
+    Quota::Author(event.pubkey)
+}
+
+/// `d` tag of an addressable event
+fn identifier(event: &Event) -> Option<&str> {
+    event
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .find(|tag| tag.len() > 1 && tag[0] == "d")
+        .map(|tag| tag[1].as_str())
+}
+
+/// NIP-01 filter match
+fn matches(filter: &Filter, event: &Event) -> bool {
+    filter.ids.as_ref().is_none_or(|ids| ids.contains(&event.id))
+        && filter.authors.as_ref().is_none_or(|authors| authors.contains(&event.pubkey))
+        && filter.kinds.as_ref().is_none_or(|kinds| kinds.contains(&event.kind))
+        && filter.since.is_none_or(|since| event.created_at >= since)
+        && filter.until.is_none_or(|until| event.created_at <= until)
+        && filter.generic_tags.iter().all(|(name, values)| {
+            event.tags.iter().any(|tag| {
+                tag.single_letter_tag() == Some(*name) && tag.content().is_some_and(|v| values.contains(v))
+            })
+        })
+}
+
+#[derive(Debug, Clone)]
+struct StoredEvent {
+    event: Arc<Event>,
+    /// Receipt time; gift wraps are backdated so `created_at` is not used for retention
+    received_at: u64,
+}
+
+/// Address of an addressable event: kind, author and `d` tag
+type Address = (Kind, nostr::PublicKey, String);
+
+/// Stored events with the indexes `store` needs
+///
+/// The id queues are in receipt order and may hold ids of events removed since;
+/// those are skipped when they reach the front and compacted away when they
+/// make up most of a queue.
+#[derive(Default)]
+struct Store {
+    events: HashMap<EventId, StoredEvent>,
+    /// Every stored event, for retention
+    received: VecDeque<EventId>,
+    /// Events not signed by the operator, the ones evicted when the relay is full
+    evictable: VecDeque<EventId>,
+    /// Events of each capped budget, oldest first; the operator's own are not capped
+    quotas: HashMap<Quota, VecDeque<EventId>>,
+    /// Stored event of each address
+    addresses: HashMap<Address, EventId>,
+}
+
+impl Store {
+    fn insert(&mut self, event: Arc<Event>, now: u64, budget: Option<Quota>, evictable: bool) {
+        let id = event.id;
+        if let Some(address) = address(&event) {
+            self.addresses.insert(address, id);
+        }
+        if let Some(budget) = budget {
+            self.quotas.entry(budget).or_default().push_back(id);
+        }
+        if evictable {
+            self.evictable.push_back(id);
+        }
+        self.received.push_back(id);
+        self.events.insert(id, StoredEvent { event, received_at: now });
+    }
+
+    fn remove(&mut self, id: &EventId) {
+        let Some(stored) = self.events.remove(id) else {
+            return;
+        };
+        if let Some(address) = address(&stored.event) {
+            if self.addresses.get(&address) == Some(id) {
+                self.addresses.remove(&address);
+            }
+        }
+        let budget = quota(&stored.event);
+        if let Some(ids) = self.quotas.get_mut(&budget) {
+            // Usually the oldest of its budget; other positions are bounded by the cap
+            if ids.front() == Some(id) {
+                ids.pop_front();
+            } else {
+                ids.retain(|other| other != id);
+            }
+            if ids.is_empty() {
+                self.quotas.remove(&budget);
+            }
+        }
+    }
+
+    /// Drop events received more than `retention` seconds before `now`
+    fn expire(&mut self, now: u64, retention: u64) {
+        while let Some(id) = self.received.front().copied() {
+            match self.events.get(&id) {
+                Some(stored) if stored.received_at.saturating_add(retention) >= now => break,
+                Some(_) => self.remove(&id),
+                None => {}
+            }
+            self.received.pop_front();
+        }
+        self.compact();
+    }
+
+    /// Remove the oldest evictable event, returning false when there is none
+    fn evict_oldest(&mut self) -> bool {
+        while let Some(id) = self.evictable.pop_front() {
+            if self.events.contains_key(&id) {
+                self.remove(&id);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Drop ids of removed events from the queues once they outnumber the stored ones
+    fn compact(&mut self) {
+        let events = &self.events;
+        for queue in [&mut self.received, &mut self.evictable] {
+            if queue.len() > 2 * events.len() + 64 {
+                queue.retain(|id| events.contains_key(id));
+            }
+        }
+    }
+}
+
+/// Address of `event`, if it is addressable
+fn address(event: &Event) -> Option<Address> {
+    event
+        .kind
+        .is_addressable()
+        .then(|| (event.kind, event.pubkey, identifier(event).unwrap_or_default().to_string()))
+}
+
+struct Inner {
+    config: EmbeddedRelayConfig,
+    /// Kinds the relay stores, [`is_accepted_kind`] outside tests
+    accepts: fn(Kind) -> bool,
+    /// Pubkey of the mint serving the relay, whose own events are not capped
+    operator: Option<nostr::PublicKey>,
+    events: Mutex<Store>,
+    live: broadcast::Sender<Arc<Event>>,
+    closed: watch::Sender<bool>,
+}
+
+/// Outcome of storing an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stored {
+    New,
+    Duplicate,
+    /// The author already has `max_events_per_pubkey` events stored
+    OverQuota,
+}
+
+impl Inner {
+    /// Store `event`
+    ///
+    /// Events of the operator are neither capped nor evicted when the relay is
+    /// full, so fresh keys flooding the relay cannot push out the mint's results
+    /// and announcements; they only leave by retention or replacement.
+    fn store(&self, event: Event, now: u64) -> Stored {
+        let mut store = match self.events.lock() {
+            Ok(store) => store,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        store.expire(now, self.config.retention_secs);
+        if store.events.contains_key(&event.id) {
+            return Stored::Duplicate;
+        }
+
+        // Announcements are addressable: keep only the newest per author and `d`
+        if let Some(address) = address(&event) {
+            if let Some(stored_id) = store.addresses.get(&address).copied() {
+                let newer_stored = store
+                    .events
+                    .get(&stored_id)
+                    .is_some_and(|stored| stored.event.created_at >= event.created_at);
+                if newer_stored {
+                    return Stored::Duplicate;
+                }
+                store.remove(&stored_id);
+            }
+        }
+
+        // Anyone can wrap events to any recipient, so a full recipient loses its
+        // oldest wrap rather than new requests; subscribers have already seen it
+        let operated = Some(event.pubkey) == self.operator;
+        let budget = quota(&event);
+        let capped = !matches!(budget, Quota::Author(pubkey) if Some(pubkey) == self.operator);
+        let used = store.quotas.get(&budget).map_or(0, VecDeque::len);
+        if capped && used >= self.config.max_events_per_pubkey {
+            match budget {
+                Quota::Author(_) => return Stored::OverQuota,
+                Quota::Recipient(_) => {
+                    if let Some(oldest) = store.quotas.get(&budget).and_then(|ids| ids.front()).copied() {
+                        store.remove(&oldest);
+                    }
+                }
+            }
+        }
+
+        let event = Arc::new(event);
+        store.insert(event.clone(), now, capped.then_some(budget), !operated);
+        while store.events.len() > self.config.max_events && store.evict_oldest() {}
+        store.compact();
+        drop(store);
+
+        // No live subscribers is fine
+        let _ = self.live.send(event);
+        Stored::New
+    }
+
+    /// Stored events matching `filter`, oldest first, honouring its `limit`
+    fn query(&self, filter: &Filter) -> Vec<Arc<Event>> {
+        let store = match self.events.lock() {
+            Ok(store) => store,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let mut found: Vec<Arc<Event>> = store
+            .events
+            .values()
+            .filter(|stored| matches(filter, &stored.event))
+            .map(|stored| stored.event.clone())
+            .collect();
+        found.sort_by_key(|event| event.created_at);
+        if let Some(limit) = filter.limit {
+            let skip = found.len().saturating_sub(limit);
+            found.drain(..skip);
+        }
+        found
+    }
+}
+
+/// Open subscriptions of one connection
+type Subscriptions = HashMap<String, Vec<Filter>>;
+
+/// Minimal relay accepting only NIP-74 kinds, cheap to clone
+#[derive(Clone)]
+pub struct EmbeddedRelay {
+    inner: Arc<Inner>,
+}
+
+impl EmbeddedRelay {
+    /// Create an empty relay for the mint with pubkey `operator`
+    pub fn new(config: EmbeddedRelayConfig, operator: Option<nostr::PublicKey>) -> Self {
+        Self::with_kinds(config, operator, is_accepted_kind)
+    }
+
+    /// Relay storing the kinds `accepts` lets through, to test clients of other protocols
+    #[cfg(test)]
+    pub(crate) fn accepting(config: EmbeddedRelayConfig, accepts: fn(Kind) -> bool) -> Self {
+        Self::with_kinds(config, None, accepts)
+    }
+
+    fn with_kinds(config: EmbeddedRelayConfig, operator: Option<nostr::PublicKey>, accepts: fn(Kind) -> bool) -> Self {
+        let (live, _) = broadcast::channel(1024);
+        let (closed, _) = watch::channel(false);
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                accepts,
+                operator,
+                events: Mutex::new(Store::default()),
+                live,
+                closed,
+            }),
+        }
+    }
+
+    /// Close all open connections
+    ///
+    /// Upgraded websockets outlive the HTTP server's graceful shutdown, so they
+    /// are closed explicitly.
+    pub fn close(&self) {
+        self.inner.closed.send_replace(true);
+    }
+
+    /// HTTP path the relay is served on
+    pub fn path(&self) -> &str {
+        &self.inner.config.path
+    }
+
+    /// Number of stored events
+    pub fn event_count(&self) -> usize {
+        match self.inner.events.lock() {
+            Ok(store) => store.events.len(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Router serving the relay websocket on the configured path
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route(&self.inner.config.path, get(upgrade))
+            .with_state(self.clone())
+    }
+
+    async fn serve(self, mut socket: WebSocket) {
+        let mut live = self.inner.live.subscribe();
+        let mut closed = self.inner.closed.subscribe();
+        let mut subscriptions = Subscriptions::new();
+        debug!("Embedded relay connection opened");
+
+        loop {
+            if *closed.borrow_and_update() {
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+
+            let outgoing = tokio::select! {
+                _ = closed.changed() => continue,
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Text(text))) => self.handle_message(text.as_str(), &mut subscriptions),
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                },
+                event = live.recv() => match event {
+                    Ok(event) => subscriptions
+                        .iter()
+                        .filter(|(_, filters)| filters.iter().any(|filter| matches(filter, &event)))
+                        .map(|(id, _)| json!(["EVENT", id, event.as_ref()]))
+                        .collect(),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        vec![json!(["NOTICE", format!("dropped {} events, resubscribe", skipped)])]
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+
+            for message in outgoing {
+                if socket.send(Message::Text(message.to_string().into())).await.is_err() {
+                    debug!("Embedded relay connection closed while sending");
+                    return;
+                }
+            }
+        }
+        debug!("Embedded relay connection closed");
+    }
+
+    /// Handle one client message and return the relay messages to send back
+    fn handle_message(&self, text: &str, subscriptions: &mut Subscriptions) -> Vec<Value> {
+        let message: Vec<Value> = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(_) => return vec![json!(["NOTICE", "invalid: message is not a JSON array"])],
+        };
+
+        match (message.first().and_then(Value::as_str), message.get(1)) {
+            (Some("EVENT"), Some(event)) => vec![self.handle_event(event.clone())],
+            (Some("REQ"), Some(Value::String(id))) => {
+                let filters: Result<Vec<Filter>, _> =
+                    message[2..].iter().cloned().map(serde_json::from_value).collect();
+                let filters = match filters {
+                    Ok(filters) if !filters.is_empty() => filters,
+                    _ => return vec![json!(["CLOSED", id, "invalid: bad filter"])],
+                };
+                if !subscriptions.contains_key(id) && subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                    return vec![json!(["CLOSED", id, "error: too many subscriptions"])];
+                }
+
+                let mut replies: Vec<Value> = filters
+                    .iter()
+                    .flat_map(|filter| self.inner.query(filter))
+                    .map(|event| json!(["EVENT", id, event.as_ref()]))
+                    .collect();
+                replies.push(json!(["EOSE", id]));
+                subscriptions.insert(id.clone(), filters);
+                replies
+            }
+            (Some("CLOSE"), Some(Value::String(id))) => {
+                subscriptions.remove(id);
+                Vec::new()
+            }
+            _ => vec![json!(["NOTICE", "unsupported: only EVENT, REQ and CLOSE are supported"])],
+        }
+    }
+
+    fn handle_event(&self, event: Value) -> Value {
+        // NIP-01 answers with OK whenever the event id can be told
+        let claimed_id = event.get("id").and_then(Value::as_str).map(str::to_string);
+        let event: Event = match (serde_json::from_value(event), claimed_id) {
+            (Ok(event), _) => event,
+            (Err(e), Some(id)) => return json!(["OK", id, false, format!("invalid: {}", e)]),
+            (Err(e), None) => return json!(["NOTICE", format!("invalid: {}", e)]),
+        };
+        let id = event.id.to_hex();
+
+        if !(self.inner.accepts)(event.kind) {
+            return json!(["OK", id, false, "blocked: this relay only accepts NIP-74 events"]);
+        }
+        if event.verify().is_err() {
+            return json!(["OK", id, false, "invalid: bad id or signature"]);
+        }
+
+        let kind = event.kind;
+        match self.inner.store(event, nostr::Timestamp::now().as_u64()) {
+            Stored::New => {
+                debug!("Embedded relay stored kind {} event {}", kind, id);
+                json!(["OK", id, true, ""])
+            }
+            Stored::Duplicate => json!(["OK", id, true, "duplicate: already have this event"]),
+            Stored::OverQuota => json!(["OK", id, false, "rate-limited: too many stored events for this pubkey"]),
+        }
+    }
+}
+
+async fn upgrade(ws: WebSocketUpgrade, State(relay): State<EmbeddedRelay>) -> impl IntoResponse {
+    ws.max_message_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| relay.serve(socket))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relay() -> EmbeddedRelay {
+        EmbeddedRelay::new(EmbeddedRelayConfig { enabled: true, ..Default::default() }, None)
+    }
+
+    fn event(keys: &nostr::Keys, kind: u16, tags: Vec<nostr::Tag>) -> Event {
+        nostr::EventBuilder::new(Kind::from(kind), "payload")
+            .tags(tags)
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    fn send(relay: &EmbeddedRelay, subscriptions: &mut Subscriptions, message: Value) -> Vec<Value> {
+        relay.handle_message(&message.to_string(), subscriptions)
+    }
+
+    #[test]
+    fn test_only_nip74_kinds_are_accepted() {
+        let relay = relay();
+        let keys = nostr::Keys::generate();
+        let mut subscriptions = Subscriptions::new();
+
+        let request = event(&keys, KIND_OPERATION_REQUEST, vec![]);
+        let reply = send(&relay, &mut subscriptions, json!(["EVENT", request]));
+        assert_eq!(reply[0][2], true);
+
+        let announcement = event(&keys, KIND_CASHU_MINT_ANNOUNCEMENT, vec![nostr::Tag::identifier("mint")]);
+        let reply = send(&relay, &mut subscriptions, json!(["EVENT", announcement]));
+        assert_eq!(reply[0][2], true);
+
+        let note = event(&keys, 1, vec![]);
+        let reply = send(&relay, &mut subscriptions, json!(["EVENT", note]));
+        assert_eq!(reply[0][2], false);
+
+        let mut forged = serde_json::to_value(event(&keys, KIND_OPERATION_RESULT, vec![])).unwrap();
+        forged["content"] = json!("tampered");
+        let reply = send(&relay, &mut subscriptions, json!(["EVENT", forged]));
+        assert_eq!(reply[0][2], false);
+
+        assert_eq!(relay.event_count(), 2);
+    }
+
+    #[test]
+    fn test_req_returns_matching_events_then_eose() {
+        let relay = relay();
+        let mint = nostr::Keys::generate();
+        let client = nostr::Keys::generate();
+        let mut subscriptions = Subscriptions::new();
+
+        let for_mint = event(&client, KIND_OPERATION_REQUEST, vec![nostr::Tag::public_key(mint.public_key())]);
+        let for_other = event(
+            &client,
+            KIND_OPERATION_REQUEST,
+            vec![nostr::Tag::public_key(nostr::Keys::generate().public_key())],
+        );
+        send(&relay, &mut subscriptions, json!(["EVENT", for_mint]));
+        send(&relay, &mut subscriptions, json!(["EVENT", for_other]));
+
+        let filter = Filter::new().kind(Kind::from(KIND_OPERATION_REQUEST)).pubkey(mint.public_key());
+        let replies = send(&relay, &mut subscriptions, json!(["REQ", "sub", filter]));
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0][2]["id"], for_mint.id.to_hex());
+        assert_eq!(replies[1], json!(["EOSE", "sub"]));
+        assert!(subscriptions.contains_key("sub"));
+
+        send(&relay, &mut subscriptions, json!(["CLOSE", "sub"]));
+        assert!(subscriptions.is_empty());
+    }
+
+    #[test]
+    fn test_announcements_are_replaced() {
+        let relay = relay();
+        let mint = nostr::Keys::generate();
+        let d = nostr::Tag::identifier("mint");
+
+        let older = nostr::EventBuilder::new(Kind::from(KIND_MINT_INFO), "old")
+            .tag(d.clone())
+            .custom_created_at(nostr::Timestamp::from(1_000))
+            .sign_with_keys(&mint)
+            .unwrap();
+        let newer = nostr::EventBuilder::new(Kind::from(KIND_MINT_INFO), "new")
+            .tag(d)
+            .custom_created_at(nostr::Timestamp::from(2_000))
+            .sign_with_keys(&mint)
+            .unwrap();
+
+        assert_eq!(relay.inner.store(newer.clone(), 100), Stored::New);
+        assert_eq!(relay.inner.store(older, 100), Stored::Duplicate);
+        let stored = relay.inner.query(&Filter::new().kind(Kind::from(KIND_MINT_INFO)));
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].id, newer.id);
+    }
+
+    #[test]
+    fn test_retention_and_capacity() {
+        let relay = EmbeddedRelay::new(
+            EmbeddedRelayConfig {
+                enabled: true,
+                max_events: 2,
+                retention_secs: 60,
+                ..Default::default()
+            },
+            None,
+        );
+        let keys = nostr::Keys::generate();
+
+        for now in [0, 1, 2] {
+            relay.inner.store(event(&keys, KIND_OPERATION_RESULT, vec![nostr::Tag::hashtag(now.to_string())]), now);
+        }
+        assert_eq!(relay.event_count(), 2);
+
+        relay.inner.store(event(&keys, KIND_OPERATION_RESULT, vec![]), 100);
+        assert_eq!(relay.event_count(), 1);
+    }
+
+    #[test]
+    fn test_per_pubkey_cap() {
+        let mint = nostr::Keys::generate();
+        let relay = EmbeddedRelay::new(
+            EmbeddedRelayConfig {
+                enabled: true,
+                max_events_per_pubkey: 2,
+                ..Default::default()
+            },
+            Some(mint.public_key()),
+        );
+        let spammer = nostr::Keys::generate();
+
+        for i in 0..2 {
+            let request = event(&spammer, KIND_OPERATION_REQUEST, vec![nostr::Tag::hashtag(i.to_string())]);
+            assert_eq!(relay.inner.store(request, 0), Stored::New);
+        }
+        let request = event(&spammer, KIND_OPERATION_REQUEST, vec![]);
+        assert_eq!(relay.inner.store(request, 0), Stored::OverQuota);
+
+        // Cheap one-time-key wraps to the mint only push out older wraps
+        let wrap = |i: usize| {
+            event(
+                &nostr::Keys::generate(),
+                1059,
+                vec![nostr::Tag::public_key(mint.public_key()), nostr::Tag::hashtag(i.to_string())],
+            )
+        };
+        for i in 0..10 {
+            assert_eq!(relay.inner.store(wrap(i), 0), Stored::New);
+        }
+        let request = wrap(10);
+        assert_eq!(relay.inner.store(request.clone(), 0), Stored::New);
+        let wraps = relay.inner.query(&Filter::new().kind(Kind::GiftWrap).pubkey(mint.public_key()));
+        assert_eq!(wraps.len(), 2);
+        assert!(wraps.iter().any(|wrap| wrap.id == request.id));
+
+        // The mint keeps publishing its results
+        for i in 0..5 {
+            let result = event(&mint, KIND_OPERATION_RESULT, vec![nostr::Tag::hashtag(i.to_string())]);
+            assert_eq!(relay.inner.store(result, 0), Stored::New);
+        }
+    }
+
+    #[test]
+    fn test_flood_keeps_mint_events() {
+        let mint = nostr::Keys::generate();
+        let relay = EmbeddedRelay::new(
+            EmbeddedRelayConfig {
+                enabled: true,
+                max_events: 10,
+                max_events_per_pubkey: 2,
+                ..Default::default()
+            },
+            Some(mint.public_key()),
+        );
+
+        let info = event(&mint, KIND_MINT_INFO, vec![nostr::Tag::identifier("mint")]);
+        let result = event(&mint, KIND_OPERATION_RESULT, vec![]);
+        assert_eq!(relay.inner.store(info.clone(), 0), Stored::New);
+        assert_eq!(relay.inner.store(result.clone(), 0), Stored::New);
+
+        // Fresh keys get around the per-author cap, not past the mint's events
+        let mut last = None;
+        for i in 0..100 {
+            let request = event(
+                &nostr::Keys::generate(),
+                KIND_OPERATION_REQUEST,
+                vec![nostr::Tag::hashtag(i.to_string())],
+            );
+            assert_eq!(relay.inner.store(request.clone(), 1), Stored::New);
+            last = Some(request.id);
+        }
+        assert_eq!(relay.event_count(), 10);
+
+        let stored = relay.inner.query(&Filter::new().author(mint.public_key()));
+        assert_eq!(stored.len(), 2);
+        assert!(stored.iter().any(|event| event.id == info.id));
+        assert!(stored.iter().any(|event| event.id == result.id));
+        let requests = relay.inner.query(&Filter::new().kind(Kind::from(KIND_OPERATION_REQUEST)));
+        assert_eq!(requests.len(), 8);
+        assert!(requests.iter().any(|event| Some(event.id) == last));
+    }
+
+    #[test]
+    fn test_invalid_event_with_id_gets_ok_false() {
+        let relay = relay();
+        let mut subscriptions = Subscriptions::new();
+
+        let reply = send(&relay, &mut subscriptions, json!(["EVENT", {"id": "abc", "kind": "request"}]));
+        assert_eq!(reply[0][0], "OK");
+        assert_eq!(reply[0][1], "abc");
+        assert_eq!(reply[0][2], false);
+        assert!(reply[0][3].as_str().unwrap().starts_with("invalid: "));
+
+        let reply = send(&relay, &mut subscriptions, json!(["EVENT", {"kind": "request"}]));
+        assert_eq!(reply[0][0], "NOTICE");
+    }
+}
@@ -0,0 +1,345 @@
+//! NIP-74 to HTTP gateway
+//! Serves the Cashu NUT `/v1/*` routes locally and forwards each call as a
+//! NIP-74 request to a remote mint, so stock HTTP wallets can reach Nostr-only mints
+
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::any;
+use serde_json::{Value, json};
+use tokio::sync::Notify;
+use tower_http::trace::TraceLayer;
+use tracing::{debug, error, info, warn};
+
+use crate::config::GatewayConfig;
+use crate::nip74_service::{
+    ErrorCode, Nip74Client, Nip74Error, Nip74Result, OperationMethod, OperationRequest, OperationResult,
+    ResultError, ResultStatus,
+};
+
+/// Local HTTP server forwarding Cashu API calls to a remote NIP-74 mint
+pub struct GatewayService {
+    config: GatewayConfig,
+    keys: nostr::Keys,
+    client: Option<Arc<Nip74Client>>,
+    shutdown: Arc<Notify>,
+    http_server: Option<tokio::task::JoinHandle<()>>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl GatewayService {
+    /// Create a gateway sending requests signed by `keys`
+    pub fn new(config: GatewayConfig, keys: nostr::Keys) -> Self {
+        Self {
+            config,
+            keys,
+            client: None,
+            shutdown: Arc::new(Notify::new()),
+            http_server: None,
+            local_addr: None,
+        }
+    }
+
+    /// Connect to the mint's relays and start serving the `/v1/*` routes
+    pub async fn start(&mut self) -> Result<()> {
+        if self.is_running() {
+            info!("Gateway is already running");
+            return Ok(());
+        }
+
+        let mint_pubkey = nostr::PublicKey::parse(self.config.mint_npub.trim())
+            .map_err(|e| anyhow!("Invalid mint npub '{}': {}", self.config.mint_npub, e))?;
+        if self.config.relays.is_empty() {
+            return Err(anyhow!("Gateway requires at least one relay"));
+        }
+
+        // Bound before connecting so no error path leaves relay connections open
+        let socket_addr = SocketAddr::from_str(&format!("{}:{}", self.config.listen_host, self.config.listen_port))
+            .map_err(|e| {
+                anyhow!(
+                    "Invalid socket address '{}:{}': {}",
+                    self.config.listen_host,
+                    self.config.listen_port,
+                    e
+                )
+            })?;
+        let listener = tokio::net::TcpListener::bind(socket_addr)
+            .await
+            .map_err(|e| anyhow!("Failed to bind to address '{}': {}", socket_addr, e))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| anyhow!("Failed to get local address: {}", e))?;
+
+        info!("Starting NIP-74 gateway to {} via {:?}", mint_pubkey, self.config.relays);
+        let client = Nip74Client::connect(self.keys.clone(), mint_pubkey, &self.config.relays)
+            .await?
+            .with_gift_wrap(self.config.gift_wrap)
+            .with_timeout(Duration::from_secs(self.config.timeout_secs));
+        let pow = match self.config.min_pow {
+            Some(pow) => pow,
+            None => client.fetch_required_pow().await.unwrap_or_else(|e| {
+                warn!("Failed to fetch mint announcement, sending requests without proof of work: {}", e);
+                0
+            }),
+        };
+        let client = Arc::new(client.with_pow(pow));
+
+        let app = router(client.clone()).layer(TraceLayer::new_for_http());
+        let shutdown = self.shutdown.clone();
+        self.http_server = Some(tokio::spawn(async move {
+            let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+                shutdown.notified().await;
+                info!("Gateway received shutdown signal");
+            });
+            match server.await {
+                Ok(()) => info!("Gateway stopped gracefully"),
+                Err(e) => error!("Gateway HTTP server error: {}", e),
+            }
+        }));
+
+        info!("Gateway listening on http://{} (request difficulty {})", local_addr, pow);
+        self.client = Some(client);
+        self.local_addr = Some(local_addr);
+        Ok(())
+    }
+
+    /// Stop serving and disconnect from the relays
+    pub async fn stop(&mut self) -> Result<()> {
+        self.shutdown.notify_waiters();
+        if let Some(http_server) = self.http_server.take() {
+            let _ = http_server.await;
+        }
+        if let Some(client) = self.client.take() {
+            client.disconnect().await;
+        }
+        self.local_addr = None;
+        info!("Gateway stopped");
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.http_server.is_some()
+    }
+
+    /// Address the gateway is listening on
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    pub fn get_status(&self) -> Value {
+        json!({
+            "running": self.is_running(),
+            "mint": self.config.mint_npub,
+            "url": self.local_addr.map(|addr| format!("http://{}", addr)),
+            "relays": self.config.relays,
+            "gift_wrap": self.config.gift_wrap,
+        })
+    }
+}
+
+/// Router serving the Cashu `/v1/*` routes through `client`
+pub fn router(client: Arc<Nip74Client>) -> Router {
+    Router::new().route("/v1/{*path}", any(forward)).with_state(client)
+}
+
+async fn forward(
+    State(client): State<Arc<Nip74Client>>,
+    method: Method,
+    Path(path): Path<String>,
+    body: Bytes,
+) -> Response {
+    let body = if body.is_empty() {
+        None
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(body) => Some(body),
+            Err(e) => {
+                let error = ResultError::new(ErrorCode::InvalidRequest, format!("Invalid JSON body: {}", e));
+                return error_response(StatusCode::BAD_REQUEST, &error);
+            }
+        }
+    };
+
+    let Some((operation, data)) = operation(&method, &path, body) else {
+        let error = ResultError::new(ErrorCode::UnsupportedMethod, format!("No route for {} /v1/{}", method, path));
+        return error_response(StatusCode::NOT_FOUND, &error);
+    };
+
+    debug!("Forwarding {} /v1/{} as {:?}", method, path, operation);
+    let result = client.send(&OperationRequest::new(operation.clone(), data)).await;
+    into_response(&operation, result)
+}
+
+/// NIP-74 operation and `data` of a NUT HTTP call (inverse of the proxy handler's routes)
+fn operation(method: &Method, path: &str, body: Option<Value>) -> Option<(OperationMethod, Option<Value>)> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    Some(match (method.as_str(), segments.as_slice()) {
+        ("GET", ["info"]) => (OperationMethod::Info, None),
+        ("GET", ["keys"]) => (OperationMethod::Keys, None),
+        ("GET", ["keys", id]) => (OperationMethod::Keys, Some(json!({ "keyset_id": id }))),
+        ("GET", ["keysets"]) => (OperationMethod::Keysets, None),
+        ("POST", ["mint", "quote", "bolt11"]) => (OperationMethod::GetMintQuote, body),
+        ("GET", ["mint", "quote", "bolt11", quote]) => (OperationMethod::CheckMintQuote, Some(json!(quote))),
+        ("POST", ["mint", "bolt11"]) => (OperationMethod::Mint, body),
+        ("POST", ["melt", "quote", "bolt11"]) => (OperationMethod::GetMeltQuote, body),
+        ("GET", ["melt", "quote", "bolt11", quote]) => (OperationMethod::CheckMeltQuote, Some(json!(quote))),
+        ("POST", ["melt", "bolt11"]) => (OperationMethod::Melt, body),
+        ("POST", ["mint", "quote", "bolt12"]) => (OperationMethod::GetMintQuoteBolt12, body),
+        ("GET", ["mint", "quote", "bolt12", quote]) => (OperationMethod::CheckMintQuoteBolt12, Some(json!(quote))),
+        ("POST", ["mint", "bolt12"]) => (OperationMethod::MintBolt12, body),
+        ("POST", ["melt", "quote", "bolt12"]) => (OperationMethod::GetMeltQuoteBolt12, body),
+        ("GET", ["melt", "quote", "bolt12", quote]) => (OperationMethod::CheckMeltQuoteBolt12, Some(json!(quote))),
+        ("POST", ["melt", "bolt12"]) => (OperationMethod::MeltBolt12, body),
+        ("POST", ["swap"]) => (OperationMethod::Swap, body),
+        ("POST", ["checkstate"]) => (OperationMethod::CheckState, body),
+        ("POST", ["restore"]) => (OperationMethod::Restore, body),
+        _ => return None,
+    })
+}
+
+/// HTTP response of a NIP-74 result, with failures in the Cashu `{code, detail}` format
+fn into_response(operation: &OperationMethod, result: Nip74Result<OperationResult>) -> Response {
+    let result = match result {
+        Ok(result) => result,
+        Err(Nip74Error::Mint(error)) => return error_response(error_status(&error), &error),
+        Err(Nip74Error::Timeout(request_id)) => {
+            let error = ResultError::new(
+                ErrorCode::BackendUnavailable,
+                format!("Mint did not answer request {}", request_id),
+            );
+            return error_response(StatusCode::GATEWAY_TIMEOUT, &error);
+        }
+        Err(e) => {
+            let error = ResultError::new(ErrorCode::BackendUnavailable, e.to_string());
+            return error_response(StatusCode::BAD_GATEWAY, &error);
+        }
+    };
+
+    match result.status {
+        ResultStatus::Success => {
+            let data = result.data.unwrap_or(Value::Null);
+            // The in-process mint handler wraps mint info as `{"info": ...}`.
+            let data = match (operation, data.get("info")) {
+                (OperationMethod::Info, Some(info)) => info.clone(),
+                _ => data,
+            };
+            (StatusCode::OK, Json(data)).into_response()
+        }
+        ResultStatus::Error => {
+            let error = result.error.unwrap_or_else(|| {
+                ResultError::new(ErrorCode::Unknown, "mint returned an error without details")
+            });
+            error_response(error_status(&error), &error)
+        }
+    }
+}
+
+fn error_status(error: &ResultError) -> StatusCode {
+    if error.cashu_code.is_some() {
+        return StatusCode::BAD_REQUEST;
+    }
+    match error.error_code() {
+        ErrorCode::Unauthorized => StatusCode::FORBIDDEN,
+        ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::BackendUnavailable => StatusCode::BAD_GATEWAY,
+        ErrorCode::InternalError | ErrorCode::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+/// Cashu error body; transport-level failures without a NUT code use `0`
+fn error_response(status: StatusCode, error: &ResultError) -> Response {
+    let body = json!({
+        "code": error.cashu_code.unwrap_or(0),
+        "detail": error.message,
+    });
+    (status, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_routes_map_to_operations() {
+        let payload = json!({ "amount": 100, "unit": "sat" });
+        let cases = [
+            (Method::GET, "info", OperationMethod::Info, None),
+            (Method::GET, "keys", OperationMethod::Keys, None),
+            (Method::GET, "keys/009a1f293253e41e", OperationMethod::Keys, Some(json!({ "keyset_id": "009a1f293253e41e" }))),
+            (Method::GET, "keysets", OperationMethod::Keysets, None),
+            (Method::POST, "mint/quote/bolt11", OperationMethod::GetMintQuote, Some(payload.clone())),
+            (Method::GET, "mint/quote/bolt11/abc-123", OperationMethod::CheckMintQuote, Some(json!("abc-123"))),
+            (Method::POST, "mint/bolt11", OperationMethod::Mint, Some(payload.clone())),
+            (Method::POST, "melt/quote/bolt11", OperationMethod::GetMeltQuote, Some(payload.clone())),
+            (Method::GET, "melt/quote/bolt11/abc-123", OperationMethod::CheckMeltQuote, Some(json!("abc-123"))),
+            (Method::POST, "melt/bolt11", OperationMethod::Melt, Some(payload.clone())),
+            (Method::POST, "mint/quote/bolt12", OperationMethod::GetMintQuoteBolt12, Some(payload.clone())),
+            (Method::GET, "melt/quote/bolt12/abc-123", OperationMethod::CheckMeltQuoteBolt12, Some(json!("abc-123"))),
+            (Method::POST, "swap", OperationMethod::Swap, Some(payload.clone())),
+            (Method::POST, "checkstate", OperationMethod::CheckState, Some(payload.clone())),
+            (Method::POST, "restore", OperationMethod::Restore, Some(payload.clone())),
+        ];
+
+        for (method, path, expected, data) in cases {
+            let body = (method == Method::POST).then(|| payload.clone());
+            assert_eq!(operation(&method, path, body), Some((expected, data)), "{} {}", method, path);
+        }
+
+        assert_eq!(operation(&Method::POST, "info", None), None);
+        assert_eq!(operation(&Method::GET, "mint/bolt11", None), None);
+        assert_eq!(operation(&Method::POST, "batch", Some(json!([]))), None);
+    }
+
+    #[tokio::test]
+    async fn test_results_map_to_http_responses() {
+        let info = OperationResult::success("1", json!({ "info": { "name": "PurrMint" } }));
+        let response = into_response(&OperationMethod::Info, Ok(info));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, json!({ "name": "PurrMint" }));
+
+        let spent = OperationResult::failure("2", ResultError::new(ErrorCode::TokenAlreadySpent, "spent"));
+        let response = into_response(&OperationMethod::Swap, Ok(spent));
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body(response).await, json!({ "code": 11001, "detail": "spent" }));
+
+        let limited = OperationResult::failure("3", ResultError::new(ErrorCode::RateLimited, "slow down"));
+        let response = into_response(&OperationMethod::Swap, Ok(limited));
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body(response).await["code"], 0);
+
+        let response = into_response(&OperationMethod::Keys, Err(Nip74Error::Timeout("4".into())));
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_listen_address_fails_before_connecting() {
+        let config = GatewayConfig {
+            listen_host: "not a host".to_string(),
+            mint_npub: nostr::Keys::generate().public_key().to_hex(),
+            // Unreachable, so connecting would wait for the connection timeout
+            relays: vec!["ws://127.0.0.1:1".to_string()],
+            ..Default::default()
+        };
+        let mut gateway = GatewayService::new(config, nostr::Keys::generate());
+
+        let started = std::time::Instant::now();
+        let error = gateway.start().await.unwrap_err();
+        assert!(error.to_string().starts_with("Invalid socket address"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(!gateway.is_running());
+    }
+}
@@ -407,4 +407,201 @@ pub extern "system" fn Java_com_purrmint_app_PurrmintNative_getOnionAddress(
             ptr::null_mut()
         }
     }
-} 
\ No newline at end of file
+}
+
+/// Toggle the maintenance status of the NIP-74 mint announcement
+#[no_mangle]
+pub extern "system" fn Java_com_purrmint_app_PurrmintNative_setMaintenanceMode(
+    _env: JNIEnv,
+    _class: JClass,
+    maintenance: jni::sys::jboolean,
+) -> jint {
+    match crate::core::set_maintenance_mode(maintenance != 0) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Failed to set maintenance mode: {}", e);
+            1
+        }
+    }
+}
+
+// =============================================================================
+// Access policy methods - Client allowlist and denylist
+// =============================================================================
+
+fn access_list(deny: jni::sys::jboolean) -> crate::core::AccessList {
+    if deny != 0 {
+        crate::core::AccessList::Deny
+    } else {
+        crate::core::AccessList::Allow
+    }
+}
+
+/// Add an npub to the allowlist (or denylist when `deny` is true)
+///
+/// Returns the updated `accessMode`/`allowedNpubs`/`deniedNpubs` JSON for the
+/// app to save into its config, or null on failure.
+#[no_mangle]
+pub extern "system" fn Java_com_purrmint_app_PurrmintNative_addAccessNpub(
+    mut _env: JNIEnv,
+    _class: JClass,
+    npub: JString,
+    deny: jni::sys::jboolean,
+) -> jstring {
+    let npub_str = java_string_to_rust_string(&mut _env, npub);
+
+    let lists = match crate::core::add_access_npub(access_list(deny), &npub_str) {
+        Ok(lists) => lists,
+        Err(e) => {
+            error!("Failed to add npub to access policy: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    match _env.new_string(lists) {
+        Ok(java_string) => java_string.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for access lists: {:?}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Remove an npub from the allowlist (or denylist when `deny` is true)
+///
+/// Returns the updated lists like `addAccessNpub`, or null on failure.
+#[no_mangle]
+pub extern "system" fn Java_com_purrmint_app_PurrmintNative_removeAccessNpub(
+    mut _env: JNIEnv,
+    _class: JClass,
+    npub: JString,
+    deny: jni::sys::jboolean,
+) -> jstring {
+    let npub_str = java_string_to_rust_string(&mut _env, npub);
+
+    let lists = match crate::core::remove_access_npub(access_list(deny), &npub_str) {
+        Ok(lists) => lists,
+        Err(e) => {
+            error!("Failed to remove npub from access policy: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    match _env.new_string(lists) {
+        Ok(java_string) => java_string.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for access lists: {:?}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Get the access policy as JSON
+#[no_mangle]
+pub extern "system" fn Java_com_purrmint_app_PurrmintNative_getAccessPolicy(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let policy_str = match crate::core::get_access_policy() {
+        Ok(policy) => policy,
+        Err(e) => {
+            error!("Failed to get access policy: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    match _env.new_string(policy_str) {
+        Ok(java_string) => java_string.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for access policy: {:?}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// =============================================================================
+// Gateway methods - Local HTTP gateway to a remote NIP-74 mint
+// =============================================================================
+
+/// Start the gateway with its JSON configuration (empty nsec uses fresh keys)
+#[no_mangle]
+pub extern "system" fn Java_com_purrmint_app_PurrmintNative_startGateway(
+    mut _env: JNIEnv,
+    _class: JClass,
+    config_json: JString,
+    nsec: JString,
+) -> jint {
+    let config_str = java_string_to_rust_string(&mut _env, config_json);
+    let nsec_str = java_string_to_rust_string(&mut _env, nsec);
+
+    match crate::core::start_gateway(&config_str, &nsec_str) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Failed to start gateway: {}", e);
+            1
+        }
+    }
+}
+
+/// Stop the gateway
+#[no_mangle]
+pub extern "system" fn Java_com_purrmint_app_PurrmintNative_stopGateway(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    match crate::core::stop_gateway() {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Failed to stop gateway: {}", e);
+            1
+        }
+    }
+}
+
+/// Get gateway status
+#[no_mangle]
+pub extern "system" fn Java_com_purrmint_app_PurrmintNative_getGatewayStatus(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let status_str = crate::core::get_gateway_status();
+
+    match _env.new_string(status_str) {
+        Ok(java_string) => java_string.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for gateway status: {:?}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// =============================================================================
+// Discovery methods - Browse NIP-74 mints announced on relays
+// =============================================================================
+
+/// Discover mints on a JSON array of relay URLs (empty for the defaults)
+#[no_mangle]
+pub extern "system" fn Java_com_purrmint_app_PurrmintNative_discoverMints(
+    mut _env: JNIEnv,
+    _class: JClass,
+    relays_json: JString,
+    timeout_secs: jint,
+) -> jstring {
+    let relays_str = java_string_to_rust_string(&mut _env, relays_json);
+
+    let mints_str = match crate::core::discover_mints(&relays_str, timeout_secs.max(1) as u64) {
+        Ok(mints) => mints,
+        Err(e) => {
+            error!("Failed to discover mints: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    match _env.new_string(mints_str) {
+        Ok(java_string) => java_string.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for discovered mints: {:?}", e);
+            ptr::null_mut()
+        }
+    }
+}
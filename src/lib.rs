@@ -8,6 +8,20 @@ pub mod core;
 pub mod nostr;
 pub mod config;
 pub mod tor_service;
+pub mod relay_manager;
+pub mod request_store;
+pub mod middleware;
+pub mod quote_notifier;
+pub mod access_policy;
+pub mod rate_limiter;
+pub mod embedded_relay;
+pub mod gateway_service;
+pub mod discovery;
+pub mod payment;
+pub mod lnd;
+pub mod nwc;
+pub mod phoenixd;
+pub mod routing;
 
 // Re-export key types
 pub use service::MintService;
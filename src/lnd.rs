@@ -0,0 +1,608 @@
+//! LND Lightning backend
+//! Talks to the LND REST API, authenticated with a macaroon and pinned to the
+//! node's TLS certificate
+
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE as BASE64_URL};
+use cdk::lightning_invoice::Bolt11Invoice;
+use cdk::types::FeeReserve;
+use cdk_common::nuts::{CurrencyUnit, MeltQuoteState};
+use cdk_common::payment::{
+    self, CreateIncomingPaymentResponse, IncomingPaymentOptions, MakePaymentResponse, MintPayment,
+    OutgoingPaymentOptions, PaymentIdentifier, PaymentQuoteResponse, WaitPaymentResponse,
+};
+use cdk_common::Amount;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tracing::{debug, info, warn};
+
+use crate::payment::{
+    InvoiceLookup, InvoiceWatcher, PaymentStream, bolt11_quote, bolt11_settings, check_unit, invoice_expiry,
+    lookup_hash, payment_hash, to_msat,
+};
+use crate::routing::HealthProbe;
+
+/// LND encodes 64-bit integers as JSON strings
+fn msat(value: &Option<String>) -> u64 {
+    value.as_deref().and_then(|v| v.parse().ok()).unwrap_or(0)
+}
+
+#[derive(Debug, Deserialize)]
+struct AddInvoiceResponse {
+    payment_request: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Invoice {
+    state: String,
+    amt_paid_msat: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendResponse {
+    #[serde(default)]
+    payment_error: String,
+    payment_preimage: Option<String>,
+    payment_route: Option<Route>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Route {
+    total_amt_msat: Option<String>,
+}
+
+/// One message of the `TrackPaymentV2` stream
+#[derive(Debug, Deserialize)]
+struct TrackUpdate {
+    result: Option<Payment>,
+    error: Option<StreamError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamError {
+    #[serde(default)]
+    code: i32,
+    #[serde(default)]
+    message: String,
+}
+
+/// gRPC status LND answers for payments it never sent
+const GRPC_NOT_FOUND: i32 = 5;
+
+/// `payment_error` of invoices the node has paid before
+const ALREADY_PAID: &str = "invoice is already paid";
+
+#[derive(Debug, Deserialize)]
+struct Payment {
+    status: String,
+    payment_preimage: Option<String>,
+    value_msat: Option<String>,
+    fee_msat: Option<String>,
+}
+
+/// LND node reached over its REST API
+#[derive(Clone)]
+pub struct Lnd {
+    client: reqwest::Client,
+    base_url: String,
+    macaroon: String,
+    fee_reserve: FeeReserve,
+    invoices: InvoiceWatcher,
+}
+
+impl Lnd {
+    /// Connect to the REST API at `address` with the macaroon and (optional) TLS cert files
+    pub fn new(address: &str, cert_file: Option<&Path>, macaroon_file: &Path, fee_reserve: FeeReserve) -> Result<Self> {
+        let macaroon = std::fs::read(macaroon_file)
+            .map_err(|e| anyhow!("Failed to read LND macaroon {}: {}", macaroon_file.display(), e))?;
+        let cert = cert_file
+            .map(|path| {
+                std::fs::read(path).map_err(|e| anyhow!("Failed to read LND TLS cert {}: {}", path.display(), e))
+            })
+            .transpose()?;
+        Self::from_parts(address, cert.as_deref(), &macaroon, fee_reserve)
+    }
+
+    /// Connect with an in-memory macaroon and PEM certificate
+    pub fn from_parts(address: &str, cert_pem: Option<&[u8]>, macaroon: &[u8], fee_reserve: FeeReserve) -> Result<Self> {
+        let base_url = reqwest::Url::parse(address).map_err(|e| anyhow!("Invalid LND address '{}': {}", address, e))?;
+
+        let mut builder = reqwest::Client::builder();
+        if let Some(pem) = cert_pem {
+            let cert = reqwest::Certificate::from_pem(pem).map_err(|e| anyhow!("Invalid LND TLS cert: {}", e))?;
+            builder = builder.add_root_certificate(cert);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| anyhow!("Failed to build HTTP client: {}", e))?;
+
+        info!("Using LND at {}", base_url);
+        Ok(Self {
+            client,
+            base_url: base_url.as_str().trim_end_matches('/').to_string(),
+            macaroon: hex::encode(macaroon),
+            fee_reserve,
+            invoices: InvoiceWatcher::default(),
+        })
+    }
+
+    pub(crate) fn invoices(&self) -> &InvoiceWatcher {
+        &self.invoices
+    }
+
+    async fn request<T>(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> Result<T, payment::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path))
+            .header("Grpc-Metadata-macaroon", &self.macaroon);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| payment::Error::Custom(format!("LND request failed: {}", e)))?;
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(payment::Error::Custom(format!("LND returned {}: {}", status, text)));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Current state of the outgoing payment with `payment_hash`, `None` if the
+    /// node never sent it
+    ///
+    /// `TrackPaymentV2` keeps streaming until the payment completes; only its
+    /// first message, the current state, is read.
+    async fn track_payment(&self, payment_hash: [u8; 32]) -> Result<Option<Payment>, payment::Error> {
+        let mut response = self
+            .client
+            .get(format!(
+                "{}/v2/router/track/{}?no_inflight_updates=false",
+                self.base_url,
+                BASE64_URL.encode(payment_hash)
+            ))
+            .header("Grpc-Metadata-macaroon", &self.macaroon)
+            .send()
+            .await
+            .map_err(|e| payment::Error::Custom(format!("LND request failed: {}", e)))?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(payment::Error::Custom(format!("LND returned {}: {}", status, text)));
+        }
+
+        let mut message = Vec::new();
+        while !message.contains(&b'\n') {
+            match response
+                .chunk()
+                .await
+                .map_err(|e| payment::Error::Custom(format!("LND request failed: {}", e)))?
+            {
+                Some(chunk) => message.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        let end = message.iter().position(|b| *b == b'\n').unwrap_or(message.len());
+        let update: TrackUpdate = serde_json::from_slice(&message[..end])?;
+        match (update.result, update.error) {
+            (Some(payment), _) => Ok(Some(payment)),
+            (None, Some(error)) if error.code == GRPC_NOT_FOUND => Ok(None),
+            (None, error) => Err(payment::Error::Custom(format!(
+                "LND could not track payment {}: {}",
+                hex::encode(payment_hash),
+                error.map(|e| e.message).unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl InvoiceLookup for Lnd {
+    async fn paid_msat(&self, payment_hash: [u8; 32]) -> Result<Option<u64>, payment::Error> {
+        let invoice: Invoice = self
+            .request(reqwest::Method::GET, &format!("/v1/invoice/{}", hex::encode(payment_hash)), None)
+            .await?;
+        Ok((invoice.state == "SETTLED").then(|| msat(&invoice.amt_paid_msat)))
+    }
+}
+
+#[async_trait]
+impl HealthProbe for Lnd {
+    async fn probe(&self) -> Result<(), payment::Error> {
+        self.request::<Value>(reqwest::Method::GET, "/v1/getinfo", None).await.map(|_| ())
+    }
+}
+
+#[async_trait]
+impl MintPayment for Lnd {
+    type Err = payment::Error;
+
+    async fn get_settings(&self) -> Result<Value, Self::Err> {
+        Ok(bolt11_settings())
+    }
+
+    async fn create_incoming_payment_request(
+        &self,
+        unit: &CurrencyUnit,
+        options: IncomingPaymentOptions,
+    ) -> Result<CreateIncomingPaymentResponse, Self::Err> {
+        check_unit(unit)?;
+        let IncomingPaymentOptions::Bolt11(options) = options else {
+            return Err(payment::Error::UnsupportedPaymentOption);
+        };
+
+        let mut body = json!({
+            "value_msat": to_msat(options.amount, unit)?.to_string(),
+            "memo": options.description.unwrap_or_default(),
+        });
+        if let Some(expiry) = options.unix_expiry {
+            let now = cdk::util::unix_time();
+            body["expiry"] = json!(expiry.saturating_sub(now).to_string());
+        }
+
+        let response: AddInvoiceResponse = self.request(reqwest::Method::POST, "/v1/invoices", Some(body)).await?;
+        let invoice = Bolt11Invoice::from_str(&response.payment_request)
+            .map_err(|e| payment::Error::Custom(format!("LND returned an invalid invoice: {}", e)))?;
+        let hash = payment_hash(&invoice)?;
+        self.invoices.register(hash, invoice_expiry(&invoice));
+
+        Ok(CreateIncomingPaymentResponse {
+            request_lookup_id: PaymentIdentifier::PaymentHash(hash),
+            request: response.payment_request,
+            expiry: invoice.expires_at().map(|expiry| expiry.as_secs()),
+        })
+    }
+
+    async fn get_payment_quote(
+        &self,
+        unit: &CurrencyUnit,
+        options: OutgoingPaymentOptions,
+    ) -> Result<PaymentQuoteResponse, Self::Err> {
+        bolt11_quote(unit, options, &self.fee_reserve)
+    }
+
+    async fn make_payment(
+        &self,
+        unit: &CurrencyUnit,
+        options: OutgoingPaymentOptions,
+    ) -> Result<MakePaymentResponse, Self::Err> {
+        check_unit(unit)?;
+        let OutgoingPaymentOptions::Bolt11(options) = options else {
+            return Err(payment::Error::UnsupportedPaymentOption);
+        };
+
+        let hash = payment_hash(&options.bolt11)?;
+        let mut body = json!({ "payment_request": options.bolt11.to_string() });
+        if let Some(max_fee) = options.max_fee_amount {
+            body["fee_limit"] = json!({ "fixed_msat": to_msat(max_fee, unit)?.to_string() });
+        }
+
+        let response: SendResponse = self
+            .request(reqwest::Method::POST, "/v1/channels/transactions", Some(body))
+            .await?;
+        // Failed payments are answered with HTTP 200 and a `payment_error`
+        if response.payment_error.contains(ALREADY_PAID) {
+            return Err(payment::Error::InvoiceAlreadyPaid);
+        }
+        if !response.payment_error.is_empty() {
+            return Err(payment::Error::Custom(format!("LND payment failed: {}", response.payment_error)));
+        }
+
+        let payment_lookup_id = PaymentIdentifier::PaymentHash(hash);
+        let preimage = response
+            .payment_preimage
+            .and_then(|preimage| BASE64.decode(preimage).ok())
+            .map(hex::encode);
+        let total_msat = response.payment_route.map(|route| msat(&route.total_amt_msat)).unwrap_or(0);
+        if total_msat == 0 {
+            // Without the route the amount spent is unknown: ask the router, and
+            // leave the melt pending until it can tell
+            match self.check_outgoing_payment(&payment_lookup_id).await {
+                Ok(payment) if payment.status == MeltQuoteState::Paid && payment.total_spent > Amount::ZERO => {
+                    return Ok(MakePaymentResponse {
+                        payment_proof: payment.payment_proof.or(preimage),
+                        ..payment
+                    });
+                }
+                Ok(_) => warn!("LND paid {} without a route, leaving it pending", hex::encode(hash)),
+                Err(e) => warn!("Failed to look up LND payment {}, leaving it pending: {}", hex::encode(hash), e),
+            }
+            return Ok(MakePaymentResponse {
+                payment_lookup_id,
+                payment_proof: preimage,
+                status: MeltQuoteState::Pending,
+                total_spent: Amount::ZERO,
+                unit: CurrencyUnit::Msat,
+            });
+        }
+        debug!("Paid {} with LND ({} msat)", hex::encode(hash), total_msat);
+
+        Ok(MakePaymentResponse {
+            payment_lookup_id,
+            payment_proof: preimage,
+            status: MeltQuoteState::Paid,
+            total_spent: Amount::from(total_msat),
+            unit: CurrencyUnit::Msat,
+        })
+    }
+
+    async fn wait_any_incoming_payment(&self) -> Result<PaymentStream, Self::Err> {
+        Ok(self.invoices.poll(self))
+    }
+
+    fn is_wait_invoice_active(&self) -> bool {
+        self.invoices.is_active()
+    }
+
+    fn cancel_wait_invoice(&self) {
+        self.invoices.cancel()
+    }
+
+    async fn check_incoming_payment_status(
+        &self,
+        payment_identifier: &PaymentIdentifier,
+    ) -> Result<Vec<WaitPaymentResponse>, Self::Err> {
+        self.incoming_status(payment_identifier).await
+    }
+
+    async fn check_outgoing_payment(
+        &self,
+        payment_identifier: &PaymentIdentifier,
+    ) -> Result<MakePaymentResponse, Self::Err> {
+        let hash = lookup_hash(payment_identifier)?;
+        let Some(payment) = self.track_payment(hash).await? else {
+            return Ok(MakePaymentResponse {
+                payment_lookup_id: payment_identifier.clone(),
+                payment_proof: None,
+                status: MeltQuoteState::Unknown,
+                total_spent: Amount::ZERO,
+                unit: CurrencyUnit::Msat,
+            });
+        };
+
+        let status = match payment.status.as_str() {
+            "SUCCEEDED" => MeltQuoteState::Paid,
+            "FAILED" => MeltQuoteState::Failed,
+            "IN_FLIGHT" | "INITIATED" => MeltQuoteState::Pending,
+            _ => MeltQuoteState::Unknown,
+        };
+        let total_spent = match status {
+            MeltQuoteState::Paid => msat(&payment.value_msat) + msat(&payment.fee_msat),
+            _ => 0,
+        };
+
+        Ok(MakePaymentResponse {
+            payment_lookup_id: payment_identifier.clone(),
+            payment_proof: payment.payment_preimage.filter(|preimage| !preimage.is_empty()),
+            status,
+            total_spent: Amount::from(total_spent),
+            unit: CurrencyUnit::Msat,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::{Path as UrlPath, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+
+    use crate::payment::testing::{assert_incoming_payment, fee_reserve, pay_options, serve};
+
+    const MACAROON: &[u8] = b"admin";
+
+    /// Invoice amounts the node answers specially when paying
+    const NO_ROUTE_MSAT: u64 = 30_000;
+    const ALREADY_PAID_MSAT: u64 = 40_000;
+    const UNROUTABLE_MSAT: u64 = 50_000;
+
+    /// Payment hashes the router knows, the first one with `-` and `_` in base64url
+    const SUCCEEDED: [u8; 32] = [0xfb; 32];
+    const IN_FLIGHT: [u8; 32] = [2; 32];
+
+    /// Stand-in for the LND REST API with a single invoice
+    #[derive(Clone, Default)]
+    struct Node {
+        invoice: Arc<Mutex<Option<Bolt11Invoice>>>,
+        settled: Arc<Mutex<bool>>,
+        /// Body of the last `AddInvoice`
+        added: Arc<Mutex<Value>>,
+    }
+
+    fn check_macaroon(headers: &HeaderMap) -> Result<(), StatusCode> {
+        match headers.get("Grpc-Metadata-macaroon") {
+            Some(value) if value.as_bytes() == hex::encode(MACAROON).as_bytes() => Ok(()),
+            _ => Err(StatusCode::FORBIDDEN),
+        }
+    }
+
+    async fn add_invoice(
+        State(node): State<Node>,
+        headers: HeaderMap,
+        Json(body): Json<Value>,
+    ) -> Result<Json<Value>, StatusCode> {
+        check_macaroon(&headers)?;
+        let amount_msat: u64 = body["value_msat"].as_str().unwrap().parse().unwrap();
+        let invoice = cdk_fake_wallet::create_fake_invoice(amount_msat, body["memo"].as_str().unwrap().to_string());
+        *node.invoice.lock().unwrap() = Some(invoice.clone());
+        *node.added.lock().unwrap() = body;
+        Ok(Json(json!({ "payment_request": invoice.to_string(), "add_index": "1" })))
+    }
+
+    /// Amounts are int64 strings, and an open invoice has been paid `"0"`
+    async fn lookup_invoice(
+        State(node): State<Node>,
+        headers: HeaderMap,
+        UrlPath(hash): UrlPath<String>,
+    ) -> Result<Json<Value>, StatusCode> {
+        check_macaroon(&headers)?;
+        let invoice = node.invoice.lock().unwrap().clone().ok_or(StatusCode::NOT_FOUND)?;
+        if hex::encode(payment_hash(&invoice).unwrap()) != hash {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let settled = *node.settled.lock().unwrap();
+        let paid = if settled { invoice.amount_milli_satoshis().unwrap() } else { 0 };
+        Ok(Json(json!({
+            "state": if settled { "SETTLED" } else { "OPEN" },
+            "amt_paid_msat": paid.to_string(),
+        })))
+    }
+
+    /// `SendPaymentSync` answers failures with HTTP 200 and a `payment_error`,
+    /// and the preimage in base64
+    async fn send_payment(headers: HeaderMap, Json(body): Json<Value>) -> Result<Json<Value>, StatusCode> {
+        check_macaroon(&headers)?;
+        let invoice = Bolt11Invoice::from_str(body["payment_request"].as_str().unwrap()).unwrap();
+        assert_eq!(body["fee_limit"]["fixed_msat"], "10000");
+        let amount_msat = invoice.amount_milli_satoshis().unwrap();
+        let response = match amount_msat {
+            ALREADY_PAID_MSAT => json!({ "payment_error": "invoice is already paid" }),
+            UNROUTABLE_MSAT => json!({ "payment_error": "unable to find a path to destination" }),
+            NO_ROUTE_MSAT => json!({ "payment_error": "", "payment_preimage": BASE64.encode([7u8; 32]) }),
+            _ => json!({
+                "payment_error": "",
+                "payment_preimage": BASE64.encode([7u8; 32]),
+                "payment_route": { "total_amt_msat": (amount_msat + 1_000).to_string() },
+            }),
+        };
+        Ok(Json(response))
+    }
+
+    /// `TrackPaymentV2` streams newline-delimited updates for a base64url hash
+    /// until the payment completes, and a gRPC NOT_FOUND for unknown payments
+    async fn track_payment(headers: HeaderMap, UrlPath(hash): UrlPath<String>) -> Result<String, StatusCode> {
+        check_macaroon(&headers)?;
+        let update = |status: &str| {
+            json!({
+                "result": {
+                    "status": status,
+                    "payment_preimage": hex::encode([7u8; 32]),
+                    "value_msat": "21000",
+                    "fee_msat": "1000",
+                }
+            })
+        };
+        let updates = if hash == BASE64_URL.encode(SUCCEEDED) {
+            vec![update("SUCCEEDED")]
+        } else if hash == BASE64_URL.encode(IN_FLIGHT) {
+            vec![update("IN_FLIGHT"), update("SUCCEEDED")]
+        } else {
+            vec![json!({ "error": { "code": 5, "message": "payment isn't initiated" } })]
+        };
+        Ok(updates.iter().map(|update| format!("{}\n", update)).collect())
+    }
+
+    async fn node() -> (String, Node) {
+        let state = Node::default();
+        let app = Router::new()
+            .route("/v1/invoices", post(add_invoice))
+            .route("/v1/invoice/{hash}", get(lookup_invoice))
+            .route("/v1/channels/transactions", post(send_payment))
+            .route("/v2/router/track/{hash}", get(track_payment))
+            .with_state(state.clone());
+        (serve(app).await, state)
+    }
+
+    fn lnd(address: &str) -> Lnd {
+        Lnd::from_parts(address, None, MACAROON, fee_reserve()).unwrap()
+    }
+
+    async fn track(lnd: &Lnd, hash: [u8; 32]) -> MakePaymentResponse {
+        lnd.check_outgoing_payment(&PaymentIdentifier::PaymentHash(hash)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_incoming_payment() {
+        let (address, node) = node().await;
+        assert_incoming_payment(&lnd(&address), CurrencyUnit::Sat, 21.into(), || {
+            *node.settled.lock().unwrap() = true;
+        })
+        .await;
+        assert_eq!(node.added.lock().unwrap()["value_msat"], "21000");
+    }
+
+    #[tokio::test]
+    async fn test_send_payment() {
+        let (address, _) = node().await;
+        let lnd = lnd(&address);
+        let invoice = cdk_fake_wallet::create_fake_invoice(20_000, "melt".to_string());
+
+        let payment = lnd.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await.unwrap();
+        assert_eq!(payment.status, MeltQuoteState::Paid);
+        assert_eq!(payment.total_spent, Amount::from(21_000));
+        // Re-encoded from base64 to hex
+        assert_eq!(payment.payment_proof, Some(hex::encode([7u8; 32])));
+    }
+
+    #[tokio::test]
+    async fn test_payment_error() {
+        let (address, _) = node().await;
+        let lnd = lnd(&address);
+
+        let invoice = cdk_fake_wallet::create_fake_invoice(ALREADY_PAID_MSAT, "melt".to_string());
+        let paid = lnd.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await;
+        assert!(matches!(paid, Err(payment::Error::InvoiceAlreadyPaid)));
+
+        let invoice = cdk_fake_wallet::create_fake_invoice(UNROUTABLE_MSAT, "melt".to_string());
+        let unroutable = lnd.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await;
+        assert!(matches!(unroutable, Err(payment::Error::Custom(message)) if message.contains("path to destination")));
+    }
+
+    #[tokio::test]
+    async fn test_payment_without_route_stays_pending() {
+        let (address, _) = node().await;
+        let lnd = lnd(&address);
+
+        // The router does not know it either, so the amount spent is unknown
+        let invoice = cdk_fake_wallet::create_fake_invoice(NO_ROUTE_MSAT, "melt".to_string());
+        let payment = lnd.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await.unwrap();
+        assert_eq!(payment.status, MeltQuoteState::Pending);
+        assert_eq!(payment.total_spent, Amount::ZERO);
+        assert_eq!(payment.payment_proof, Some(hex::encode([7u8; 32])));
+    }
+
+    #[tokio::test]
+    async fn test_track_payment() {
+        let (address, _) = node().await;
+        let lnd = lnd(&address);
+
+        let paid = track(&lnd, SUCCEEDED).await;
+        assert_eq!(paid.status, MeltQuoteState::Paid);
+        assert_eq!(paid.total_spent, Amount::from(22_000));
+        assert_eq!(paid.payment_proof, Some(hex::encode([7u8; 32])));
+
+        // Only the current state is read, not the updates that follow
+        let pending = track(&lnd, IN_FLIGHT).await;
+        assert_eq!(pending.status, MeltQuoteState::Pending);
+        assert_eq!(pending.total_spent, Amount::ZERO);
+
+        assert_eq!(track(&lnd, [3; 32]).await.status, MeltQuoteState::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_macaroon() {
+        let (address, _) = node().await;
+        let lnd = Lnd::from_parts(&address, None, b"readonly", fee_reserve()).unwrap();
+        let status = lnd.check_incoming_payment_status(&PaymentIdentifier::PaymentHash([1; 32])).await;
+        assert!(status.is_err());
+    }
+}
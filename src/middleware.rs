@@ -0,0 +1,331 @@
+//! Composable middleware for NIP-74 request handlers
+//! Layers wrap a [`RequestHandler`] with cross-cutting policies (logging,
+//! authorization, metrics, ...) the same way tower layers wrap the HTTP router
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use async_trait::async_trait;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::nip74_service::{
+    Nip74Result, OperationMethod, OperationRequest, OperationResult, RequestContext, RequestHandler,
+    ResultError, ResultStatus,
+};
+
+/// Wraps a request handler with additional behaviour
+pub trait Layer: Send + Sync + 'static {
+    /// Wrap `inner` and return the decorated handler
+    fn layer(&self, inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler>;
+}
+
+/// Builds a layered request handler
+///
+/// Like `tower::ServiceBuilder`, the first layer added is the outermost one and
+/// sees every request first.
+#[derive(Default)]
+pub struct HandlerBuilder {
+    layers: Vec<Arc<dyn Layer>>,
+}
+
+impl HandlerBuilder {
+    /// Create a builder without layers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a layer inside the layers added so far
+    pub fn layer<L: Layer>(mut self, layer: L) -> Self {
+        self.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Wrap `handler` with the configured layers
+    pub fn service(&self, handler: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+        self.layers
+            .iter()
+            .rev()
+            .fold(handler, |inner, layer| layer.layer(inner))
+    }
+}
+
+// ===== LOGGING =====
+
+/// Logs every request with its author, outcome and latency
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingLayer;
+
+impl Layer for LoggingLayer {
+    fn layer(&self, inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+        Arc::new(Logging { inner })
+    }
+}
+
+struct Logging {
+    inner: Arc<dyn RequestHandler>,
+}
+
+#[async_trait]
+impl RequestHandler for Logging {
+    async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        let method = req.method.clone();
+        let request_id = req.request_id.clone();
+        let started = Instant::now();
+
+        let result = self.inner.handle(ctx, req).await;
+        let elapsed = started.elapsed().as_millis();
+        match &result {
+            Ok(result) if result.status == ResultStatus::Success => info!(
+                "NIP-74 {:?} {} from {} succeeded in {}ms",
+                method, request_id, ctx.author, elapsed
+            ),
+            Ok(result) => info!(
+                "NIP-74 {:?} {} from {} failed in {}ms: {}",
+                method,
+                request_id,
+                ctx.author,
+                elapsed,
+                result.error.as_ref().map(|e| e.code.as_str()).unwrap_or("unknown")
+            ),
+            Err(e) => warn!("NIP-74 {:?} {} from {} errored: {}", method, request_id, ctx.author, e),
+        }
+        result
+    }
+}
+
+// ===== AUTHORIZATION =====
+
+/// Rejects requests for which the predicate returns an error
+pub struct AuthorizeLayer<F> {
+    authorize: Arc<F>,
+}
+
+impl<F> AuthorizeLayer<F>
+where
+    F: Fn(&RequestContext, &OperationRequest) -> Result<(), ResultError> + Send + Sync + 'static,
+{
+    /// Create a layer from an authorization predicate
+    pub fn new(authorize: F) -> Self {
+        Self { authorize: Arc::new(authorize) }
+    }
+}
+
+impl<F> Layer for AuthorizeLayer<F>
+where
+    F: Fn(&RequestContext, &OperationRequest) -> Result<(), ResultError> + Send + Sync + 'static,
+{
+    fn layer(&self, inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+        Arc::new(Authorize { inner, authorize: self.authorize.clone() })
+    }
+}
+
+struct Authorize<F> {
+    inner: Arc<dyn RequestHandler>,
+    authorize: Arc<F>,
+}
+
+#[async_trait]
+impl<F> RequestHandler for Authorize<F>
+where
+    F: Fn(&RequestContext, &OperationRequest) -> Result<(), ResultError> + Send + Sync + 'static,
+{
+    async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        match (self.authorize)(ctx, &req) {
+            Ok(()) => self.inner.handle(ctx, req).await,
+            Err(error) => Ok(OperationResult::failure(req.request_id, error)),
+        }
+    }
+}
+
+// ===== METRICS =====
+
+/// Counters of a single method
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MethodMetrics {
+    pub requests: u64,
+    pub errors: u64,
+    pub total_latency_ms: u64,
+}
+
+/// Shared request counters, cheap to clone
+#[derive(Debug, Clone, Default)]
+pub struct HandlerMetrics {
+    methods: Arc<Mutex<HashMap<String, MethodMetrics>>>,
+}
+
+impl HandlerMetrics {
+    /// Create empty metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&self, method: &OperationMethod, success: bool, latency_ms: u64) {
+        let key = serde_json::to_value(method)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_else(|| format!("{:?}", method));
+
+        let mut methods = match self.methods.lock() {
+            Ok(methods) => methods,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let entry = methods.entry(key).or_default();
+        entry.requests += 1;
+        if !success {
+            entry.errors += 1;
+        }
+        entry.total_latency_ms += latency_ms;
+    }
+
+    /// Counters of `method`
+    pub fn method(&self, method: &str) -> MethodMetrics {
+        match self.methods.lock() {
+            Ok(methods) => methods.get(method).cloned().unwrap_or_default(),
+            Err(_) => MethodMetrics::default(),
+        }
+    }
+
+    /// Snapshot of all counters as JSON
+    pub fn snapshot(&self) -> serde_json::Value {
+        match self.methods.lock() {
+            Ok(methods) => serde_json::json!(*methods),
+            Err(_) => serde_json::json!({}),
+        }
+    }
+}
+
+/// Records per-method request, error and latency counters
+#[derive(Debug, Clone, Default)]
+pub struct MetricsLayer {
+    metrics: HandlerMetrics,
+}
+
+impl MetricsLayer {
+    /// Create a layer recording into `metrics`
+    pub fn new(metrics: HandlerMetrics) -> Self {
+        Self { metrics }
+    }
+}
+
+impl Layer for MetricsLayer {
+    fn layer(&self, inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+        Arc::new(Metrics { inner, metrics: self.metrics.clone() })
+    }
+}
+
+struct Metrics {
+    inner: Arc<dyn RequestHandler>,
+    metrics: HandlerMetrics,
+}
+
+#[async_trait]
+impl RequestHandler for Metrics {
+    async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        let method = req.method.clone();
+        let started = Instant::now();
+
+        let result = self.inner.handle(ctx, req).await;
+        let success = matches!(&result, Ok(r) if r.status == ResultStatus::Success);
+        self.metrics.record(&method, success, started.elapsed().as_millis() as u64);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nip74_service::{Envelope, ErrorCode};
+
+    struct OkHandler;
+
+    #[async_trait]
+    impl RequestHandler for OkHandler {
+        async fn handle(&self, _ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+            Ok(OperationResult::success(req.request_id, serde_json::json!({})))
+        }
+    }
+
+    /// Appends its tag to the request data so layer order can be observed
+    struct TagLayer(&'static str);
+
+    struct Tag {
+        inner: Arc<dyn RequestHandler>,
+        tag: &'static str,
+    }
+
+    impl Layer for TagLayer {
+        fn layer(&self, inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+            Arc::new(Tag { inner, tag: self.0 })
+        }
+    }
+
+    #[async_trait]
+    impl RequestHandler for Tag {
+        async fn handle(&self, ctx: &RequestContext, mut req: OperationRequest) -> Nip74Result<OperationResult> {
+            let mut seen = req.data.take().and_then(|d| d.as_str().map(str::to_string)).unwrap_or_default();
+            seen.push_str(self.tag);
+            req.data = Some(serde_json::json!(seen));
+            let mut result = self.inner.handle(ctx, req.clone()).await?;
+            result.data = req.data;
+            Ok(result)
+        }
+    }
+
+    fn context() -> RequestContext {
+        RequestContext {
+            author: nostr::Keys::generate().public_key(),
+            event_id: nostr::EventId::all_zeros(),
+            created_at: nostr::Timestamp::now(),
+            relay_url: None,
+            envelope: Envelope::Direct,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_first_layer_is_outermost() {
+        let handler = HandlerBuilder::new()
+            .layer(TagLayer("a"))
+            .layer(TagLayer("b"))
+            .service(Arc::new(OkHandler));
+
+        let result = handler
+            .handle(&context(), OperationRequest::new(OperationMethod::Info, None))
+            .await
+            .unwrap();
+        assert_eq!(result.data.unwrap(), "ab");
+    }
+
+    #[tokio::test]
+    async fn test_authorize_and_metrics_layers() {
+        let metrics = HandlerMetrics::new();
+        let ctx = context();
+        let allowed = ctx.author;
+        let handler = HandlerBuilder::new()
+            .layer(MetricsLayer::new(metrics.clone()))
+            .layer(AuthorizeLayer::new(move |ctx: &RequestContext, _req: &OperationRequest| {
+                if ctx.author == allowed {
+                    Ok(())
+                } else {
+                    Err(ResultError::new(ErrorCode::InvalidRequest, "not allowed"))
+                }
+            }))
+            .service(Arc::new(OkHandler));
+
+        let ok = handler
+            .handle(&ctx, OperationRequest::new(OperationMethod::Swap, None))
+            .await
+            .unwrap();
+        assert_eq!(ok.status, ResultStatus::Success);
+
+        let denied = handler
+            .handle(&context(), OperationRequest::new(OperationMethod::Swap, None))
+            .await
+            .unwrap();
+        assert_eq!(denied.status, ResultStatus::Error);
+
+        let swap = metrics.method("swap");
+        assert_eq!(swap.requests, 2);
+        assert_eq!(swap.errors, 1);
+    }
+}
@@ -15,12 +15,25 @@ use uuid::Uuid;
 
 use crate::config::{
     AndroidConfig, Cln, Database, DatabaseEngine, FakeWallet, Info, LNbits, Ln, LnBackend,
-    MintInfo, Settings,
+    MintInfo, RelayEntry, Settings,
 };
+use crate::nip74_service::{
+    DefaultMintHandler, DefaultRequestHandler, MintAnnouncement, MintAnnouncer, MintInfoSource,
+    MintStatus, Nip74Service, RequestHandler,
+};
+use crate::access_policy::AccessPolicy;
+use crate::embedded_relay::EmbeddedRelay;
+use crate::middleware::{HandlerBuilder, HandlerMetrics, LoggingLayer, MetricsLayer};
+use crate::quote_notifier::QuoteNotifier;
+use crate::payment::InvoiceWatcher;
+use crate::rate_limiter::RateLimiter;
+use crate::routing::{ClnProbe, ConfiguredBackend, HttpProbe, resolve_routes};
+use crate::request_store::RequestStore;
 use cdk::mint::{MintBuilder, MintMeltLimits};
 use cdk::types::QuoteTTL;
 use cdk::Bolt11Invoice;
 use cdk_axum::cache::HttpCache;
+use cdk_common::payment::PaymentIdentifier;
 use cdk_sqlite::MintSqliteDatabase;
 
 pub struct MintdService {
@@ -31,12 +44,20 @@ pub struct MintdService {
     nsec: Option<String>,
     is_running: bool,
     http_server: Option<tokio::task::JoinHandle<()>>,
+    nip74_service: Option<Nip74Service>,
+    announcer: Option<MintAnnouncer>,
+    nip74_metrics: HandlerMetrics,
+    access_policy: AccessPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    embedded_relay: Option<EmbeddedRelay>,
+    onion_url: Option<String>,
 }
 
 impl MintdService {
     /// Create new MintdService with nsec (Nostr private key)
     pub fn new_with_nsec(work_dir: PathBuf, nsec: String) -> Self {
         let config = Self::create_default_config(None);
+        let access_policy = AccessPolicy::from_config(&config.access);
 
         Self {
             mint: None,
@@ -46,6 +67,13 @@ impl MintdService {
             nsec: Some(nsec),
             is_running: false,
             http_server: None,
+            nip74_service: None,
+            announcer: None,
+            nip74_metrics: HandlerMetrics::new(),
+            access_policy,
+            rate_limiter: None,
+            embedded_relay: None,
+            onion_url: None,
         }
     }
 
@@ -56,6 +84,7 @@ impl MintdService {
         nsec: String,
     ) -> Self {
         let config = Self::create_config_from_android(android_config);
+        let access_policy = AccessPolicy::from_config(&config.access);
 
         Self {
             mint: None,
@@ -65,6 +94,13 @@ impl MintdService {
             nsec: Some(nsec),
             is_running: false,
             http_server: None,
+            nip74_service: None,
+            announcer: None,
+            nip74_metrics: HandlerMetrics::new(),
+            access_policy,
+            rate_limiter: None,
+            embedded_relay: None,
+            onion_url: None,
         }
     }
 
@@ -147,9 +183,18 @@ impl MintdService {
             }),
             lnbits: None,
             cln: None,
+            lnd: None,
+            nwc: None,
+            phoenixd: None,
+            routing: crate::config::RoutingConfig::default(),
             database,
             service_mode: crate::config::ServiceMode::MintdOnly,
             tor: crate::config::TorConfig::default(),
+            relays: crate::config::RelayConfig::default(),
+            nip74: crate::config::Nip74Config::default(),
+            access: crate::config::AccessConfig::default(),
+            rate_limits: crate::config::RateLimitConfig::default(),
+            embedded_relay: crate::config::EmbeddedRelayConfig::default(),
         }
     }
 
@@ -180,6 +225,10 @@ impl MintdService {
             ln_backend: match android_config.lightning_backend.as_str() {
                 "fakewallet" | "fake" => LnBackend::FakeWallet,
                 "lnbits" => LnBackend::LNbits,
+                "cln" => LnBackend::Cln,
+                "lnd" => LnBackend::Lnd,
+                "nwc" => LnBackend::Nwc,
+                "phoenixd" => LnBackend::Phoenixd,
                 _ => LnBackend::None,
             },
             invoice_description: None,
@@ -201,9 +250,18 @@ impl MintdService {
             fake_wallet: None,
             lnbits: None,
             cln: None,
+            lnd: None,
+            nwc: None,
+            phoenixd: None,
+            routing: android_config.to_routing_config(),
             database,
-            service_mode: crate::config::ServiceMode::MintdOnly,
-            tor: crate::config::TorConfig::default(),
+            service_mode: android_config.to_service_mode(),
+            tor: android_config.to_tor_config(),
+            relays: android_config.to_relay_config(),
+            nip74: android_config.to_nip74_config(),
+            access: android_config.to_access_config(),
+            rate_limits: android_config.to_rate_limit_config(),
+            embedded_relay: android_config.to_embedded_relay_config(),
         };
 
         // Set backend-specific configuration
@@ -253,6 +311,18 @@ impl MintdService {
                     settings.cln = Some(Cln::default());
                 }
             }
+            "lnd" => {
+                // Left unset when incomplete so build_mint reports the missing settings
+                settings.lnd = android_config.to_lnd_config();
+            }
+            "nwc" => {
+                // Left unset when incomplete so build_mint reports the missing settings
+                settings.nwc = android_config.to_nwc_config();
+            }
+            "phoenixd" => {
+                // Left unset when incomplete so build_mint reports the missing settings
+                settings.phoenixd = android_config.to_phoenixd_config();
+            }
             _ => {
                 // Default to fake wallet if backend is not recognized
                 settings.fake_wallet = Some(FakeWallet {
@@ -268,6 +338,9 @@ impl MintdService {
             }
         }
 
+        // Configure the fallback backends of the routing table
+        android_config.add_routed_backends(&mut settings);
+
         settings
     }
 
@@ -281,28 +354,56 @@ impl MintdService {
         // Create work directory
         std::fs::create_dir_all(&self.work_dir)?;
 
-        // Build and start mint
-        let (mint, mint_info) = self.build_mint().await?;
-        let mint_arc = Arc::new(mint);
+        // Build and start mint, unless NIP-74 is its only frontend and proxies to an upstream mint
+        let mut bolt12 = false;
+        if self.config.service_mode.runs_mintd() || !self.proxies_upstream() {
+            let (mint, mint_info, routes_bolt12) = self.build_mint().await?;
+            let mint_arc = Arc::new(mint);
 
-        mint_arc.set_mint_info(mint_info).await?;
-        self.mint = Some(mint_arc.clone());
+            mint_arc.set_mint_info(mint_info).await?;
 
-        // Initialize mint
-        mint_arc.check_pending_mint_quotes().await?;
-        mint_arc.check_pending_melt_quotes().await?;
-        mint_arc
-            .set_quote_ttl(QuoteTTL::new(10_000, 10_000))
-            .await?;
+            // Initialize mint
+            mint_arc.check_pending_mint_quotes().await?;
+            mint_arc.check_pending_melt_quotes().await?;
+            mint_arc
+                .set_quote_ttl(QuoteTTL::new(10_000, 10_000))
+                .await?;
 
-        // Start HTTP server
-        info!("About to start HTTP server");
-        match self.start_http_server(mint_arc).await {
-            Ok(()) => {
-                info!("HTTP server started successfully");
+            self.mint = Some(mint_arc);
+            bolt12 = routes_bolt12;
+        } else {
+            info!("NIP-74 proxy mode, the local mint and Lightning backend are not started");
+        }
+
+        if self.config.embedded_relay.enabled {
+            let operator = self
+                .nsec
+                .as_ref()
+                .and_then(|nsec| nostr::Keys::parse(nsec).ok())
+                .map(|keys| keys.public_key());
+            self.embedded_relay = Some(EmbeddedRelay::new(self.config.embedded_relay.clone(), operator));
+        }
+
+        // Start HTTP server (also needed to serve the embedded relay)
+        if self.config.service_mode.runs_mintd() || self.embedded_relay.is_some() {
+            info!("About to start HTTP server");
+            match self.start_http_server(self.mint.clone(), bolt12).await {
+                Ok(()) => {
+                    info!("HTTP server started successfully");
+                }
+                Err(e) => {
+                    error!("HTTP server failed to start: {}", e);
+                    self.abort_start().await;
+                    return Err(e);
+                }
             }
-            Err(e) => {
-                error!("HTTP server failed to start: {}", e);
+        }
+
+        // Start NIP-74 relay listener
+        if self.config.service_mode.runs_nip74() {
+            if let Err(e) = self.start_nip74_service(self.mint.clone()).await {
+                error!("NIP-74 service failed to start: {}", e);
+                self.abort_start().await;
                 return Err(e);
             }
         }
@@ -312,17 +413,39 @@ impl MintdService {
         Ok(())
     }
 
-    async fn start_http_server(&mut self, mint: Arc<cdk::mint::Mint>) -> Result<()> {
+    /// Undo a partial start so the service can be started again
+    async fn abort_start(&mut self) {
+        if let Some(relay) = self.embedded_relay.take() {
+            relay.close();
+        }
+        self.shutdown.notify_waiters();
+        if let Some(http_server) = self.http_server.take() {
+            let _ = http_server.await;
+        }
+        self.rate_limiter = None;
+        self.mint = None;
+    }
+
+    async fn start_http_server(&mut self, mint: Option<Arc<cdk::mint::Mint>>, bolt12: bool) -> Result<()> {
         let listen_addr = self.config.info.listen_host.clone();
         let listen_port = self.config.info.listen_port;
 
         info!("Starting HTTP server on {}:{}", listen_addr, listen_port);
 
-        // Create mint router with default cache
-        let v1_service =
-            cdk_axum::create_mint_router_with_custom_cache(mint, HttpCache::default()).await?;
+        let mut router = Router::new();
+        if self.config.service_mode.runs_mintd() {
+            let mint = mint.ok_or_else(|| anyhow!("Mint not available"))?;
+            // Create mint router with default cache, adding the NUT-25 routes when BOLT12 is routed
+            let v1_service =
+                cdk_axum::create_mint_router_with_custom_cache(mint, HttpCache::default(), bolt12).await?;
+            router = router.merge(v1_service);
+        }
+        if let Some(relay) = &self.embedded_relay {
+            info!("Serving embedded Nostr relay on {}", relay.path());
+            router = router.merge(relay.router());
+        }
 
-        let mint_service = Router::new().merge(v1_service).layer(
+        let mint_service = router.layer(
             ServiceBuilder::new()
                 .layer(RequestDecompressionLayer::new())
                 .layer(CompressionLayer::new())
@@ -410,7 +533,181 @@ impl MintdService {
         }
     }
 
-    async fn build_mint(&self) -> Result<(cdk::mint::Mint, cdk::nuts::MintInfo)> {
+    /// Whether NIP-74 requests are proxied to an upstream mint
+    fn proxies_upstream(&self) -> bool {
+        self.config.service_mode.runs_nip74() && self.config.nip74.upstream_url.is_some()
+    }
+
+    /// Mint served over NIP-74: the in-process mint, or a proxy to the
+    /// configured upstream mint
+    fn nip74_mint_source(&self, mint: Option<Arc<cdk::mint::Mint>>) -> Result<MintInfoSource> {
+        let Some(upstream_url) = &self.config.nip74.upstream_url else {
+            let mint = mint.ok_or_else(|| anyhow!("Mint not available"))?;
+            return Ok(MintInfoSource::Local(mint));
+        };
+
+        // The embedded Tor client opens no SOCKS listener, so onion mints need an
+        // explicitly configured proxy (e.g. Orbot or a system Tor)
+        let proxy = self.config.nip74.upstream_proxy.as_deref();
+        let handler = DefaultRequestHandler::with_base_url(upstream_url, proxy)
+            .map_err(|e| anyhow!("Failed to create upstream mint proxy (set nip74.upstream_proxy for onion mints): {}", e))?;
+        info!("NIP-74 requests are proxied to {}", handler.base_url());
+        Ok(MintInfoSource::Upstream(Arc::new(handler)))
+    }
+
+    async fn start_nip74_service(&mut self, mint: Option<Arc<cdk::mint::Mint>>) -> Result<()> {
+        let nsec = self
+            .nsec
+            .as_ref()
+            .ok_or_else(|| anyhow!("NIP-74 mode requires an nsec"))?;
+        let keys =
+            nostr::Keys::parse(nsec).map_err(|e| anyhow!("Failed to parse nsec: {}", e))?;
+
+        let mut relays = self.config.relays.clone();
+        if let Some(url) = self.local_relay_url() {
+            relays.relays.push(RelayEntry::read_write(&url));
+        }
+        let source = self.nip74_mint_source(mint)?;
+        let keyset_units = source
+            .keysets()
+            .await?
+            .keysets
+            .into_iter()
+            .map(|keyset| (keyset.id.to_string(), keyset.unit));
+        let rate_limiter = Arc::new(
+            RateLimiter::open(self.work_dir.join("nip74_rate_limits.json"), self.config.rate_limits.clone())?
+                .with_keyset_units(keyset_units),
+        );
+        let mut layers = HandlerBuilder::new()
+            .layer(LoggingLayer)
+            .layer(MetricsLayer::new(self.nip74_metrics.clone()))
+            .layer(self.access_policy.layer())
+            .layer(rate_limiter.layer());
+        self.rate_limiter = Some(rate_limiter);
+
+        // Quote updates are pushed from cdk's subscription manager, so only the
+        // in-process mint can provide them
+        let (handler, quote_notifier): (Arc<dyn RequestHandler>, _) = match &source {
+            MintInfoSource::Local(mint) => (
+                Arc::new(DefaultMintHandler::from_arc(mint.clone())),
+                Some(QuoteNotifier::new(mint.clone(), keys.clone())),
+            ),
+            MintInfoSource::Upstream(upstream) => (upstream.clone(), None),
+        };
+        if let Some(notifier) = &quote_notifier {
+            layers = layers.layer(notifier.layer());
+        }
+
+        let handler = layers.service(handler);
+        let mut nip74_service = Nip74Service::new(keys.clone(), relays, handler);
+        nip74_service.set_min_pow(self.config.nip74.min_pow);
+        nip74_service.set_max_concurrent_requests(self.config.nip74.max_concurrent_requests);
+        if let Some(notifier) = quote_notifier {
+            nip74_service.set_quote_notifier(notifier);
+        }
+
+        let store = RequestStore::open(
+            self.work_dir.join("nip74_requests.json"),
+            self.config.nip74.replay_window,
+        )?;
+        nip74_service.set_request_store(Arc::new(store));
+        nip74_service.start().await?;
+
+        // Announce the mint on the relays it listens on, advertising the upstream
+        // mint's URL in proxy mode
+        let (clearnet_url, onion_url) = match &source {
+            MintInfoSource::Local(_) => (
+                self.config.service_mode.runs_mintd().then(|| self.config.info.url.clone()),
+                self.onion_url.clone(),
+            ),
+            MintInfoSource::Upstream(upstream) => {
+                let url = upstream.base_url().to_string();
+                let is_onion = reqwest::Url::parse(&url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(|host| host.ends_with(".onion")))
+                    .unwrap_or(false);
+                if is_onion { (None, Some(url)) } else { (Some(url), None) }
+            }
+        };
+        if let Some(client) = nip74_service.client() {
+            let announcement = MintAnnouncement {
+                identifier: self
+                    .config
+                    .nip74
+                    .identifier
+                    .clone()
+                    .unwrap_or_else(|| keys.public_key().to_hex()),
+                relays: self
+                    .config
+                    .relays
+                    .read_relays()
+                    .map(|r| r.url.clone())
+                    .chain(self.onion_relay_url())
+                    .filter_map(|url| nostr::RelayUrl::parse(&url).ok())
+                    .collect(),
+                clearnet_url,
+                onion_url,
+                min_pow: self.config.nip74.min_pow,
+                nip87_network: self
+                    .config
+                    .nip74
+                    .nip87
+                    .then(|| self.config.nip74.network.clone()),
+                interval: std::time::Duration::from_secs(self.config.nip74.announcement_interval),
+            };
+
+            let mut announcer = MintAnnouncer::new(client, keys, source, announcement);
+            if let Err(e) = announcer.start().await {
+                error!("Failed to publish mint announcement: {}", e);
+            }
+            self.announcer = Some(announcer);
+        }
+
+        self.nip74_service = Some(nip74_service);
+        Ok(())
+    }
+
+    /// Set the onion URL advertised in the NIP-74 announcement
+    pub fn set_onion_url(&mut self, onion_url: Option<String>) {
+        self.onion_url = onion_url;
+    }
+
+    /// Websocket URL the NIP-74 service uses to reach the embedded relay
+    fn local_relay_url(&self) -> Option<String> {
+        let relay = self.embedded_relay.as_ref()?;
+        let host = match self.config.info.listen_host.as_str() {
+            "0.0.0.0" | "::" | "" => "127.0.0.1",
+            host => host,
+        };
+        Some(format!("ws://{}:{}{}", host, self.config.info.listen_port, relay.path()))
+    }
+
+    /// Websocket URL of the embedded relay through the onion service
+    fn onion_relay_url(&self) -> Option<String> {
+        let relay = self.embedded_relay.as_ref()?;
+        let onion_url = self.onion_url.as_ref()?;
+        let host = onion_url.trim_start_matches("http://").trim_end_matches('/');
+        Some(format!("ws://{}{}", host, relay.path()))
+    }
+
+    /// Toggle maintenance status in the NIP-74 announcement
+    pub fn set_maintenance(&self, maintenance: bool) {
+        if let Some(announcer) = &self.announcer {
+            announcer.set_status(if maintenance {
+                MintStatus::Maintenance
+            } else {
+                MintStatus::Running
+            });
+        }
+    }
+
+    /// Client access policy enforced on NIP-74 requests, mutable at runtime
+    pub fn access_policy(&self) -> &AccessPolicy {
+        &self.access_policy
+    }
+
+    /// Build the mint, also reporting whether a BOLT12 backend was registered
+    async fn build_mint(&self) -> Result<(cdk::mint::Mint, cdk::nuts::MintInfo, bool)> {
         let database_path = self.work_dir.join("mint.db");
         let database = MintSqliteDatabase::new(database_path).await?;
 
@@ -418,6 +715,12 @@ impl MintdService {
             .with_localstore(Arc::new(database.clone()))
             .with_keystore(Arc::new(database));
 
+        // Build every configured Lightning backend; the routing table decides where they are used
+        let sat_msat = vec![cdk::nuts::CurrencyUnit::Sat, cdk::nuts::CurrencyUnit::Msat];
+        let mut backends: Vec<ConfiguredBackend> = Vec::new();
+        // Invoice watchers of the backends that poll for incoming payments
+        let mut watchers: Vec<(LnBackend, InvoiceWatcher)> = Vec::new();
+
         // Configure FakeWallet backend
         if let Some(fake_wallet_config) = &self.config.fake_wallet {
             let fee_reserve = cdk::types::FeeReserve {
@@ -432,19 +735,13 @@ impl MintdService {
                 fake_wallet_config.min_delay_time,
             );
 
-            for unit in &fake_wallet_config.supported_units {
-                mint_builder = mint_builder
-                    .add_ln_backend(
-                        unit.clone(),
-                        cdk::nuts::PaymentMethod::Bolt11,
-                        MintMeltLimits::new(
-                            self.config.ln.min_mint.into(),
-                            self.config.ln.max_mint.into(),
-                        ),
-                        Arc::new(fake_wallet.clone()),
-                    )
-                    .await?;
-            }
+            backends.push(ConfiguredBackend {
+                kind: LnBackend::FakeWallet,
+                units: fake_wallet_config.supported_units.clone(),
+                methods: vec![cdk::nuts::PaymentMethod::Bolt11],
+                backend: Arc::new(fake_wallet),
+                probe: None,
+            });
         }
 
         // Configure LNBits backend
@@ -463,21 +760,15 @@ impl MintdService {
             )
             .await?;
 
-            // Add LNBits backend for supported units (default to sat)
-            let supported_units = vec![cdk::nuts::CurrencyUnit::Sat, cdk::nuts::CurrencyUnit::Msat];
-            for unit in supported_units {
-                mint_builder = mint_builder
-                    .add_ln_backend(
-                        unit,
-                        cdk::nuts::PaymentMethod::Bolt11,
-                        MintMeltLimits::new(
-                            self.config.ln.min_mint.into(),
-                            self.config.ln.max_mint.into(),
-                        ),
-                        Arc::new(lnbits.clone()),
-                    )
-                    .await?;
-            }
+            let probe = HttpProbe::new(format!("{}/api/v1/wallet", lnbits_config.lnbits_api.trim_end_matches('/')))
+                .header("X-Api-Key", lnbits_config.invoice_api_key.clone());
+            backends.push(ConfiguredBackend {
+                kind: LnBackend::LNbits,
+                units: sat_msat.clone(),
+                methods: vec![cdk::nuts::PaymentMethod::Bolt11],
+                backend: Arc::new(lnbits),
+                probe: Some(Arc::new(probe)),
+            });
         }
 
         // Configure CLN backend
@@ -489,21 +780,118 @@ impl MintdService {
 
             let cln = cdk_cln::Cln::new(cln_config.rpc_path.clone().into(), fee_reserve).await?;
 
-            // Add CLN backend for supported units
-            let supported_units = vec![cdk::nuts::CurrencyUnit::Sat, cdk::nuts::CurrencyUnit::Msat];
-            for unit in supported_units {
-                mint_builder = mint_builder
-                    .add_ln_backend(
-                        unit,
-                        cdk::nuts::PaymentMethod::Bolt11,
-                        MintMeltLimits::new(
-                            self.config.ln.min_mint.into(),
-                            self.config.ln.max_mint.into(),
-                        ),
-                        Arc::new(cln.clone()),
-                    )
-                    .await?;
+            // Reusable BOLT12 offers (NUT-25) on the same node
+            let mut methods = vec![cdk::nuts::PaymentMethod::Bolt11];
+            if cln_config.bolt12 {
+                methods.push(cdk::nuts::PaymentMethod::Bolt12);
             }
+
+            backends.push(ConfiguredBackend {
+                kind: LnBackend::Cln,
+                units: sat_msat.clone(),
+                methods,
+                backend: Arc::new(cln),
+                probe: Some(Arc::new(ClnProbe::new(cln_config.rpc_path.clone()))),
+            });
+        }
+
+        // Configure LND backend
+        if let Some(lnd_config) = &self.config.lnd {
+            let fee_reserve = cdk::types::FeeReserve {
+                min_fee_reserve: lnd_config.reserve_fee_min,
+                percent_fee_reserve: lnd_config.fee_percent,
+            };
+
+            let lnd = crate::lnd::Lnd::new(
+                &lnd_config.address,
+                lnd_config.cert_file.as_deref().map(std::path::Path::new),
+                std::path::Path::new(&lnd_config.macaroon_file),
+                fee_reserve,
+            )?;
+
+            watchers.push((LnBackend::Lnd, lnd.invoices().clone()));
+            let lnd = Arc::new(lnd);
+            backends.push(ConfiguredBackend {
+                kind: LnBackend::Lnd,
+                units: sat_msat.clone(),
+                methods: vec![cdk::nuts::PaymentMethod::Bolt11],
+                backend: lnd.clone(),
+                probe: Some(lnd),
+            });
+        } else if self.config.ln.ln_backend == LnBackend::Lnd {
+            return Err(anyhow!("LND backend selected but LND address or macaroon is not configured"));
+        }
+
+        // Configure NWC backend
+        if let Some(nwc_config) = &self.config.nwc {
+            let fee_reserve = cdk::types::FeeReserve {
+                min_fee_reserve: nwc_config.reserve_fee_min,
+                percent_fee_reserve: nwc_config.fee_percent,
+            };
+
+            let fee_cap = crate::payment::FeeCap {
+                base_msat: u64::from(nwc_config.max_fee_base).saturating_mul(1_000),
+                ppm: nwc_config.max_fee_ppm,
+            };
+
+            let nwc = crate::nwc::Nwc::new(&nwc_config.uri, fee_reserve, fee_cap).await?;
+
+            watchers.push((LnBackend::Nwc, nwc.invoices().clone()));
+            let nwc = Arc::new(nwc);
+            backends.push(ConfiguredBackend {
+                kind: LnBackend::Nwc,
+                units: sat_msat.clone(),
+                methods: vec![cdk::nuts::PaymentMethod::Bolt11],
+                backend: nwc.clone(),
+                probe: Some(nwc),
+            });
+        } else if self.config.ln.ln_backend == LnBackend::Nwc {
+            return Err(anyhow!("NWC backend selected but no nostr+walletconnect URI is configured"));
+        }
+
+        // Configure phoenixd backend
+        if let Some(phoenixd_config) = &self.config.phoenixd {
+            let fee_reserve = cdk::types::FeeReserve {
+                min_fee_reserve: phoenixd_config.reserve_fee_min,
+                percent_fee_reserve: phoenixd_config.fee_percent,
+            };
+
+            let phoenixd = crate::phoenixd::Phoenixd::new(
+                &phoenixd_config.api_url,
+                &phoenixd_config.api_password,
+                fee_reserve,
+            )?;
+
+            watchers.push((LnBackend::Phoenixd, phoenixd.invoices().clone()));
+            let phoenixd = Arc::new(phoenixd);
+            backends.push(ConfiguredBackend {
+                kind: LnBackend::Phoenixd,
+                units: sat_msat.clone(),
+                methods: vec![cdk::nuts::PaymentMethod::Bolt11],
+                backend: phoenixd.clone(),
+                probe: Some(phoenixd),
+            });
+        } else if self.config.ln.ln_backend == LnBackend::Phoenixd {
+            return Err(anyhow!("Phoenixd backend selected but API URL or password is not configured"));
+        }
+
+        // Register each routed unit and method, wrapping fallback chains in failover backends
+        let registrations = resolve_routes(&self.config.routing, &backends)?;
+        let bolt12 = registrations
+            .iter()
+            .any(|registration| registration.method == cdk::nuts::PaymentMethod::Bolt12);
+        for registration in &registrations {
+            mint_builder = mint_builder
+                .add_ln_backend(
+                    registration.unit.clone(),
+                    registration.method.clone(),
+                    MintMeltLimits::new(
+                        self.config.ln.min_mint.into(),
+                        self.config.ln.max_mint.into(),
+                    ),
+                    registration.backend.clone(),
+                )
+                .await?;
         }
 
         // Set seed from nsec or mnemonic
@@ -569,7 +957,56 @@ impl MintdService {
         let mint = mint_builder.build().await?;
         mint.set_mint_info(mint_builder.mint_info.clone()).await?;
 
-        Ok((mint, mint_builder.mint_info.clone()))
+        // Watchers only live in memory, so pick the invoices of unpaid quotes up
+        // again, each on the backend that issued it
+        if !watchers.is_empty() {
+            let now = cdk::util::unix_time();
+            let mut seeded = 0;
+            for quote in mint.mint_quotes().await? {
+                if quote.state() != cdk::nuts::MintQuoteState::Unpaid || quote.expiry < now {
+                    continue;
+                }
+                let PaymentIdentifier::PaymentHash(hash) = quote.request_lookup_id else {
+                    continue;
+                };
+
+                let candidates: Vec<&(LnBackend, InvoiceWatcher)> = registrations
+                    .iter()
+                    .filter(|registration| {
+                        registration.unit == quote.unit && registration.method == quote.payment_method
+                    })
+                    .flat_map(|registration| registration.chain.iter())
+                    .filter_map(|kind| watchers.iter().find(|(watched, _)| watched == kind))
+                    .collect();
+                let issuer = match candidates.as_slice() {
+                    [] => None,
+                    [only] => Some(*only),
+                    // Any member of a failover chain may have issued it: only its node knows the invoice
+                    several => {
+                        let mut issuer = None;
+                        for candidate in several {
+                            let Some(configured) = backends.iter().find(|configured| configured.kind == candidate.0)
+                            else {
+                                continue;
+                            };
+                            let status = configured.backend.check_incoming_payment_status(&quote.request_lookup_id);
+                            if status.await.is_ok() {
+                                issuer = Some(*candidate);
+                                break;
+                            }
+                        }
+                        issuer
+                    }
+                };
+                if let Some((_, watcher)) = issuer {
+                    watcher.register(hash, quote.expiry);
+                    seeded += 1;
+                }
+            }
+            info!("Watching {} unpaid mint quotes for payment", seeded);
+        }
+
+        Ok((mint, mint_builder.mint_info.clone(), bolt12))
     }
 
     pub async fn stop(&mut self) -> Result<()> {
@@ -579,6 +1016,26 @@ impl MintdService {
 
         info!("Stopping MintdService...");
 
+        if let Some(mut announcer) = self.announcer.take() {
+            announcer.stop().await;
+        }
+
+        if let Some(mut nip74_service) = self.nip74_service.take() {
+            if let Err(e) = nip74_service.stop().await {
+                error!("Failed to stop NIP-74 service: {}", e);
+            }
+        }
+
+        if let Some(rate_limiter) = self.rate_limiter.take() {
+            if let Err(e) = rate_limiter.flush() {
+                error!("Failed to persist rate limiter state: {}", e);
+            }
+        }
+
+        if let Some(relay) = self.embedded_relay.take() {
+            relay.close();
+        }
+
         self.shutdown.notify_waiters();
 
         if let Some(http_server) = self.http_server.take() {
@@ -599,8 +1056,27 @@ impl MintdService {
             "running": self.is_running,
             "server_url": format!("http://{}:{}", self.config.info.listen_host, self.config.info.listen_port),
             "work_dir": self.work_dir.to_string_lossy(),
+            "service_mode": self.config.service_mode,
         });
 
+        if let Some(nip74_service) = &self.nip74_service {
+            status["nip74"] = nip74_service.get_status();
+            status["relays"] = serde_json::json!(nip74_service.relay_health());
+            status["nip74_metrics"] = self.nip74_metrics.snapshot();
+            status["access"] = serde_json::json!(self.access_policy.to_config());
+        }
+
+        if let Some(relay) = &self.embedded_relay {
+            status["embedded_relay"] = serde_json::json!({
+                "path": relay.path(),
+                "events": relay.event_count(),
+            });
+        }
+
+        if let Some(announcer) = &self.announcer {
+            status["announcement_status"] = serde_json::json!(announcer.status());
+        }
+
         status
     }
 
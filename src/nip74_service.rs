@@ -4,23 +4,67 @@
 //! - Type definitions and error handling
 //! - Event helper functions
 //! - Default request handlers
+//! - Relay listener runtime
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use anyhow::anyhow;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use nostr::event::tag::kind::TagKind;
+use nostr_sdk::{Client, RelayPoolNotification};
 use cdk::mint::Mint;
 use serde_json::json;
-use cdk::nuts::{MintQuoteBolt11Request, MeltQuoteBolt11Request, MintRequest, MeltRequest};
+use cdk::nuts::{
+    CheckStateRequest, Id, KeysetResponse, MeltQuoteBolt11Request, MeltQuoteBolt12Request, MeltRequest,
+    MintQuoteBolt11Request, MintQuoteBolt12Request, MintQuoteBolt12Response, MintRequest, RestoreRequest,
+    SwapRequest,
+};
 use serde_json::Value;
-use serde::de::Error as _;
 use reqwest;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Notify;
+use tracing::{debug, error, info, warn};
 
-use crate::MintInfo;
+use crate::config::RelayConfig;
+use crate::quote_notifier::QuoteNotifier;
+use crate::relay_manager::RelayManager;
+use crate::request_store::{RequestCheck, RequestStore};
 
 // ===== TYPE DEFINITIONS =====
 
+/// Event kind for NIP-74 operation requests.
+pub const KIND_OPERATION_REQUEST: u16 = 27401;
+
+/// Event kind for NIP-74 operation results.
+pub const KIND_OPERATION_RESULT: u16 = 27402;
+
+/// How far back gift wraps are fetched, covering NIP-59 timestamp tweaking (seconds).
+const GIFT_WRAP_LOOKBACK: u64 = 2 * 24 * 60 * 60;
+
+/// Event kind for NIP-74 mint information announcements.
+pub const KIND_MINT_INFO: u16 = 37400;
+
+/// Event kind for NIP-87 Cashu mint announcements.
+pub const KIND_CASHU_MINT_ANNOUNCEMENT: u16 = 38172;
+
+/// Announcement tag carrying the minimum NIP-13 difficulty of request events.
+pub const POW_TAG: &str = "pow";
+
+/// Largest serialized result sent in a single reply event, in bytes before
+/// encryption. Larger results are split into [`ResultChunk`]s.
+pub const MAX_RESULT_SIZE: usize = 16 * 1024;
+
+/// Reply tag marking a chunk of an oversized result: `["chunk", <index>, <total>]`.
+pub const CHUNK_TAG: &str = "chunk";
+
+/// Maximum number of sub-requests in a [`OperationMethod::Batch`] request.
+pub const MAX_BATCH_SIZE: usize = 20;
+
+/// Default number of requests a [`Nip74Service`] processes concurrently.
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 64;
+
 /// Crate-level error type for NIP-74 helpers.
 #[derive(Debug, thiserror::Error)]
 pub enum Nip74Error {
@@ -33,6 +77,24 @@ pub enum Nip74Error {
     /// Event builder error.
     #[error(transparent)]
     Nostr(#[from] nostr::event::builder::Error),
+    /// Relay client error.
+    #[error("relay error: {0}")]
+    Relay(String),
+    /// Invalid handler or client configuration.
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    /// Reply could not be reassembled.
+    #[error("invalid reply: {0}")]
+    InvalidReply(String),
+    /// Mint announcement is malformed or not validly signed.
+    #[error("invalid announcement: {0}")]
+    InvalidAnnouncement(String),
+    /// No reply was received in time.
+    #[error("request {0} timed out")]
+    Timeout(String),
+    /// The mint answered with an error result.
+    #[error("mint returned error {}: {}", .0.code, .0.message)]
+    Mint(ResultError),
 }
 
 /// Convenience result alias for NIP-74 helpers.
@@ -48,17 +110,197 @@ pub enum ResultStatus {
     Error,
 }
 
+/// Stable machine-readable error codes carried in [`ResultError::code`].
+///
+/// Cashu protocol failures mirror the NUT error codes (see [`ErrorCode::cashu_code`]);
+/// the remaining codes describe transport-level failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Request payload could not be decrypted.
+    DecryptionFailed,
+    /// Request or its `data` payload is malformed.
+    InvalidRequest,
+    /// Method is not supported by this mint.
+    UnsupportedMethod,
+    /// Request `created_at` is outside the freshness window.
+    RequestExpired,
+    /// Client pubkey is not admitted by the mint's access policy.
+    Unauthorized,
+    /// Client exceeded a rate limit or quota.
+    RateLimited,
+    /// Blinded message of an output has already been signed (10002).
+    BlindedMessageAlreadySigned,
+    /// Proof could not be verified (10003).
+    TokenNotVerified,
+    /// Proof is already spent (11001).
+    TokenAlreadySpent,
+    /// Inputs and outputs are not balanced (11002).
+    TransactionUnbalanced,
+    /// Unit is not supported (11005).
+    UnitUnsupported,
+    /// Amount is outside the mint's limit range (11006).
+    AmountOutOfRange,
+    /// Duplicate inputs provided (11007).
+    DuplicateInputs,
+    /// Duplicate outputs provided (11008).
+    DuplicateOutputs,
+    /// Inputs or outputs of multiple units (11009).
+    MultipleUnits,
+    /// Inputs and outputs are not of the same unit (11010).
+    UnitMismatch,
+    /// Keyset is not known (12001).
+    KeysetNotFound,
+    /// Keyset is inactive (12002).
+    KeysetInactive,
+    /// Quote has not been paid (20001).
+    QuoteNotPaid,
+    /// Tokens have already been issued for the quote (20002).
+    TokensAlreadyIssued,
+    /// Minting is disabled (20003).
+    MintingDisabled,
+    /// Lightning payment failed (20004).
+    LightningPaymentFailed,
+    /// Quote is pending (20005).
+    QuotePending,
+    /// Invoice is already paid (20006).
+    InvoiceAlreadyPaid,
+    /// Quote has expired (20007).
+    QuoteExpired,
+    /// Backend mint or Lightning node could not be reached.
+    BackendUnavailable,
+    /// Unexpected internal failure.
+    InternalError,
+    /// Code not known to this version.
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    /// Map a Cashu NUT error code to an [`ErrorCode`].
+    pub fn from_cashu_code(code: u16) -> Self {
+        match code {
+            10002 => ErrorCode::BlindedMessageAlreadySigned,
+            10003 => ErrorCode::TokenNotVerified,
+            11001 => ErrorCode::TokenAlreadySpent,
+            11002 => ErrorCode::TransactionUnbalanced,
+            11005 => ErrorCode::UnitUnsupported,
+            11006 => ErrorCode::AmountOutOfRange,
+            11007 => ErrorCode::DuplicateInputs,
+            11008 => ErrorCode::DuplicateOutputs,
+            11009 => ErrorCode::MultipleUnits,
+            11010 => ErrorCode::UnitMismatch,
+            12001 => ErrorCode::KeysetNotFound,
+            12002 => ErrorCode::KeysetInactive,
+            20001 => ErrorCode::QuoteNotPaid,
+            20002 => ErrorCode::TokensAlreadyIssued,
+            20003 => ErrorCode::MintingDisabled,
+            20004 => ErrorCode::LightningPaymentFailed,
+            20005 => ErrorCode::QuotePending,
+            20006 => ErrorCode::InvoiceAlreadyPaid,
+            20007 => ErrorCode::QuoteExpired,
+            _ => ErrorCode::InternalError,
+        }
+    }
+
+    /// Cashu NUT error code, if this code mirrors one.
+    pub fn cashu_code(&self) -> Option<u16> {
+        match self {
+            ErrorCode::BlindedMessageAlreadySigned => Some(10002),
+            ErrorCode::TokenNotVerified => Some(10003),
+            ErrorCode::TokenAlreadySpent => Some(11001),
+            ErrorCode::TransactionUnbalanced => Some(11002),
+            ErrorCode::UnitUnsupported => Some(11005),
+            ErrorCode::AmountOutOfRange => Some(11006),
+            ErrorCode::DuplicateInputs => Some(11007),
+            ErrorCode::DuplicateOutputs => Some(11008),
+            ErrorCode::MultipleUnits => Some(11009),
+            ErrorCode::UnitMismatch => Some(11010),
+            ErrorCode::KeysetNotFound => Some(12001),
+            ErrorCode::KeysetInactive => Some(12002),
+            ErrorCode::QuoteNotPaid => Some(20001),
+            ErrorCode::TokensAlreadyIssued => Some(20002),
+            ErrorCode::MintingDisabled => Some(20003),
+            ErrorCode::LightningPaymentFailed => Some(20004),
+            ErrorCode::QuotePending => Some(20005),
+            ErrorCode::InvoiceAlreadyPaid => Some(20006),
+            ErrorCode::QuoteExpired => Some(20007),
+            _ => None,
+        }
+    }
+
+    /// Wire representation of the code.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::DecryptionFailed => "decryption_failed",
+            ErrorCode::InvalidRequest => "invalid_request",
+            ErrorCode::UnsupportedMethod => "unsupported_method",
+            ErrorCode::RequestExpired => "request_expired",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::BlindedMessageAlreadySigned => "blinded_message_already_signed",
+            ErrorCode::TokenNotVerified => "token_not_verified",
+            ErrorCode::TokenAlreadySpent => "token_already_spent",
+            ErrorCode::TransactionUnbalanced => "transaction_unbalanced",
+            ErrorCode::UnitUnsupported => "unit_unsupported",
+            ErrorCode::AmountOutOfRange => "amount_out_of_range",
+            ErrorCode::DuplicateInputs => "duplicate_inputs",
+            ErrorCode::DuplicateOutputs => "duplicate_outputs",
+            ErrorCode::MultipleUnits => "multiple_units",
+            ErrorCode::UnitMismatch => "unit_mismatch",
+            ErrorCode::KeysetNotFound => "keyset_not_found",
+            ErrorCode::KeysetInactive => "keyset_inactive",
+            ErrorCode::QuoteNotPaid => "quote_not_paid",
+            ErrorCode::TokensAlreadyIssued => "tokens_already_issued",
+            ErrorCode::MintingDisabled => "minting_disabled",
+            ErrorCode::LightningPaymentFailed => "lightning_payment_failed",
+            ErrorCode::QuotePending => "quote_pending",
+            ErrorCode::InvoiceAlreadyPaid => "invoice_already_paid",
+            ErrorCode::QuoteExpired => "quote_expired",
+            ErrorCode::BackendUnavailable => "backend_unavailable",
+            ErrorCode::InternalError => "internal_error",
+            ErrorCode::Unknown => "unknown",
+        }
+    }
+}
+
 /// Error payload for a failed [`OperationResult`].
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ResultError {
-    /// Machine-readable error code.
+    /// Machine-readable error code (see [`ErrorCode`]).
     pub code: String,
     /// Human-readable error message.
     pub message: String,
+    /// Cashu NUT error code, when the failure mirrors one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cashu_code: Option<u16>,
+}
+
+impl ResultError {
+    /// Create an error payload for `code`.
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code: code.as_str().to_string(),
+            message: message.into(),
+            cashu_code: code.cashu_code(),
+        }
+    }
+
+    /// Map a cdk mint error to its stable error code.
+    pub fn from_mint_error(err: cdk::Error) -> Self {
+        let message = err.to_string();
+        let response = cdk_common::error::ErrorResponse::from(err);
+        Self::new(ErrorCode::from_cashu_code(response.code.to_code()), message)
+    }
+
+    /// Parsed error code; unknown codes map to [`ErrorCode::Unknown`].
+    pub fn error_code(&self) -> ErrorCode {
+        serde_json::from_value(Value::String(self.code.clone())).unwrap_or(ErrorCode::Unknown)
+    }
 }
 
 /// Supported NIP-74 operation methods.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OperationMethod {
     /// Static information about the mint.
@@ -75,6 +317,64 @@ pub enum OperationMethod {
     CheckMeltQuote,
     /// Perform melt using a quote.
     Melt,
+    /// BOLT12 offer quote for minting (NUT-25).
+    GetMintQuoteBolt12,
+    /// Check status of previously requested BOLT12 mint quote.
+    CheckMintQuoteBolt12,
+    /// Perform minting using a BOLT12 quote.
+    MintBolt12,
+    /// BOLT12 offer quote for melting (NUT-25).
+    GetMeltQuoteBolt12,
+    /// Check status of previously requested BOLT12 melt quote.
+    CheckMeltQuoteBolt12,
+    /// Perform melt using a BOLT12 quote.
+    MeltBolt12,
+    /// Swap proofs for new blind signatures (NUT-03).
+    Swap,
+    /// Check the state of proofs (NUT-07).
+    CheckState,
+    /// Restore blind signatures for outputs (NUT-09).
+    Restore,
+    /// Public keys of active keysets, or of one keyset (NUT-01).
+    Keys,
+    /// List of all keysets (NUT-02).
+    Keysets,
+    /// Several read-only requests in one event; `data` is the array of
+    /// sub-requests and the result `data` the matching array of results.
+    Batch,
+}
+
+impl OperationMethod {
+    /// Whether the method may be used inside a [`OperationMethod::Batch`].
+    ///
+    /// Only read-only methods are batchable so that state-changing operations
+    /// keep going through per-request policies such as replay protection and
+    /// quote notifications. Rate limits charge each sub-request under its own
+    /// method.
+    pub fn is_batchable(&self) -> bool {
+        matches!(
+            self,
+            OperationMethod::Info
+                | OperationMethod::CheckMintQuote
+                | OperationMethod::CheckMeltQuote
+                | OperationMethod::CheckMintQuoteBolt12
+                | OperationMethod::CheckMeltQuoteBolt12
+                | OperationMethod::CheckState
+                | OperationMethod::Keys
+                | OperationMethod::Keysets
+        )
+    }
+}
+
+/// Transport wrapping of a NIP-74 event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Envelope {
+    /// Plain kind 27401/27402 event with a NIP-44 encrypted payload.
+    #[default]
+    Direct,
+    /// NIP-59 gift wrap (kind 1059) around a sealed kind 27401/27402 rumor.
+    GiftWrap,
 }
 
 /// Request sent to a mint (kind 27401).
@@ -94,7 +394,8 @@ pub struct OperationRequest {
 pub struct OperationResult {
     /// Outcome status.
     pub status: ResultStatus,
-    /// Mirrors `request_id` from the originating [`OperationRequest`].
+    /// Mirrors `request_id` from the originating [`OperationRequest`], or is the
+    /// request event id when the request could not be read.
     pub request_id: String,
     /// Optional JSON payload.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -118,7 +419,7 @@ pub fn new_request_id() -> String {
 /// – `status` tag gives a quick health indicator (e.g. "running").
 /// Additional tags can be appended via `extra_tags`.
 pub async fn build_mint_info_event<S>(
-    mint_info: &MintInfo,
+    mint_info: &cdk::nuts::MintInfo,
     signer: &S,
     identifier: &str,
     relays: &[nostr::RelayUrl],
@@ -132,7 +433,7 @@ where
     let content = serde_json::to_string(mint_info)?;
 
     // Compose mandatory tags.
-    let mut builder = nostr::EventBuilder::new(nostr::Kind::from(37400u16), content)
+    let mut builder = nostr::EventBuilder::new(nostr::Kind::from(KIND_MINT_INFO), content)
         .tag(nostr::Tag::identifier(identifier.to_owned()))
         .tag(nostr::Tag::custom(
             TagKind::Relays,
@@ -150,301 +451,2238 @@ where
     Ok(event)
 }
 
-impl OperationResult {
-    /// Convert to `kind:27402` event and sign.
+/// NUT numbers supported by a mint, as advertised in NUT-06 `nuts`.
+///
+/// NUT-01 to NUT-03 are mandatory; optional NUTs count when they are listed
+/// and not marked `disabled`, unsupported or without methods.
+pub fn supported_nuts(mint_info: &cdk::nuts::MintInfo) -> Vec<u16> {
+    let mut nuts = vec![1, 2, 3];
+    if let Ok(Value::Object(settings)) = serde_json::to_value(&mint_info.nuts) {
+        for (nut, settings) in settings {
+            let disabled = settings.get("disabled").and_then(Value::as_bool).unwrap_or(false);
+            let supported = match settings.get("supported").or_else(|| settings.get("methods")) {
+                Some(Value::Bool(supported)) => *supported,
+                Some(Value::Array(methods)) => !methods.is_empty(),
+                _ => true,
+            };
+            if disabled || !supported {
+                continue;
+            }
+            if let Ok(nut) = nut.parse() {
+                nuts.push(nut);
+            }
+        }
+    }
+    // BOLT12 (NUT-25) is advertised as a NUT-04 payment method
+    if mint_info
+        .nuts
+        .nut04
+        .methods
+        .iter()
+        .any(|settings| settings.method == cdk::nuts::PaymentMethod::Bolt12)
+    {
+        nuts.push(25);
+    }
+    nuts.sort_unstable();
+    nuts.dedup();
+    nuts
+}
+
+/// Build a NIP-87 `kind:38172` Cashu mint announcement:
+/// – `d` tag stores the mint's NUT-06 pubkey (the signer's pubkey when unset);
+/// – one `u` tag per HTTP URL of the mint;
+/// – `nuts` tag lists the supported NUT numbers and `n` the Bitcoin network;
+/// – `content` carries kind:0 style metadata (name, about, picture).
+pub async fn build_nip87_announcement_event<S>(
+    mint_info: &cdk::nuts::MintInfo,
+    signer: &S,
+    urls: &[String],
+    network: &str,
+) -> Nip74Result<nostr::Event>
+where
+    S: nostr::NostrSigner,
+{
+    let identifier = match &mint_info.pubkey {
+        Some(pubkey) => pubkey.to_string(),
+        None => signer.get_public_key().await?.to_hex(),
+    };
+
+    let mut metadata = serde_json::Map::new();
+    for (key, value) in [
+        ("name", &mint_info.name),
+        ("about", &mint_info.description),
+        ("picture", &mint_info.icon_url),
+    ] {
+        if let Some(value) = value {
+            metadata.insert(key.to_string(), Value::String(value.clone()));
+        }
+    }
+
+    let nuts = supported_nuts(mint_info)
+        .iter()
+        .map(u16::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    let event = nostr::EventBuilder::new(
+        nostr::Kind::from(KIND_CASHU_MINT_ANNOUNCEMENT),
+        Value::Object(metadata).to_string(),
+    )
+    .tag(nostr::Tag::identifier(identifier))
+    .tags(urls.iter().map(|url| nostr::Tag::custom(TagKind::custom("u"), [url.clone()])))
+    .tag(nostr::Tag::custom(TagKind::custom("nuts"), [nuts]))
+    .tag(nostr::Tag::custom(TagKind::custom("n"), [network.to_owned()]))
+    .sign(signer)
+    .await?;
+    Ok(event)
+}
+
+impl OperationRequest {
+    /// Create a request with a fresh request id.
+    pub fn new(method: OperationMethod, data: Option<serde_json::Value>) -> Self {
+        Self {
+            method,
+            request_id: new_request_id(),
+            data,
+        }
+    }
+
+    /// Batch of `requests`, answered in one reply.
+    pub fn batch(requests: Vec<OperationRequest>) -> Self {
+        Self::new(OperationMethod::Batch, Some(json!(requests)))
+    }
+
+    /// Convert to `kind:27401` event addressed to `mint_pubkey` and sign.
     pub async fn to_event_with_signer<T>(
         &self,
         signer: &T,
-        author_pubkey: &nostr::PublicKey,
-        receiver_pubkey: &nostr::PublicKey,
-        request_event_id: &nostr::EventId,
+        mint_pubkey: &nostr::PublicKey,
+        extra_tags: Option<Vec<nostr::Tag>>,
+    ) -> Nip74Result<nostr::Event>
+    where
+        T: nostr::NostrSigner,
+    {
+        self.to_event_with_pow(signer, mint_pubkey, 0, extra_tags).await
+    }
+
+    /// Like [`Self::to_event_with_signer`], mining a NIP-13 nonce so the event id
+    /// has at least `difficulty` leading zero bits (`0` disables mining).
+    pub async fn to_event_with_pow<T>(
+        &self,
+        signer: &T,
+        mint_pubkey: &nostr::PublicKey,
+        difficulty: u8,
         extra_tags: Option<Vec<nostr::Tag>>,
-    ) -> nostr::Result<nostr::Event>
+    ) -> Nip74Result<nostr::Event>
     where
         T: nostr::NostrSigner,
     {
-        // Serialize response content
         let content_str = serde_json::to_string(self)?;
-        
-        // Use NIP-44 encryption
-        let encrypted_content = signer.nip44_encrypt(receiver_pubkey, &content_str).await?;
-        
-        let mut builder = nostr::EventBuilder::new(nostr::Kind::from(27402u16), encrypted_content)
-            .tag(nostr::Tag::public_key(*receiver_pubkey))
-            .tag(nostr::Tag::event(*request_event_id));
+        let encrypted_content = signer.nip44_encrypt(mint_pubkey, &content_str).await?;
+
+        let mut builder = nostr::EventBuilder::new(nostr::Kind::from(KIND_OPERATION_REQUEST), encrypted_content)
+            .tag(nostr::Tag::public_key(*mint_pubkey));
 
         if let Some(tags) = extra_tags {
             builder = builder.tags(tags);
         }
-
-        // NIP-74 spec doesn't enforce, but we set the author explicitly for clarity.
-        builder = builder.allow_self_tagging();
+        if difficulty > 0 {
+            builder = builder.pow(difficulty);
+        }
 
         let event = builder.sign(signer).await?;
-        // Ensure builder signed with the provided author_pubkey if needed.
-        debug_assert_eq!(event.pubkey, *author_pubkey);
         Ok(event)
     }
-}
 
-// ===== REQUEST HANDLER TRAIT =====
+    /// Convert to a NIP-59 gift wrap around a sealed `kind:27401` rumor addressed
+    /// to `mint_pubkey`.
+    ///
+    /// Returns the wrap together with the rumor id, which replies reference.
+    pub async fn to_gift_wrap_with_signer<T>(
+        &self,
+        signer: &T,
+        mint_pubkey: &nostr::PublicKey,
+        extra_tags: Option<Vec<nostr::Tag>>,
+    ) -> Nip74Result<(nostr::Event, nostr::EventId)>
+    where
+        T: nostr::NostrSigner,
+    {
+        self.to_gift_wrap_with_pow(signer, mint_pubkey, 0, extra_tags).await
+    }
 
-/// Request handler trait – application implements custom business logic.
-#[async_trait]
-pub trait RequestHandler: Send + Sync + 'static {
-    /// Handle an OperationRequest and return the OperationResult.
-    async fn handle(&self, req: OperationRequest) -> Nip74Result<OperationResult>;
-}
+    /// Like [`Self::to_gift_wrap_with_signer`], mining the NIP-13 nonce on the
+    /// outer wrap, whose id the mint checks before decrypting anything.
+    pub async fn to_gift_wrap_with_pow<T>(
+        &self,
+        signer: &T,
+        mint_pubkey: &nostr::PublicKey,
+        difficulty: u8,
+        extra_tags: Option<Vec<nostr::Tag>>,
+    ) -> Nip74Result<(nostr::Event, nostr::EventId)>
+    where
+        T: nostr::NostrSigner,
+    {
+        let content_str = serde_json::to_string(self)?;
+        let author = signer.get_public_key().await?;
 
-// ===== DEFAULT REQUEST HANDLERS =====
+        let mut rumor = nostr::EventBuilder::new(nostr::Kind::from(KIND_OPERATION_REQUEST), content_str)
+            .tag(nostr::Tag::public_key(*mint_pubkey))
+            .build(author);
+        rumor.ensure_id();
+        let rumor_id = rumor.id.ok_or_else(|| Nip74Error::Config("rumor id missing".into()))?;
 
-/// Default request handler that proxies requests to local mintd HTTP API
-pub struct DefaultRequestHandler {
-    mintd_port: u16,
+        let seal = nostr::EventBuilder::seal(signer, mint_pubkey, rumor).await?.sign(signer).await?;
+
+        // Same as `EventBuilder::gift_wrap_from_seal`, which cannot mine the wrap
+        let wrap_keys = nostr::Keys::generate();
+        let content = nostr::nips::nip44::encrypt(
+            wrap_keys.secret_key(),
+            mint_pubkey,
+            nostr::JsonUtil::as_json(&seal),
+            nostr::nips::nip44::Version::default(),
+        )
+        .map_err(nostr::event::builder::Error::from)?;
+        let mut builder = nostr::EventBuilder::new(nostr::Kind::GiftWrap, content)
+            .tags(extra_tags.unwrap_or_default())
+            .tag(nostr::Tag::public_key(*mint_pubkey))
+            .custom_created_at(nostr::Timestamp::tweaked(nostr::nips::nip59::RANGE_RANDOM_TIMESTAMP_TWEAK));
+        if difficulty > 0 {
+            builder = builder.pow(difficulty);
+        }
+        let wrap = builder.sign_with_keys(&wrap_keys)?;
+        Ok((wrap, rumor_id))
+    }
 }
 
-impl DefaultRequestHandler {
-    pub fn new(mintd_port: u16) -> Self {
-        Self { mintd_port }
+impl OperationResult {
+    /// Successful result carrying `data`.
+    pub fn success(request_id: impl Into<String>, data: Value) -> Self {
+        Self {
+            status: ResultStatus::Success,
+            request_id: request_id.into(),
+            data: Some(data),
+            error: None,
+        }
     }
 
-    /// Convert NIP-74 operation to mintd HTTP endpoint
-    fn get_mintd_endpoint(&self, method: &OperationMethod) -> String {
-        match method {
-            OperationMethod::Info => "/v1/info".to_string(),
-            OperationMethod::GetMintQuote => "/v1/mint/quote".to_string(),
-            OperationMethod::CheckMintQuote => "/v1/mint/quote/check".to_string(),
-            OperationMethod::Mint => "/v1/mint".to_string(),
-            OperationMethod::GetMeltQuote => "/v1/melt/quote".to_string(),
-            OperationMethod::CheckMeltQuote => "/v1/melt/quote/check".to_string(),
-            OperationMethod::Melt => "/v1/melt".to_string(),
-        }
-    }
-
-    /// Make HTTP request to mintd
-    async fn call_mintd(&self, endpoint: &str, payload: Value) -> Result<Value, Nip74Error> {
-        let url = format!("http://localhost:{}{}", self.mintd_port, endpoint);
-        
-        let client = reqwest::Client::new();
-        let response = client
-            .post(&url)
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| Nip74Error::Serde(serde_json::Error::custom(format!("HTTP request failed: {}", e))))?;
+    /// Failed result carrying `error`.
+    pub fn failure(request_id: impl Into<String>, error: ResultError) -> Self {
+        Self {
+            status: ResultStatus::Error,
+            request_id: request_id.into(),
+            data: None,
+            error: Some(error),
+        }
+    }
 
-        let status = response.status();
-        let text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        if status.is_success() {
-            let result: Value = serde_json::from_str(&text)
-                .map_err(|e| Nip74Error::Serde(serde_json::Error::custom(format!("Failed to parse response: {}", e))))?;
-            Ok(result)
-        } else {
-            Err(Nip74Error::Serde(serde_json::Error::custom(format!("Mintd request failed: {} - {}", status, text))))
+    /// Convert to `kind:27402` event and sign.
+    pub async fn to_event_with_signer<T>(
+        &self,
+        signer: &T,
+        author_pubkey: &nostr::PublicKey,
+        receiver_pubkey: &nostr::PublicKey,
+        request_event_id: &nostr::EventId,
+        extra_tags: Option<Vec<nostr::Tag>>,
+    ) -> Nip74Result<nostr::Event>
+    where
+        T: nostr::NostrSigner,
+    {
+        let content_str = serde_json::to_string(self)?;
+        let event = sign_reply(signer, content_str, receiver_pubkey, request_event_id, extra_tags).await?;
+        // Ensure builder signed with the provided author_pubkey if needed.
+        debug_assert_eq!(event.pubkey, *author_pubkey);
+        Ok(event)
+    }
+
+    /// Convert to a NIP-59 gift wrap around a sealed `kind:27402` rumor addressed
+    /// to `receiver_pubkey`.
+    pub async fn to_gift_wrap_with_signer<T>(
+        &self,
+        signer: &T,
+        receiver_pubkey: &nostr::PublicKey,
+        request_event_id: &nostr::EventId,
+        extra_tags: Option<Vec<nostr::Tag>>,
+    ) -> Nip74Result<nostr::Event>
+    where
+        T: nostr::NostrSigner,
+    {
+        let content_str = serde_json::to_string(self)?;
+        wrap_reply(signer, content_str, receiver_pubkey, request_event_id, extra_tags).await
+    }
+
+    /// Convert to a reply in the given `envelope`.
+    pub async fn to_envelope_with_signer<T>(
+        &self,
+        signer: &T,
+        envelope: Envelope,
+        receiver_pubkey: &nostr::PublicKey,
+        request_event_id: &nostr::EventId,
+        extra_tags: Option<Vec<nostr::Tag>>,
+    ) -> Nip74Result<nostr::Event>
+    where
+        T: nostr::NostrSigner,
+    {
+        let content_str = serde_json::to_string(self)?;
+        encode_reply(signer, envelope, content_str, receiver_pubkey, request_event_id, extra_tags).await
+    }
+
+    /// Convert to the reply events to publish, in order.
+    ///
+    /// Results larger than [`MAX_RESULT_SIZE`] are split into individually
+    /// encrypted [`ResultChunk`] events tagged with [`CHUNK_TAG`]; smaller ones
+    /// are sent as a single reply.
+    pub async fn to_reply_events_with_signer<T>(
+        &self,
+        signer: &T,
+        envelope: Envelope,
+        receiver_pubkey: &nostr::PublicKey,
+        request_event_id: &nostr::EventId,
+        extra_tags: Option<Vec<nostr::Tag>>,
+    ) -> Nip74Result<Vec<nostr::Event>>
+    where
+        T: nostr::NostrSigner,
+    {
+        let chunks = ResultChunk::split(self, MAX_RESULT_SIZE)?;
+        if chunks.len() <= 1 {
+            let event = self
+                .to_envelope_with_signer(signer, envelope, receiver_pubkey, request_event_id, extra_tags)
+                .await?;
+            return Ok(vec![event]);
+        }
+
+        debug!("Splitting result {} into {} chunks", self.request_id, chunks.len());
+        let mut events = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let mut tags = extra_tags.clone().unwrap_or_default();
+            tags.push(chunk.tag());
+            let content_str = serde_json::to_string(&chunk)?;
+            events.push(encode_reply(signer, envelope, content_str, receiver_pubkey, request_event_id, Some(tags)).await?);
+        }
+        Ok(events)
+    }
+
+    /// Open a kind 27402 reply from `mint_pubkey`, direct or gift-wrapped.
+    ///
+    /// Returns the result and the id of the request event it references, or
+    /// `None` if `event` is not such a reply. Chunks of oversized results are
+    /// opened with [`Reply::from_event`].
+    pub async fn from_reply_event<T>(
+        signer: &T,
+        mint_pubkey: &nostr::PublicKey,
+        event: &nostr::Event,
+    ) -> Option<(Self, Option<nostr::EventId>)>
+    where
+        T: nostr::NostrSigner,
+    {
+        match Reply::from_event(signer, mint_pubkey, event).await? {
+            (Reply::Result(result), reply_to) => Some((result, reply_to)),
+            (Reply::Chunk(_), _) => None,
         }
     }
 }
 
-#[async_trait]
-impl RequestHandler for DefaultRequestHandler {
-    async fn handle(&self, req: OperationRequest) -> Nip74Result<OperationResult> {
-        let endpoint = self.get_mintd_endpoint(&req.method);
-        
-        // Convert OperationRequest to mintd payload
-        let payload = serde_json::json!({
-            "request_id": req.request_id,
-            "data": req.data,
-        });
+/// Sign a direct `kind:27402` reply carrying NIP-44 encrypted `content`.
+async fn sign_reply<T>(
+    signer: &T,
+    content: String,
+    receiver_pubkey: &nostr::PublicKey,
+    request_event_id: &nostr::EventId,
+    extra_tags: Option<Vec<nostr::Tag>>,
+) -> Nip74Result<nostr::Event>
+where
+    T: nostr::NostrSigner,
+{
+    let encrypted_content = signer.nip44_encrypt(receiver_pubkey, &content).await?;
 
-        // Call mintd
-        let result = self.call_mintd(&endpoint, payload).await?;
+    let mut builder = nostr::EventBuilder::new(nostr::Kind::from(KIND_OPERATION_RESULT), encrypted_content)
+        .tag(nostr::Tag::public_key(*receiver_pubkey))
+        .tag(nostr::Tag::event(*request_event_id));
 
-        // Convert mintd response to OperationResult
-        Ok(OperationResult {
-            status: ResultStatus::Success,
-            request_id: req.request_id,
-            data: Some(result),
-            error: None,
-        })
+    if let Some(tags) = extra_tags {
+        builder = builder.tags(tags);
     }
+
+    // NIP-74 spec doesn't enforce, but we set the author explicitly for clarity.
+    builder = builder.allow_self_tagging();
+
+    Ok(builder.sign(signer).await?)
 }
 
-/// Default handler that bridges NIP-74 requests to the underlying Cashu `Mint` implementation.
-pub struct DefaultMintHandler {
-    mint: Arc<Mint>,
+/// Gift-wrap a `kind:27402` reply rumor carrying `content`.
+async fn wrap_reply<T>(
+    signer: &T,
+    content: String,
+    receiver_pubkey: &nostr::PublicKey,
+    request_event_id: &nostr::EventId,
+    extra_tags: Option<Vec<nostr::Tag>>,
+) -> Nip74Result<nostr::Event>
+where
+    T: nostr::NostrSigner,
+{
+    let author = signer.get_public_key().await?;
+
+    let mut builder = nostr::EventBuilder::new(nostr::Kind::from(KIND_OPERATION_RESULT), content)
+        .tag(nostr::Tag::public_key(*receiver_pubkey))
+        .tag(nostr::Tag::event(*request_event_id));
+    if let Some(tags) = extra_tags {
+        builder = builder.tags(tags);
+    }
+    let rumor = builder.build(author);
+
+    let wrap = nostr::EventBuilder::gift_wrap(signer, receiver_pubkey, rumor, []).await?;
+    Ok(wrap)
 }
 
-impl DefaultMintHandler {
-    /// Create new handler from an instantiated [`Mint`].
-    pub fn new(mint: Mint) -> Self {
-        Self { mint: Arc::new(mint) }
+/// Encode a `kind:27402` reply carrying `content` in the given `envelope`.
+async fn encode_reply<T>(
+    signer: &T,
+    envelope: Envelope,
+    content: String,
+    receiver_pubkey: &nostr::PublicKey,
+    request_event_id: &nostr::EventId,
+    extra_tags: Option<Vec<nostr::Tag>>,
+) -> Nip74Result<nostr::Event>
+where
+    T: nostr::NostrSigner,
+{
+    match envelope {
+        Envelope::Direct => sign_reply(signer, content, receiver_pubkey, request_event_id, extra_tags).await,
+        Envelope::GiftWrap => wrap_reply(signer, content, receiver_pubkey, request_event_id, extra_tags).await,
     }
 }
 
-#[async_trait]
-impl RequestHandler for DefaultMintHandler {
-    async fn handle(&self, req: OperationRequest) -> Nip74Result<OperationResult> {
-        match req.method {
-            OperationMethod::Info => {
-                // For Info we just relay the static mint info.
-                match self.mint.mint_info().await {
-                    Ok(info) => Ok(OperationResult {
-                        status: ResultStatus::Success,
-                        request_id: req.request_id,
-                        data: Some(json!({ "info": info })),
-                        error: None,
-                    }),
-                    Err(e) => Ok(OperationResult {
-                        status: ResultStatus::Error,
-                        request_id: req.request_id,
-                        data: None,
-                        error: Some(ResultError {
-                            code: "info_failed".into(),
-                            message: e.to_string(),
-                        }),
-                    }),
-                }
-            }
-            OperationMethod::GetMintQuote => {
-                // Parse request payload
-                let request: MintQuoteBolt11Request = serde_json::from_value(req.data.unwrap_or(Value::Null))?;
-                match self.mint.get_mint_bolt11_quote(request).await {
-                    Ok(quote) => Ok(OperationResult {
-                        status: ResultStatus::Success,
-                        request_id: req.request_id,
-                        data: Some(json!(quote)),
-                        error: None,
-                    }),
-                    Err(e) => Ok(OperationResult {
-                        status: ResultStatus::Error,
-                        request_id: req.request_id,
-                        data: None,
-                        error: Some(ResultError {
-                            code: "get_mint_quote_failed".into(),
-                            message: e.to_string(),
-                        }),
-                    }),
-                }
-            }
-            OperationMethod::CheckMintQuote => {
-                let quote_id: Uuid = serde_json::from_value(req.data.unwrap_or(Value::Null))?;
-                match self.mint.check_mint_quote(&quote_id).await {
-                    Ok(quote) => Ok(OperationResult {
-                        status: ResultStatus::Success,
-                        request_id: req.request_id,
-                        data: Some(json!(quote)),
-                        error: None,
-                    }),
-                    Err(e) => Ok(OperationResult {
-                        status: ResultStatus::Error,
-                        request_id: req.request_id,
-                        data: None,
-                        error: Some(ResultError {
-                            code: "check_mint_quote_failed".into(),
-                            message: e.to_string(),
-                        }),
-                    }),
-                }
-            }
-            OperationMethod::Mint => {
-                let mint_req_str: MintRequest<String> = serde_json::from_value(req.data.unwrap_or(Value::Null))?;
-                let mint_req_uuid: MintRequest<Uuid> = mint_req_str.try_into().map_err(|e| serde_json::Error::custom(e))?;
-                match self.mint.process_mint_request(mint_req_uuid).await {
-                    Ok(response) => Ok(OperationResult {
-                        status: ResultStatus::Success,
-                        request_id: req.request_id,
-                        data: Some(json!(response)),
-                        error: None,
-                    }),
-                    Err(e) => Ok(OperationResult {
-                        status: ResultStatus::Error,
-                        request_id: req.request_id,
-                        data: None,
-                        error: Some(ResultError {
-                            code: "mint_failed".into(),
-                            message: e.to_string(),
-                        }),
-                    }),
-                }
-            }
-            OperationMethod::GetMeltQuote => {
-                let request: MeltQuoteBolt11Request = serde_json::from_value(req.data.unwrap_or(Value::Null))?;
-                match self.mint.get_melt_bolt11_quote(&request).await {
-                    Ok(quote) => Ok(OperationResult {
-                        status: ResultStatus::Success,
-                        request_id: req.request_id,
-                        data: Some(json!(quote)),
-                        error: None,
-                    }),
-                    Err(e) => Ok(OperationResult {
-                        status: ResultStatus::Error,
-                        request_id: req.request_id,
-                        data: None,
-                        error: Some(ResultError {
-                            code: "get_melt_quote_failed".into(),
-                            message: e.to_string(),
-                        }),
-                    }),
-                }
-            }
-            OperationMethod::CheckMeltQuote => {
-                let quote_id: Uuid = serde_json::from_value(req.data.unwrap_or(Value::Null))?;
-                match self.mint.check_melt_quote(&quote_id).await {
-                    Ok(quote) => Ok(OperationResult {
-                        status: ResultStatus::Success,
-                        request_id: req.request_id,
-                        data: Some(json!(quote)),
-                        error: None,
-                    }),
-                    Err(e) => Ok(OperationResult {
-                        status: ResultStatus::Error,
-                        request_id: req.request_id,
-                        data: None,
-                        error: Some(ResultError {
-                            code: "check_melt_quote_failed".into(),
-                            message: e.to_string(),
-                        }),
-                    }),
-                }
-            }
-            OperationMethod::Melt => {
-                let melt_req_str: MeltRequest<String> = serde_json::from_value(req.data.unwrap_or(Value::Null))?;
-                let melt_req_uuid: MeltRequest<Uuid> = melt_req_str.try_into().map_err(|e| serde_json::Error::custom(e))?;
-                match self.mint.melt_bolt11(&melt_req_uuid).await {
-                    Ok(response) => Ok(OperationResult {
-                        status: ResultStatus::Success,
-                        request_id: req.request_id,
-                        data: Some(json!(response)),
-                        error: None,
-                    }),
-                    Err(e) => Ok(OperationResult {
-                        status: ResultStatus::Error,
-                        request_id: req.request_id,
-                        data: None,
-                        error: Some(ResultError {
-                            code: "melt_failed".into(),
-                            message: e.to_string(),
-                        }),
-                    }),
-                }
+// ===== RESULT CHUNKING =====
+
+/// One part of an [`OperationResult`] too large for a single reply event.
+///
+/// `data` is a slice of the serialized result; concatenating the slices of
+/// all `total` chunks in `index` order yields the result JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultChunk {
+    /// Request id of the chunked result.
+    pub request_id: String,
+    /// Zero-based position of this chunk.
+    pub index: u32,
+    /// Number of chunks of the result.
+    pub total: u32,
+    /// Slice of the serialized result.
+    pub data: String,
+}
+
+impl ResultChunk {
+    /// Split the serialized `result` into slices of at most `max_size` bytes.
+    ///
+    /// Returns a single chunk when the result fits.
+    pub fn split(result: &OperationResult, max_size: usize) -> Nip74Result<Vec<Self>> {
+        let json = serde_json::to_string(result)?;
+        let max_size = max_size.max(4);
+
+        let mut slices = Vec::new();
+        let mut start = 0;
+        while start < json.len() {
+            let mut end = (start + max_size).min(json.len());
+            while !json.is_char_boundary(end) {
+                end -= 1;
             }
+            slices.push(&json[start..end]);
+            start = end;
         }
+
+        let total = slices.len() as u32;
+        Ok(slices
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| Self {
+                request_id: result.request_id.clone(),
+                index: index as u32,
+                total,
+                data: data.to_string(),
+            })
+            .collect())
+    }
+
+    /// `["chunk", <index>, <total>]` tag of this chunk.
+    pub fn tag(&self) -> nostr::Tag {
+        nostr::Tag::custom(
+            TagKind::custom(CHUNK_TAG),
+            [self.index.to_string(), self.total.to_string()],
+        )
     }
 }
 
-// ===== TESTS =====
+/// Collects the chunks of one result and rebuilds it once all arrived.
+#[derive(Debug, Default)]
+pub struct ResultAssembler {
+    total: Option<u32>,
+    parts: BTreeMap<u32, String>,
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json;
+impl ResultAssembler {
+    /// Create an empty assembler.
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    #[test]
-    fn test_result_status_serde() {
-        let ok = ResultStatus::Success;
-        let err = ResultStatus::Error;
-        let ok_json = serde_json::to_string(&ok).unwrap();
-        let err_json = serde_json::to_string(&err).unwrap();
+    /// Add `chunk`, returning the result once every chunk has been received.
+    ///
+    /// Chunks may arrive in any order and duplicates are ignored.
+    pub fn push(&mut self, chunk: ResultChunk) -> Nip74Result<Option<OperationResult>> {
+        if chunk.total == 0 || chunk.index >= chunk.total || self.total.is_some_and(|t| t != chunk.total) {
+            return Err(Nip74Error::InvalidReply(format!(
+                "inconsistent chunk {}/{} of result {}",
+                chunk.index, chunk.total, chunk.request_id
+            )));
+        }
+        self.total = Some(chunk.total);
+        self.parts.entry(chunk.index).or_insert(chunk.data);
+
+        if self.parts.len() as u32 != chunk.total {
+            return Ok(None);
+        }
+        let json: String = self.parts.values().map(String::as_str).collect();
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    /// Number of chunks received so far.
+    pub fn received(&self) -> usize {
+        self.parts.len()
+    }
+}
+
+/// Decrypted kind 27402 reply: a whole result or a chunk of one.
+#[derive(Debug, Clone)]
+pub enum Reply {
+    /// Complete result.
+    Result(OperationResult),
+    /// Chunk of an oversized result.
+    Chunk(ResultChunk),
+}
+
+impl Reply {
+    /// Open a kind 27402 reply from `mint_pubkey`, direct or gift-wrapped.
+    ///
+    /// Returns the reply and the id of the request event it references, or
+    /// `None` if `event` is not such a reply.
+    pub async fn from_event<T>(
+        signer: &T,
+        mint_pubkey: &nostr::PublicKey,
+        event: &nostr::Event,
+    ) -> Option<(Self, Option<nostr::EventId>)>
+    where
+        T: nostr::NostrSigner,
+    {
+        let (tags, content) = if event.kind == nostr::Kind::GiftWrap {
+            let unwrapped = nostr::nips::nip59::UnwrappedGift::from_gift_wrap(signer, event).await.ok()?;
+            if unwrapped.sender != *mint_pubkey
+                || unwrapped.rumor.pubkey != *mint_pubkey
+                || unwrapped.rumor.kind != nostr::Kind::from(KIND_OPERATION_RESULT)
+            {
+                return None;
+            }
+            (unwrapped.rumor.tags, unwrapped.rumor.content)
+        } else if event.kind == nostr::Kind::from(KIND_OPERATION_RESULT) && event.pubkey == *mint_pubkey {
+            let plaintext = match signer.nip44_decrypt(mint_pubkey, &event.content).await {
+                Ok(plaintext) => plaintext,
+                Err(e) => {
+                    warn!("Failed to decrypt reply {}: {}", event.id, e);
+                    return None;
+                }
+            };
+            (event.tags.clone(), plaintext)
+        } else {
+            return None;
+        };
+
+        let is_chunk = tags.iter().any(|tag| tag.kind() == TagKind::custom(CHUNK_TAG));
+        let reply = if is_chunk {
+            serde_json::from_str(&content).map(Reply::Chunk)
+        } else {
+            serde_json::from_str(&content).map(Reply::Result)
+        };
+        match reply {
+            Ok(reply) => Some((reply, tags.event_ids().next().copied())),
+            Err(e) => {
+                warn!("Invalid reply {}: {}", event.id, e);
+                None
+            }
+        }
+    }
+
+    /// Request id the reply belongs to.
+    pub fn request_id(&self) -> &str {
+        match self {
+            Reply::Result(result) => &result.request_id,
+            Reply::Chunk(chunk) => &chunk.request_id,
+        }
+    }
+}
+
+// ===== REQUEST HANDLER TRAIT =====
+
+/// Metadata of the request event a handler is invoked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContext {
+    /// Pubkey of the request author.
+    pub author: nostr::PublicKey,
+    /// Id of the kind 27401 request event.
+    pub event_id: nostr::EventId,
+    /// `created_at` of the request event.
+    pub created_at: nostr::Timestamp,
+    /// Relay the request was received from, if known.
+    pub relay_url: Option<nostr::RelayUrl>,
+    /// Transport wrapping of the request.
+    pub envelope: Envelope,
+}
+
+/// Request handler trait – application implements custom business logic.
+///
+/// Cross-cutting policies are added by wrapping handlers with
+/// [`Layer`](crate::middleware::Layer)s.
+#[async_trait]
+pub trait RequestHandler: Send + Sync + 'static {
+    /// Handle an OperationRequest and return the OperationResult.
+    async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult>;
+}
+
+// ===== DEFAULT REQUEST HANDLERS =====
+
+/// Deserialize a request `data` payload, mapping failures to `invalid_request`.
+fn parse_payload<T>(data: Value) -> Result<T, ResultError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    serde_json::from_value(data)
+        .map_err(|e| ResultError::new(ErrorCode::InvalidRequest, format!("Invalid request data: {}", e)))
+}
+
+/// Quote id of a check request, given either as a bare string or as `{"quote": "..."}`.
+fn parse_quote_id(data: &Value) -> Result<&str, ResultError> {
+    data.as_str()
+        .or_else(|| data.get("quote").and_then(Value::as_str))
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .ok_or_else(|| ResultError::new(ErrorCode::InvalidRequest, "Missing or invalid quote id"))
+}
+
+/// Quote id of a check request against the in-process mint, which keys quotes by UUID.
+fn parse_quote_uuid(data: &Value) -> Result<Uuid, ResultError> {
+    Uuid::parse_str(parse_quote_id(data)?)
+        .map_err(|e| ResultError::new(ErrorCode::InvalidRequest, format!("Invalid quote id: {}", e)))
+}
+
+/// Run the sub-requests of a batch in order through `dispatch`.
+///
+/// Sub-requests with a method that is not batchable get an `invalid_request`
+/// result in their slot; the other slots are unaffected.
+async fn run_batch<F, Fut>(data: Value, mut dispatch: F) -> Result<Value, ResultError>
+where
+    F: FnMut(OperationMethod, Value) -> Fut,
+    Fut: std::future::Future<Output = Result<Value, ResultError>>,
+{
+    let requests: Vec<OperationRequest> = parse_payload(data)?;
+    if requests.len() > MAX_BATCH_SIZE {
+        return Err(ResultError::new(
+            ErrorCode::InvalidRequest,
+            format!("Batch of {} requests exceeds the limit of {}", requests.len(), MAX_BATCH_SIZE),
+        ));
+    }
+
+    let mut results = Vec::with_capacity(requests.len());
+    for request in requests {
+        let response = if request.method.is_batchable() {
+            dispatch(request.method, request.data.unwrap_or(Value::Null)).await
+        } else {
+            Err(ResultError::new(
+                ErrorCode::InvalidRequest,
+                format!("{:?} cannot be batched", request.method),
+            ))
+        };
+        results.push(match response {
+            Ok(data) => OperationResult::success(request.request_id, data),
+            Err(error) => OperationResult::failure(request.request_id, error),
+        });
+    }
+    Ok(json!(results))
+}
+
+/// Error for a batch reaching a single-request dispatcher.
+fn nested_batch() -> ResultError {
+    ResultError::new(ErrorCode::InvalidRequest, "Batch requests cannot be nested")
+}
+
+/// HTTP route of a NIP-74 operation on a Cashu mint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MintRoute {
+    method: reqwest::Method,
+    path: String,
+    /// Whether `data` is sent as the JSON body.
+    body: bool,
+}
+
+impl MintRoute {
+    fn get(path: impl Into<String>) -> Self {
+        Self { method: reqwest::Method::GET, path: path.into(), body: false }
+    }
+
+    fn post(path: impl Into<String>) -> Self {
+        Self { method: reqwest::Method::POST, path: path.into(), body: true }
+    }
+}
+
+/// Default request handler that proxies requests to the HTTP API of a Cashu mint.
+///
+/// Each method is forwarded to its NUT route with `data` as the verbatim JSON
+/// body, so NIP-74 can front any mint, local or remote. Onion mints are reached
+/// through a SOCKS5 proxy such as the Tor client.
+pub struct DefaultRequestHandler {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl DefaultRequestHandler {
+    /// Proxy to a mintd listening on localhost.
+    pub fn new(mintd_port: u16) -> Self {
+        Self {
+            base_url: format!("http://127.0.0.1:{}", mintd_port),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Proxy to the mint at `base_url`, optionally through a SOCKS5 proxy
+    /// (e.g. `socks5h://127.0.0.1:9050` for `.onion` mints).
+    pub fn with_base_url(base_url: &str, socks_proxy: Option<&str>) -> Nip74Result<Self> {
+        let parsed = reqwest::Url::parse(base_url)
+            .map_err(|e| Nip74Error::Config(format!("Invalid mint URL '{}': {}", base_url, e)))?;
+        if parsed.host_str().is_some_and(|host| host.ends_with(".onion")) && socks_proxy.is_none() {
+            return Err(Nip74Error::Config(format!("Onion mint URL '{}' requires a SOCKS proxy", base_url)));
+        }
+
+        let mut builder = reqwest::Client::builder();
+        if let Some(proxy) = socks_proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| Nip74Error::Config(format!("Invalid SOCKS proxy '{}': {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| Nip74Error::Config(format!("Failed to build HTTP client: {}", e)))?;
+
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+        })
+    }
+
+    /// Base URL of the proxied mint.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Fetch the NUT-06 info of the proxied mint.
+    pub async fn mint_info(&self) -> Nip74Result<cdk::nuts::MintInfo> {
+        let info = self.call_mint(&MintRoute::get("/v1/info"), Value::Null).await.map_err(Nip74Error::Mint)?;
+        Ok(serde_json::from_value(info)?)
+    }
+
+    /// Fetch the keysets of the proxied mint.
+    pub async fn keysets(&self) -> Nip74Result<KeysetResponse> {
+        let keysets = self.call_mint(&MintRoute::get("/v1/keysets"), Value::Null).await.map_err(Nip74Error::Mint)?;
+        Ok(serde_json::from_value(keysets)?)
+    }
+
+    /// Map a NIP-74 operation to its NUT HTTP route.
+    fn route(method: &OperationMethod, data: &Value) -> Result<MintRoute, ResultError> {
+        Ok(match method {
+            OperationMethod::Info => MintRoute::get("/v1/info"),
+            OperationMethod::GetMintQuote => MintRoute::post("/v1/mint/quote/bolt11"),
+            OperationMethod::CheckMintQuote => {
+                MintRoute::get(format!("/v1/mint/quote/bolt11/{}", parse_quote_id(data)?))
+            }
+            OperationMethod::Mint => MintRoute::post("/v1/mint/bolt11"),
+            OperationMethod::GetMeltQuote => MintRoute::post("/v1/melt/quote/bolt11"),
+            OperationMethod::CheckMeltQuote => {
+                MintRoute::get(format!("/v1/melt/quote/bolt11/{}", parse_quote_id(data)?))
+            }
+            OperationMethod::Melt => MintRoute::post("/v1/melt/bolt11"),
+            OperationMethod::GetMintQuoteBolt12 => MintRoute::post("/v1/mint/quote/bolt12"),
+            OperationMethod::CheckMintQuoteBolt12 => {
+                MintRoute::get(format!("/v1/mint/quote/bolt12/{}", parse_quote_id(data)?))
+            }
+            OperationMethod::MintBolt12 => MintRoute::post("/v1/mint/bolt12"),
+            OperationMethod::GetMeltQuoteBolt12 => MintRoute::post("/v1/melt/quote/bolt12"),
+            OperationMethod::CheckMeltQuoteBolt12 => {
+                MintRoute::get(format!("/v1/melt/quote/bolt12/{}", parse_quote_id(data)?))
+            }
+            OperationMethod::MeltBolt12 => MintRoute::post("/v1/melt/bolt12"),
+            OperationMethod::Swap => MintRoute::post("/v1/swap"),
+            OperationMethod::CheckState => MintRoute::post("/v1/checkstate"),
+            OperationMethod::Restore => MintRoute::post("/v1/restore"),
+            OperationMethod::Keys => match data.get("keyset_id") {
+                Some(id) => {
+                    let id: Id = parse_payload(id.clone())?;
+                    MintRoute::get(format!("/v1/keys/{}", id))
+                }
+                None => MintRoute::get("/v1/keys"),
+            },
+            OperationMethod::Keysets => MintRoute::get("/v1/keysets"),
+            OperationMethod::Batch => return Err(nested_batch()),
+        })
+    }
+
+    /// Send `data` to the mint route and return the decoded response.
+    async fn call_mint(&self, route: &MintRoute, data: Value) -> Result<Value, ResultError> {
+        let url = format!("{}{}", self.base_url, route.path);
+
+        let mut request = self.client.request(route.method.clone(), &url);
+        if route.body {
+            request = request.json(&data);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ResultError::new(ErrorCode::BackendUnavailable, format!("HTTP request failed: {}", e)))?;
+
+        let status = response.status();
+        let text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        if status.is_success() {
+            serde_json::from_str(&text)
+                .map_err(|e| ResultError::new(ErrorCode::InternalError, format!("Failed to parse response: {}", e)))
+        } else {
+            Err(Self::error_from_response(status, &text))
+        }
+    }
+
+    /// Map a failed mint response, preferring the Cashu `{code, detail}` error body.
+    fn error_from_response(status: reqwest::StatusCode, body: &str) -> ResultError {
+        let cashu_error: Option<Value> = serde_json::from_str(body).ok();
+        match cashu_error.as_ref().and_then(|e| e.get("code")).and_then(Value::as_u64) {
+            Some(code) => {
+                let message = cashu_error
+                    .as_ref()
+                    .and_then(|e| e.get("detail").or_else(|| e.get("error")))
+                    .and_then(Value::as_str)
+                    .unwrap_or(body);
+                ResultError::new(ErrorCode::from_cashu_code(code as u16), message)
+            }
+            None if status.is_server_error() => {
+                ResultError::new(ErrorCode::BackendUnavailable, format!("Mint request failed: {} - {}", status, body))
+            }
+            None => ResultError::new(ErrorCode::InvalidRequest, format!("Mint request failed: {} - {}", status, body)),
+        }
+    }
+}
+
+#[async_trait]
+impl RequestHandler for DefaultRequestHandler {
+    async fn handle(&self, _ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        let data = req.data.unwrap_or(Value::Null);
+        let response = match req.method {
+            OperationMethod::Batch => {
+                run_batch(data, |method, data| async move {
+                    let route = Self::route(&method, &data)?;
+                    self.call_mint(&route, data).await
+                })
+                .await
+            }
+            method => match Self::route(&method, &data) {
+                Ok(route) => self.call_mint(&route, data).await,
+                Err(error) => Err(error),
+            },
+        };
+
+        Ok(match response {
+            Ok(result) => OperationResult::success(req.request_id, result),
+            Err(error) => OperationResult::failure(req.request_id, error),
+        })
+    }
+}
+
+/// Default handler that bridges NIP-74 requests to the underlying Cashu `Mint` implementation.
+pub struct DefaultMintHandler {
+    mint: Arc<Mint>,
+}
+
+impl DefaultMintHandler {
+    /// Create new handler from an instantiated [`Mint`].
+    pub fn new(mint: Mint) -> Self {
+        Self { mint: Arc::new(mint) }
+    }
+
+    /// Create new handler from a [`Mint`] that is already shared with other services.
+    pub fn from_arc(mint: Arc<Mint>) -> Self {
+        Self { mint }
+    }
+
+    /// Execute `method` against the mint and return the response payload.
+    async fn dispatch(&self, method: &OperationMethod, data: Value) -> Result<Value, ResultError> {
+        match method {
+            OperationMethod::Info => {
+                // For Info we just relay the static mint info.
+                let info = self.mint.mint_info().await.map_err(ResultError::from_mint_error)?;
+                Ok(json!({ "info": info }))
+            }
+            OperationMethod::GetMintQuote => {
+                let request: MintQuoteBolt11Request = parse_payload(data)?;
+                let quote = self
+                    .mint
+                    .get_mint_bolt11_quote(request)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(quote))
+            }
+            OperationMethod::CheckMintQuote => {
+                let quote_id = parse_quote_uuid(&data)?;
+                let quote = self
+                    .mint
+                    .check_mint_quote(&quote_id)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(quote))
+            }
+            OperationMethod::GetMintQuoteBolt12 => {
+                let request: MintQuoteBolt12Request = parse_payload(data)?;
+                let quote: MintQuoteBolt12Response<Uuid> = self
+                    .mint
+                    .get_mint_quote(request.into())
+                    .await
+                    .and_then(TryInto::try_into)
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(quote))
+            }
+            OperationMethod::CheckMintQuoteBolt12 => {
+                let quote_id = parse_quote_uuid(&data)?;
+                let quote: MintQuoteBolt12Response<Uuid> = self
+                    .mint
+                    .check_mint_quote(&quote_id)
+                    .await
+                    .and_then(TryInto::try_into)
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(quote))
+            }
+            // Minting and melting take the same request for both payment methods;
+            // the quote decides which backend is used.
+            OperationMethod::Mint | OperationMethod::MintBolt12 => {
+                let mint_req_str: MintRequest<String> = parse_payload(data)?;
+                let mint_req_uuid: MintRequest<Uuid> = mint_req_str
+                    .try_into()
+                    .map_err(|e| ResultError::new(ErrorCode::InvalidRequest, format!("Invalid quote id: {}", e)))?;
+                // The daily mint cap meters BOLT12 quotes on `mint_bolt12` only, so the
+                // method has to match the quote
+                let quote = self
+                    .mint
+                    .check_mint_quote(&mint_req_uuid.quote)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                let bolt12_quote = MintQuoteBolt12Response::<Uuid>::try_from(quote).is_ok();
+                if bolt12_quote != (*method == OperationMethod::MintBolt12) {
+                    let expected = if bolt12_quote { "mint_bolt12" } else { "mint" };
+                    return Err(ResultError::new(
+                        ErrorCode::InvalidRequest,
+                        format!("Quote {} must be minted with {}", mint_req_uuid.quote, expected),
+                    ));
+                }
+                let response = self
+                    .mint
+                    .process_mint_request(mint_req_uuid)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(response))
+            }
+            OperationMethod::GetMeltQuote => {
+                let request: MeltQuoteBolt11Request = parse_payload(data)?;
+                let quote = self
+                    .mint
+                    .get_melt_bolt11_quote(&request)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(quote))
+            }
+            OperationMethod::GetMeltQuoteBolt12 => {
+                let request: MeltQuoteBolt12Request = parse_payload(data)?;
+                let quote = self
+                    .mint
+                    .get_melt_quote(request.into())
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(quote))
+            }
+            OperationMethod::CheckMeltQuote | OperationMethod::CheckMeltQuoteBolt12 => {
+                let quote_id = parse_quote_uuid(&data)?;
+                let quote = self
+                    .mint
+                    .check_melt_quote(&quote_id)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(quote))
+            }
+            OperationMethod::Melt | OperationMethod::MeltBolt12 => {
+                let melt_req_str: MeltRequest<String> = parse_payload(data)?;
+                let melt_req_uuid: MeltRequest<Uuid> = melt_req_str
+                    .try_into()
+                    .map_err(|e| ResultError::new(ErrorCode::InvalidRequest, format!("Invalid quote id: {}", e)))?;
+                let response = self
+                    .mint
+                    .melt_bolt11(&melt_req_uuid)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(response))
+            }
+            OperationMethod::Swap => {
+                let swap_req: SwapRequest = parse_payload(data)?;
+                let response = self
+                    .mint
+                    .process_swap_request(swap_req)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(response))
+            }
+            OperationMethod::CheckState => {
+                let check_req: CheckStateRequest = parse_payload(data)?;
+                let response = self
+                    .mint
+                    .check_state(&check_req)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(response))
+            }
+            OperationMethod::Restore => {
+                let restore_req: RestoreRequest = parse_payload(data)?;
+                let response = self
+                    .mint
+                    .restore(restore_req)
+                    .await
+                    .map_err(ResultError::from_mint_error)?;
+                Ok(json!(response))
+            }
+            OperationMethod::Keys => {
+                // Optional `{"keyset_id": "..."}` selects a single keyset; otherwise all active keys.
+                let keyset_id: Option<Id> = match data.get("keyset_id") {
+                    Some(id) => Some(parse_payload(id.clone())?),
+                    None => None,
+                };
+                let response = match keyset_id {
+                    Some(id) => self.mint.keyset_pubkeys(&id).map_err(ResultError::from_mint_error)?,
+                    None => self.mint.pubkeys(),
+                };
+                Ok(json!(response))
+            }
+            OperationMethod::Keysets => Ok(json!(self.mint.keysets())),
+            OperationMethod::Batch => Err(nested_batch()),
+        }
+    }
+}
+
+#[async_trait]
+impl RequestHandler for DefaultMintHandler {
+    async fn handle(&self, _ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        let data = req.data.unwrap_or(Value::Null);
+        let response = match req.method {
+            OperationMethod::Batch => {
+                run_batch(data, |method, data| async move { self.dispatch(&method, data).await }).await
+            }
+            method => self.dispatch(&method, data).await,
+        };
+        Ok(match response {
+            Ok(data) => OperationResult::success(req.request_id, data),
+            Err(error) => OperationResult::failure(req.request_id, error),
+        })
+    }
+}
+
+// ===== NIP-74 SERVICE RUNTIME =====
+
+/// A kind 27401 request opened from its transport envelope.
+struct IncomingRequest {
+    /// Request author.
+    sender: nostr::PublicKey,
+    /// Id of the request event (the rumor id for gift-wrapped requests).
+    event_id: nostr::EventId,
+    /// Creation time of the request event (the rumor time for gift-wrapped requests).
+    created_at: nostr::Timestamp,
+    /// Decrypted JSON payload.
+    payload: String,
+    envelope: Envelope,
+}
+
+impl IncomingRequest {
+    /// Open a direct or gift-wrapped request event.
+    ///
+    /// Direct requests that cannot be decrypted yield the `decryption_failed`
+    /// result to send back to their signed author, with the event id as
+    /// `request_id`. Gift wraps that cannot be opened, or carry anything but a
+    /// kind 27401 rumor from their seal author, are dropped: their sender is
+    /// unknown and answering them would sign replies for arbitrary events.
+    async fn open<S>(signer: &S, event: &nostr::Event) -> Result<Option<Self>, OperationResult>
+    where
+        S: nostr::NostrSigner,
+    {
+        if event.kind == nostr::Kind::GiftWrap {
+            let unwrapped = match nostr::nips::nip59::UnwrappedGift::from_gift_wrap(signer, event).await {
+                Ok(unwrapped) => unwrapped,
+                Err(e) => {
+                    debug!("Ignoring gift wrap {}: {}", event.id, e);
+                    return Ok(None);
+                }
+            };
+
+            let mut rumor = unwrapped.rumor;
+            if rumor.kind != nostr::Kind::from(KIND_OPERATION_REQUEST) || rumor.pubkey != unwrapped.sender {
+                debug!("Ignoring gift wrap {}: not a NIP-74 request", event.id);
+                return Ok(None);
+            }
+            // Rumors are unsigned, so never trust a supplied id
+            rumor.id = None;
+            rumor.ensure_id();
+
+            return Ok(Some(Self {
+                sender: unwrapped.sender,
+                event_id: rumor.id.unwrap_or(event.id),
+                created_at: rumor.created_at,
+                payload: rumor.content,
+                envelope: Envelope::GiftWrap,
+            }));
+        }
+
+        let payload = signer.nip44_decrypt(&event.pubkey, &event.content).await.map_err(|e| {
+            OperationResult::failure(
+                event.id.to_hex(),
+                ResultError::new(ErrorCode::DecryptionFailed, format!("Failed to decrypt request: {}", e)),
+            )
+        })?;
+
+        Ok(Some(Self {
+            sender: event.pubkey,
+            event_id: event.id,
+            created_at: event.created_at,
+            payload,
+            envelope: Envelope::Direct,
+        }))
+    }
+
+    /// Parse the [`OperationRequest`] payload.
+    ///
+    /// On failure returns the error result to send back; the `request_id` is
+    /// recovered from the payload when possible and is the request event id
+    /// otherwise, so the client can still tell which request failed.
+    fn parse(&self) -> Result<OperationRequest, OperationResult> {
+        serde_json::from_str(&self.payload).map_err(|e| {
+            let raw = serde_json::from_str::<Value>(&self.payload).ok();
+            let request_id = raw
+                .as_ref()
+                .and_then(|v| v.get("request_id").and_then(Value::as_str).map(str::to_string))
+                .unwrap_or_else(|| self.event_id.to_hex());
+            let code = match raw.as_ref().and_then(|v| v.get("method").cloned()) {
+                Some(method) if serde_json::from_value::<OperationMethod>(method).is_err() => {
+                    ErrorCode::UnsupportedMethod
+                }
+                _ => ErrorCode::InvalidRequest,
+            };
+            OperationResult::failure(request_id, ResultError::new(code, format!("Invalid request: {}", e)))
+        })
+    }
+
+    fn context(&self, relay_url: Option<nostr::RelayUrl>) -> RequestContext {
+        RequestContext {
+            author: self.sender,
+            event_id: self.event_id,
+            created_at: self.created_at,
+            relay_url,
+            envelope: self.envelope,
+        }
+    }
+}
+
+/// Open a kind 27401 request event, dispatch it to `handler` and return the
+/// signed kind 27402 reply events addressed to the request author, in
+/// publishing order (several when the result is chunked).
+///
+/// Both direct and NIP-59 gift-wrapped requests are accepted; the reply uses
+/// the same wrapping as the request. With a `store`, stale requests are
+/// rejected and duplicates are answered with the cached result instead of
+/// being executed again. Returns no events while an identical request is still
+/// being processed, and for gift wraps that are not answerable.
+pub async fn handle_request_event<S>(
+    signer: &S,
+    handler: &dyn RequestHandler,
+    store: Option<&RequestStore>,
+    event: &nostr::Event,
+    relay_url: Option<nostr::RelayUrl>,
+) -> Nip74Result<Vec<nostr::Event>>
+where
+    S: nostr::NostrSigner,
+{
+    let incoming = match IncomingRequest::open(signer, event).await {
+        Ok(Some(incoming)) => incoming,
+        Ok(None) => return Ok(Vec::new()),
+        Err(result) => {
+            return result
+                .to_reply_events_with_signer(signer, Envelope::Direct, &event.pubkey, &event.id, None)
+                .await;
+        }
+    };
+
+    let result = match incoming.parse() {
+        Ok(request) => {
+            debug!(
+                "NIP-74 request {} ({:?}) from {} ({:?})",
+                request.request_id, request.method, incoming.sender, incoming.envelope
+            );
+
+            let check = store.map(|store| {
+                store.check(
+                    &incoming.event_id,
+                    &incoming.sender,
+                    &request.request_id,
+                    &request.method,
+                    incoming.created_at,
+                )
+            });
+            match check {
+                Some(RequestCheck::InProgress) => {
+                    debug!("Request {} is already being processed", request.request_id);
+                    return Ok(Vec::new());
+                }
+                Some(RequestCheck::Duplicate(result)) => {
+                    debug!("Answering duplicate request {} from cache", request.request_id);
+                    result
+                }
+                Some(RequestCheck::Processed) => OperationResult::failure(
+                    request.request_id,
+                    ResultError::new(
+                        ErrorCode::InvalidRequest,
+                        "Request was already processed and its result was not stored",
+                    ),
+                ),
+                Some(RequestCheck::MethodMismatch(method)) => OperationResult::failure(
+                    request.request_id,
+                    ResultError::new(
+                        ErrorCode::InvalidRequest,
+                        format!("request_id was already used for {:?}", method),
+                    ),
+                ),
+                // Gift wraps are fetched with a look-back window, so stale ones are expected
+                Some(RequestCheck::Expired) if incoming.envelope == Envelope::GiftWrap => return Ok(Vec::new()),
+                Some(RequestCheck::Expired) => OperationResult::failure(
+                    request.request_id,
+                    ResultError::new(
+                        ErrorCode::RequestExpired,
+                        format!(
+                            "Request created_at is outside the {}s freshness window",
+                            store.map(|store| store.window()).unwrap_or_default()
+                        ),
+                    ),
+                ),
+                Some(RequestCheck::New) | None => {
+                    let request_id = request.request_id.clone();
+                    let ctx = incoming.context(relay_url);
+                    match handler.handle(&ctx, request).await {
+                        Ok(result) => {
+                            if let Some(store) = store {
+                                store.complete(&incoming.event_id, &result);
+                            }
+                            result
+                        }
+                        Err(e) => {
+                            warn!("Handler failed for request {}: {}", request_id, e);
+                            if let Some(store) = store {
+                                store.release(&incoming.event_id);
+                            }
+                            OperationResult::failure(request_id, ResultError::new(ErrorCode::InternalError, e.to_string()))
+                        }
+                    }
+                }
+            }
+        }
+        Err(result) => result,
+    };
+
+    result
+        .to_reply_events_with_signer(signer, incoming.envelope, &incoming.sender, &incoming.event_id, None)
+        .await
+}
+
+/// NIP-74 runtime: subscribes to kind 27401 requests addressed to the mint
+/// pubkey on a relay set and publishes the kind 27402 replies.
+pub struct Nip74Service {
+    keys: nostr::Keys,
+    relays: RelayConfig,
+    handler: Arc<dyn RequestHandler>,
+    store: Option<Arc<RequestStore>>,
+    quote_notifier: Option<QuoteNotifier>,
+    min_pow: u8,
+    max_concurrent_requests: usize,
+    client: Option<Client>,
+    relay_manager: Option<RelayManager>,
+    shutdown: Arc<Notify>,
+    listener: Option<tokio::task::JoinHandle<()>>,
+    is_running: bool,
+}
+
+impl Nip74Service {
+    /// Create a new NIP-74 service for the given mint keys, relay set and handler.
+    pub fn new(keys: nostr::Keys, relays: RelayConfig, handler: Arc<dyn RequestHandler>) -> Self {
+        Self {
+            keys,
+            relays,
+            handler,
+            store: None,
+            quote_notifier: None,
+            min_pow: 0,
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            client: None,
+            relay_manager: None,
+            shutdown: Arc::new(Notify::new()),
+            listener: None,
+            is_running: false,
+        }
+    }
+
+    /// Mint public key the service listens for.
+    pub fn public_key(&self) -> nostr::PublicKey {
+        self.keys.public_key()
+    }
+
+    /// Relay set the service is configured with.
+    pub fn relays(&self) -> &RelayConfig {
+        &self.relays
+    }
+
+    /// Enable replay protection backed by `store`.
+    pub fn set_request_store(&mut self, store: Arc<RequestStore>) {
+        self.store = Some(store);
+    }
+
+    /// Push quote state changes through `notifier` while the service is running.
+    ///
+    /// The handler must be wrapped with [`QuoteNotifier::layer`] for quotes to be recorded.
+    pub fn set_quote_notifier(&mut self, notifier: QuoteNotifier) {
+        self.quote_notifier = Some(notifier);
+    }
+
+    /// Drop requests whose event id has fewer than `difficulty` NIP-13 leading
+    /// zero bits, before they are decrypted.
+    ///
+    /// For gift-wrapped requests the outer wrap id is checked.
+    pub fn set_min_pow(&mut self, difficulty: u8) {
+        self.min_pow = difficulty;
+    }
+
+    /// Process at most `limit` requests at once; requests arriving while all
+    /// slots are busy are dropped, and clients retry them after their timeout.
+    pub fn set_max_concurrent_requests(&mut self, limit: usize) {
+        self.max_concurrent_requests = limit.max(1);
+    }
+
+    /// Relay client, available while the service is running.
+    pub fn client(&self) -> Option<Client> {
+        self.client.clone()
+    }
+
+    /// Connect to the relays, subscribe to requests and start the listener task.
+    pub async fn start(&mut self) -> anyhow::Result<()> {
+        if self.is_running {
+            return Ok(());
+        }
+
+        if self.relays.relays.is_empty() {
+            return Err(anyhow!("No relays configured for NIP-74 service"));
+        }
+
+        info!("Starting NIP-74 service for {}", self.keys.public_key());
+
+        let client = Client::builder().signer(self.keys.clone()).build();
+        let mut relay_manager = RelayManager::new(client.clone(), self.relays.clone());
+        relay_manager.connect().await?;
+
+        let filter = nostr::Filter::new()
+            .kind(nostr::Kind::from(KIND_OPERATION_REQUEST))
+            .pubkey(self.keys.public_key())
+            .since(nostr::Timestamp::now());
+        client
+            .subscribe(filter, None)
+            .await
+            .map_err(|e| anyhow!("Failed to subscribe to NIP-74 requests: {}", e))?;
+
+        // Gift wraps carry a randomized created_at up to two days in the past
+        let gift_wrap_filter = nostr::Filter::new()
+            .kind(nostr::Kind::GiftWrap)
+            .pubkey(self.keys.public_key())
+            .since(nostr::Timestamp::now() - GIFT_WRAP_LOOKBACK);
+        client
+            .subscribe(gift_wrap_filter, None)
+            .await
+            .map_err(|e| anyhow!("Failed to subscribe to gift-wrapped NIP-74 requests: {}", e))?;
+        info!(
+            "Subscribed to kind {} events on {} relays",
+            KIND_OPERATION_REQUEST,
+            self.relays.read_relays().count()
+        );
+
+        let listener = tokio::spawn(Self::run_listener(
+            client.clone(),
+            self.keys.clone(),
+            self.handler.clone(),
+            self.store.clone(),
+            self.min_pow,
+            self.max_concurrent_requests,
+            self.shutdown.clone(),
+        ));
+
+        if let Some(notifier) = &self.quote_notifier {
+            notifier.attach(client.clone());
+        }
+
+        self.client = Some(client);
+        self.relay_manager = Some(relay_manager);
+        self.listener = Some(listener);
+        self.is_running = true;
+        info!("NIP-74 service started successfully");
+        Ok(())
+    }
+
+    /// Stop the listener task and disconnect from all relays.
+    pub async fn stop(&mut self) -> anyhow::Result<()> {
+        if !self.is_running {
+            return Ok(());
+        }
+
+        info!("Stopping NIP-74 service...");
+
+        self.shutdown.notify_waiters();
+        if let Some(notifier) = &self.quote_notifier {
+            notifier.detach();
+        }
+        if let Some(mut relay_manager) = self.relay_manager.take() {
+            relay_manager.disconnect().await;
+        }
+        if let Some(client) = self.client.take() {
+            client.shutdown().await;
+        }
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.await;
+        }
+        if let Some(store) = &self.store {
+            if let Err(e) = store.flush() {
+                warn!("Failed to persist request store: {}", e);
+            }
+        }
+
+        self.is_running = false;
+        info!("NIP-74 service stopped");
+        Ok(())
+    }
+
+    /// Check if the service is running
+    pub fn is_running(&self) -> bool {
+        self.is_running
+    }
+
+    /// Get service status as JSON
+    pub fn get_status(&self) -> Value {
+        let relays = match &self.relay_manager {
+            Some(relay_manager) => json!(relay_manager.health()),
+            None => json!(self.relays.relays),
+        };
+
+        json!({
+            "running": self.is_running,
+            "pubkey": self.keys.public_key().to_hex(),
+            "relays": relays,
+            "watched_quotes": self.quote_notifier.as_ref().map(|n| n.watched_count()).unwrap_or(0),
+        })
+    }
+
+    /// Health of the relays the service is connected to.
+    pub fn relay_health(&self) -> Vec<crate::relay_manager::RelayHealth> {
+        self.relay_manager
+            .as_ref()
+            .map(|relay_manager| relay_manager.health())
+            .unwrap_or_default()
+    }
+
+    async fn run_listener(
+        client: Client,
+        keys: nostr::Keys,
+        handler: Arc<dyn RequestHandler>,
+        store: Option<Arc<RequestStore>>,
+        min_pow: u8,
+        max_concurrent_requests: usize,
+        shutdown: Arc<Notify>,
+    ) {
+        let mut notifications = client.notifications();
+        let slots = Arc::new(tokio::sync::Semaphore::new(max_concurrent_requests));
+        loop {
+            tokio::select! {
+                _ = shutdown.notified() => {
+                    info!("NIP-74 listener received shutdown signal");
+                    break;
+                }
+                notification = notifications.recv() => match notification {
+                    Ok(RelayPoolNotification::Event { relay_url, event, .. }) => {
+                        if event.kind != nostr::Kind::from(KIND_OPERATION_REQUEST)
+                            && event.kind != nostr::Kind::GiftWrap
+                        {
+                            continue;
+                        }
+                        // Checked before decryption so unmined requests cost the mint nothing
+                        if !event.id.check_pow(min_pow) {
+                            debug!("Dropping request {}: below {} bits of proof of work", event.id, min_pow);
+                            continue;
+                        }
+                        // Bounds the tasks a request flood can pile up
+                        let Ok(slot) = slots.clone().try_acquire_owned() else {
+                            warn!(
+                                "Dropping request {}: {} requests already in progress",
+                                event.id, max_concurrent_requests
+                            );
+                            continue;
+                        };
+                        let client = client.clone();
+                        let keys = keys.clone();
+                        let handler = handler.clone();
+                        let store = store.clone();
+                        tokio::spawn(async move {
+                            Self::process_event(&client, &keys, handler.as_ref(), store.as_deref(), &event, relay_url)
+                                .await;
+                            drop(slot);
+                        });
+                    }
+                    Ok(RelayPoolNotification::Shutdown) => break,
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("NIP-74 listener lagged, skipped {} notifications", skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+        info!("NIP-74 listener stopped");
+    }
+
+    async fn process_event(
+        client: &Client,
+        keys: &nostr::Keys,
+        handler: &dyn RequestHandler,
+        store: Option<&RequestStore>,
+        event: &nostr::Event,
+        relay_url: nostr::RelayUrl,
+    ) {
+        let replies = match handle_request_event(keys, handler, store, event, Some(relay_url)).await {
+            Ok(replies) => replies,
+            Err(e) => {
+                warn!("Failed to handle NIP-74 request {}: {}", event.id, e);
+                return;
+            }
+        };
+
+        // Chunks are published in order so clients usually receive them in order
+        for reply in replies {
+            match client.send_event(&reply).await {
+                Ok(output) => debug!(
+                    "Published NIP-74 reply {} for request {} ({} relays ok, {} failed)",
+                    reply.id,
+                    event.id,
+                    output.success.len(),
+                    output.failed.len()
+                ),
+                Err(e) => {
+                    error!("Failed to publish NIP-74 reply for {}: {}", event.id, e);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Nip74Service {
+    fn drop(&mut self) {
+        if self.is_running {
+            self.shutdown.notify_waiters();
+        }
+    }
+}
+
+// ===== NIP-74 CLIENT =====
+
+/// Whether `event` carries an `e` tag pointing at `event_id`.
+fn references_event(event: &nostr::Event, event_id: &nostr::EventId) -> bool {
+    let id_hex = event_id.to_hex();
+    event.tags.iter().any(|t| {
+        let tag = t.as_slice();
+        tag.len() >= 2 && tag[0] == "e" && tag[1] == id_hex
+    })
+}
+
+/// Typed NIP-74 client for wallets and tests.
+///
+/// Requests are sent to every configured relay; replies are matched by the
+/// `e` tag of the kind 27402 event and the `request_id` of the result.
+pub struct Nip74Client {
+    client: Client,
+    keys: nostr::Keys,
+    mint_pubkey: nostr::PublicKey,
+    timeout: std::time::Duration,
+    retries: u32,
+    envelope: Envelope,
+    pow: u8,
+}
+
+impl Nip74Client {
+    /// Connect to `relays` and subscribe to replies from `mint_pubkey`.
+    pub async fn connect(
+        keys: nostr::Keys,
+        mint_pubkey: nostr::PublicKey,
+        relays: &[String],
+    ) -> Nip74Result<Self> {
+        let client = Client::builder().signer(keys.clone()).build();
+        // Relays already connected are closed again when a later step fails.
+        if let Err(e) = Self::subscribe(&client, &keys, mint_pubkey, relays).await {
+            client.shutdown().await;
+            return Err(e);
+        }
+
+        Ok(Self {
+            client,
+            keys,
+            mint_pubkey,
+            timeout: std::time::Duration::from_secs(30),
+            retries: 2,
+            envelope: Envelope::Direct,
+            pow: 0,
+        })
+    }
+
+    /// Add and connect `relays`, then subscribe to replies from `mint_pubkey`.
+    async fn subscribe(
+        client: &Client,
+        keys: &nostr::Keys,
+        mint_pubkey: nostr::PublicKey,
+        relays: &[String],
+    ) -> Nip74Result<()> {
+        for relay in relays {
+            client
+                .add_relay(relay.as_str())
+                .await
+                .map_err(|e| Nip74Error::Relay(format!("failed to add relay '{}': {}", relay, e)))?;
+        }
+        client.connect().await;
+        client.wait_for_connection(std::time::Duration::from_secs(5)).await;
+
+        let filter = nostr::Filter::new()
+            .kind(nostr::Kind::from(KIND_OPERATION_RESULT))
+            .author(mint_pubkey)
+            .pubkey(keys.public_key())
+            .since(nostr::Timestamp::now());
+        client
+            .subscribe(filter, None)
+            .await
+            .map_err(|e| Nip74Error::Relay(e.to_string()))?;
+
+        let gift_wrap_filter = nostr::Filter::new()
+            .kind(nostr::Kind::GiftWrap)
+            .pubkey(keys.public_key())
+            .since(nostr::Timestamp::now() - GIFT_WRAP_LOOKBACK);
+        client
+            .subscribe(gift_wrap_filter, None)
+            .await
+            .map_err(|e| Nip74Error::Relay(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Send requests NIP-59 gift-wrapped, hiding the client/mint relationship
+    /// and request timing from relays.
+    pub fn with_gift_wrap(mut self, enabled: bool) -> Self {
+        self.envelope = if enabled { Envelope::GiftWrap } else { Envelope::Direct };
+        self
+    }
+
+    /// Mine request events to at least `difficulty` NIP-13 leading zero bits.
+    pub fn with_pow(mut self, difficulty: u8) -> Self {
+        self.pow = difficulty;
+        self
+    }
+
+    /// Minimum request difficulty advertised in the mint's latest kind:37400
+    /// announcement (`0` when none is found).
+    pub async fn fetch_required_pow(&self) -> Nip74Result<u8> {
+        let filter = nostr::Filter::new()
+            .kind(nostr::Kind::from(KIND_MINT_INFO))
+            .author(self.mint_pubkey);
+        let events = self
+            .client
+            .fetch_events(filter, std::time::Duration::from_secs(10))
+            .await
+            .map_err(|e| Nip74Error::Relay(e.to_string()))?;
+
+        Ok(events
+            .into_iter()
+            .max_by_key(|event| event.created_at)
+            .map(|event| required_pow(&event))
+            .unwrap_or(0))
+    }
+
+    /// Set how long to wait for a reply before retrying.
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set how many times an unanswered request is re-sent.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Public key of the mint this client talks to.
+    pub fn mint_pubkey(&self) -> nostr::PublicKey {
+        self.mint_pubkey
+    }
+
+    /// Disconnect from all relays.
+    pub async fn disconnect(&self) {
+        self.client.shutdown().await;
+    }
+
+    /// Send `request` and wait for the matching result.
+    ///
+    /// Each attempt is signed as a new event, since relays and the mint drop
+    /// events they have already seen; the mint answers a repeated `request_id`
+    /// from its request store instead of processing it twice.
+    pub async fn send(&self, request: &OperationRequest) -> Nip74Result<OperationResult> {
+        let mut request_event_ids = Vec::new();
+        for attempt in 0..=self.retries {
+            // Replies reference the rumor id for gift-wrapped requests.
+            let (event, request_event_id) = match self.envelope {
+                Envelope::Direct => {
+                    let event = request.to_event_with_pow(&self.keys, &self.mint_pubkey, self.pow, None).await?;
+                    let id = event.id;
+                    (event, id)
+                }
+                Envelope::GiftWrap => {
+                    request
+                        .to_gift_wrap_with_pow(&self.keys, &self.mint_pubkey, self.pow, None)
+                        .await?
+                }
+            };
+            request_event_ids.push(request_event_id);
+
+            // Subscribe to notifications before publishing so the reply cannot be missed.
+            let notifications = self.client.notifications();
+
+            let output = self
+                .client
+                .send_event(&event)
+                .await
+                .map_err(|e| Nip74Error::Relay(e.to_string()))?;
+            if output.success.is_empty() {
+                warn!(
+                    "Request {} was not accepted by any relay (attempt {})",
+                    request.request_id,
+                    attempt + 1
+                );
+            }
+
+            // A late reply to an earlier attempt is as good as one to this attempt.
+            match tokio::time::timeout(self.timeout, self.wait_for_result(notifications, &request_event_ids, &request.request_id)).await {
+                Ok(result) => return result,
+                Err(_) => debug!(
+                    "No reply for request {} (attempt {}/{})",
+                    request.request_id,
+                    attempt + 1,
+                    self.retries + 1
+                ),
+            }
+        }
+
+        Err(Nip74Error::Timeout(request.request_id.clone()))
+    }
+
+    /// Send a request and deserialize the `data` of a successful result.
+    pub async fn call<T>(&self, method: OperationMethod, data: Option<Value>) -> Nip74Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let result = self.send(&OperationRequest::new(method, data)).await?;
+        match result.status {
+            ResultStatus::Success => Ok(serde_json::from_value(result.data.unwrap_or(Value::Null))?),
+            ResultStatus::Error => Err(Nip74Error::Mint(result.error.unwrap_or_else(|| {
+                ResultError::new(ErrorCode::Unknown, "mint returned an error without details")
+            }))),
+        }
+    }
+
+    /// Send read-only `requests` in one batch and return their results in order.
+    pub async fn batch(&self, requests: Vec<OperationRequest>) -> Nip74Result<Vec<OperationResult>> {
+        self.call(OperationMethod::Batch, Some(json!(requests))).await
+    }
+
+    /// Get NUT-06 mint information.
+    pub async fn info(&self) -> Nip74Result<cdk::nuts::MintInfo> {
+        let data: Value = self.call(OperationMethod::Info, None).await?;
+        Ok(serde_json::from_value(data.get("info").cloned().unwrap_or(data))?)
+    }
+
+    /// Request a bolt11 mint quote.
+    pub async fn get_mint_quote(
+        &self,
+        request: &MintQuoteBolt11Request,
+    ) -> Nip74Result<cdk::nuts::MintQuoteBolt11Response<String>> {
+        self.call(OperationMethod::GetMintQuote, Some(json!(request))).await
+    }
+
+    /// Check a bolt11 mint quote.
+    pub async fn check_mint_quote(
+        &self,
+        quote_id: &str,
+    ) -> Nip74Result<cdk::nuts::MintQuoteBolt11Response<String>> {
+        self.call(OperationMethod::CheckMintQuote, Some(json!(quote_id))).await
+    }
+
+    /// Mint blind signatures for a paid quote.
+    pub async fn mint(&self, request: &MintRequest<String>) -> Nip74Result<cdk::nuts::MintResponse> {
+        self.call(OperationMethod::Mint, Some(json!(request))).await
+    }
+
+    /// Request a bolt11 melt quote.
+    pub async fn get_melt_quote(
+        &self,
+        request: &MeltQuoteBolt11Request,
+    ) -> Nip74Result<cdk::nuts::MeltQuoteBolt11Response<String>> {
+        self.call(OperationMethod::GetMeltQuote, Some(json!(request))).await
+    }
+
+    /// Check a bolt11 melt quote.
+    pub async fn check_melt_quote(
+        &self,
+        quote_id: &str,
+    ) -> Nip74Result<cdk::nuts::MeltQuoteBolt11Response<String>> {
+        self.call(OperationMethod::CheckMeltQuote, Some(json!(quote_id))).await
+    }
+
+    /// Melt proofs to pay a quoted invoice.
+    pub async fn melt(
+        &self,
+        request: &MeltRequest<String>,
+    ) -> Nip74Result<cdk::nuts::MeltQuoteBolt11Response<String>> {
+        self.call(OperationMethod::Melt, Some(json!(request))).await
+    }
+
+    /// Request a BOLT12 mint quote (NUT-25).
+    pub async fn get_mint_quote_bolt12(
+        &self,
+        request: &MintQuoteBolt12Request,
+    ) -> Nip74Result<MintQuoteBolt12Response<String>> {
+        self.call(OperationMethod::GetMintQuoteBolt12, Some(json!(request))).await
+    }
+
+    /// Check a BOLT12 mint quote.
+    pub async fn check_mint_quote_bolt12(&self, quote_id: &str) -> Nip74Result<MintQuoteBolt12Response<String>> {
+        self.call(OperationMethod::CheckMintQuoteBolt12, Some(json!(quote_id))).await
+    }
+
+    /// Mint blind signatures for a BOLT12 quote.
+    pub async fn mint_bolt12(&self, request: &MintRequest<String>) -> Nip74Result<cdk::nuts::MintResponse> {
+        self.call(OperationMethod::MintBolt12, Some(json!(request))).await
+    }
+
+    /// Request a BOLT12 melt quote (NUT-25).
+    pub async fn get_melt_quote_bolt12(
+        &self,
+        request: &MeltQuoteBolt12Request,
+    ) -> Nip74Result<cdk::nuts::MeltQuoteBolt11Response<String>> {
+        self.call(OperationMethod::GetMeltQuoteBolt12, Some(json!(request))).await
+    }
+
+    /// Check a BOLT12 melt quote.
+    pub async fn check_melt_quote_bolt12(
+        &self,
+        quote_id: &str,
+    ) -> Nip74Result<cdk::nuts::MeltQuoteBolt11Response<String>> {
+        self.call(OperationMethod::CheckMeltQuoteBolt12, Some(json!(quote_id))).await
+    }
+
+    /// Melt proofs to pay a quoted BOLT12 offer.
+    pub async fn melt_bolt12(
+        &self,
+        request: &MeltRequest<String>,
+    ) -> Nip74Result<cdk::nuts::MeltQuoteBolt11Response<String>> {
+        self.call(OperationMethod::MeltBolt12, Some(json!(request))).await
+    }
+
+    /// Swap proofs for new blind signatures.
+    pub async fn swap(&self, request: &SwapRequest) -> Nip74Result<cdk::nuts::SwapResponse> {
+        self.call(OperationMethod::Swap, Some(json!(request))).await
+    }
+
+    /// Check the state of proofs.
+    pub async fn check_state(
+        &self,
+        request: &CheckStateRequest,
+    ) -> Nip74Result<cdk::nuts::CheckStateResponse> {
+        self.call(OperationMethod::CheckState, Some(json!(request))).await
+    }
+
+    /// Restore blind signatures for previously used outputs.
+    pub async fn restore(&self, request: &RestoreRequest) -> Nip74Result<cdk::nuts::RestoreResponse> {
+        self.call(OperationMethod::Restore, Some(json!(request))).await
+    }
+
+    /// Get public keys of the active keysets, or of a single keyset.
+    pub async fn keys(&self, keyset_id: Option<Id>) -> Nip74Result<cdk::nuts::KeysResponse> {
+        let data = keyset_id.map(|id| json!({ "keyset_id": id }));
+        self.call(OperationMethod::Keys, data).await
+    }
+
+    /// List all keysets.
+    pub async fn keysets(&self) -> Nip74Result<cdk::nuts::KeysetResponse> {
+        self.call(OperationMethod::Keysets, None).await
+    }
+
+    async fn wait_for_result(
+        &self,
+        mut notifications: tokio::sync::broadcast::Receiver<RelayPoolNotification>,
+        request_event_ids: &[nostr::EventId],
+        request_id: &str,
+    ) -> Nip74Result<OperationResult> {
+        let mut assembler = ResultAssembler::new();
+        loop {
+            let event = match notifications.recv().await {
+                Ok(RelayPoolNotification::Event { event, .. }) => event,
+                Ok(RelayPoolNotification::Shutdown) | Err(RecvError::Closed) => {
+                    return Err(Nip74Error::Relay("relay pool shut down".into()));
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            };
+
+            // Direct replies can be matched before decrypting them.
+            if event.kind == nostr::Kind::from(KIND_OPERATION_RESULT)
+                && (event.pubkey != self.mint_pubkey
+                    || !request_event_ids.iter().any(|id| references_event(&event, id)))
+            {
+                continue;
+            }
+
+            let Some((reply, reply_to)) = Reply::from_event(&self.keys, &self.mint_pubkey, &event).await else {
+                continue;
+            };
+            if !reply_to.is_some_and(|id| request_event_ids.contains(&id)) || reply.request_id() != request_id {
+                continue;
+            }
+
+            match reply {
+                Reply::Result(result) => return Ok(result),
+                Reply::Chunk(chunk) => {
+                    if let Some(result) = assembler.push(chunk)? {
+                        return Ok(result);
+                    }
+                    debug!("Received {} chunks of request {}", assembler.received(), request_id);
+                }
+            }
+        }
+    }
+}
+
+// ===== MINT ANNOUNCER =====
+
+/// Status advertised in the `status` tag of the kind:37400 announcement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MintStatus {
+    /// Mint is accepting requests.
+    Running,
+    /// Mint is shutting down.
+    Stopping,
+    /// Mint is temporarily not accepting requests.
+    Maintenance,
+}
+
+impl MintStatus {
+    /// Tag value for this status.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MintStatus::Running => "running",
+            MintStatus::Stopping => "stopping",
+            MintStatus::Maintenance => "maintenance",
+        }
+    }
+}
+
+/// Parameters of the kind:37400 announcement.
+#[derive(Debug, Clone)]
+pub struct MintAnnouncement {
+    /// `d` identifier of the announcement.
+    pub identifier: String,
+    /// Relays where the mint listens for requests.
+    pub relays: Vec<nostr::RelayUrl>,
+    /// Clearnet HTTP URL of the mint, if served.
+    pub clearnet_url: Option<String>,
+    /// Onion HTTP URL of the mint, if served.
+    pub onion_url: Option<String>,
+    /// Minimum NIP-13 difficulty of request events (`0` when not required).
+    pub min_pow: u8,
+    /// Bitcoin network of the NIP-87 announcement, published alongside when set.
+    pub nip87_network: Option<String>,
+    /// Refresh interval.
+    pub interval: std::time::Duration,
+}
+
+impl MintAnnouncement {
+    /// Extra tags carrying the mint URLs and request requirements.
+    pub fn extra_tags(&self) -> Vec<nostr::Tag> {
+        let mut tags = Vec::new();
+        if let Some(url) = &self.clearnet_url {
+            tags.push(nostr::Tag::custom(TagKind::custom("url"), [url.clone()]));
+        }
+        if let Some(url) = &self.onion_url {
+            tags.push(nostr::Tag::custom(TagKind::custom("onion"), [url.clone()]));
+        }
+        if self.min_pow > 0 {
+            tags.push(nostr::Tag::custom(TagKind::custom(POW_TAG), [self.min_pow.to_string()]));
+        }
+        tags
+    }
+
+    /// HTTP URLs of the mint reachable from other hosts.
+    pub fn urls(&self) -> Vec<String> {
+        let is_local = |url: &String| {
+            let Ok(url) = reqwest::Url::parse(url) else {
+                return true;
+            };
+            match url.host() {
+                Some(url::Host::Domain(domain)) => domain == "localhost",
+                Some(url::Host::Ipv4(ip)) => ip.is_loopback() || ip.is_unspecified(),
+                Some(url::Host::Ipv6(ip)) => ip.is_loopback() || ip.is_unspecified(),
+                None => true,
+            }
+        };
+        self.clearnet_url
+            .iter()
+            .chain(&self.onion_url)
+            .filter(|url| !is_local(url))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Minimum request difficulty advertised by a kind:37400 announcement (`0` when absent).
+pub fn required_pow(announcement: &nostr::Event) -> u8 {
+    announcement
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .find(|tag| tag.len() > 1 && tag[0] == POW_TAG)
+        .and_then(|tag| tag[1].parse().ok())
+        .unwrap_or(0)
+}
+
+/// Mint whose info and keysets a NIP-74 service announces.
+#[derive(Clone)]
+pub enum MintInfoSource {
+    /// The in-process mint.
+    Local(Arc<Mint>),
+    /// The upstream mint that requests are proxied to.
+    Upstream(Arc<DefaultRequestHandler>),
+}
+
+impl MintInfoSource {
+    /// Load the current mint info.
+    pub async fn mint_info(&self) -> anyhow::Result<cdk::nuts::MintInfo> {
+        match self {
+            MintInfoSource::Local(mint) => {
+                mint.mint_info().await.map_err(|e| anyhow!("Failed to load mint info: {}", e))
+            }
+            MintInfoSource::Upstream(upstream) => upstream
+                .mint_info()
+                .await
+                .map_err(|e| anyhow!("Failed to fetch mint info from {}: {}", upstream.base_url(), e)),
+        }
+    }
+
+    /// Load the keysets of the mint.
+    pub async fn keysets(&self) -> anyhow::Result<KeysetResponse> {
+        match self {
+            MintInfoSource::Local(mint) => Ok(mint.keysets()),
+            MintInfoSource::Upstream(upstream) => upstream
+                .keysets()
+                .await
+                .map_err(|e| anyhow!("Failed to fetch keysets from {}: {}", upstream.base_url(), e)),
+        }
+    }
+}
+
+/// Background publisher for the kind:37400 mint announcement.
+///
+/// Publishes on start, every `interval` and whenever the status changes.
+pub struct MintAnnouncer {
+    client: Client,
+    keys: nostr::Keys,
+    source: MintInfoSource,
+    announcement: MintAnnouncement,
+    status: tokio::sync::watch::Sender<MintStatus>,
+    shutdown: Arc<Notify>,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl MintAnnouncer {
+    /// Create a new announcer publishing the info of `source` through `client`.
+    pub fn new(client: Client, keys: nostr::Keys, source: MintInfoSource, announcement: MintAnnouncement) -> Self {
+        let (status, _) = tokio::sync::watch::channel(MintStatus::Running);
+        Self {
+            client,
+            keys,
+            source,
+            announcement,
+            status,
+            shutdown: Arc::new(Notify::new()),
+            task: None,
+        }
+    }
+
+    /// Currently announced status.
+    pub fn status(&self) -> MintStatus {
+        *self.status.borrow()
+    }
+
+    /// Change the announced status; triggers an immediate republish.
+    pub fn set_status(&self, status: MintStatus) {
+        self.status.send_replace(status);
+    }
+
+    /// Publish the announcement once and start the refresh task.
+    pub async fn start(&mut self) -> anyhow::Result<()> {
+        if self.task.is_some() {
+            return Ok(());
+        }
+
+        Self::publish(&self.client, &self.keys, &self.source, &self.announcement, self.status()).await?;
+
+        let task = tokio::spawn(Self::run(
+            self.client.clone(),
+            self.keys.clone(),
+            self.source.clone(),
+            self.announcement.clone(),
+            self.status.subscribe(),
+            self.shutdown.clone(),
+        ));
+        self.task = Some(task);
+        Ok(())
+    }
+
+    /// Announce `stopping` and stop the refresh task.
+    pub async fn stop(&mut self) {
+        let Some(task) = self.task.take() else {
+            return;
+        };
+
+        self.shutdown.notify_waiters();
+        task.abort();
+        let _ = task.await;
+
+        self.status.send_replace(MintStatus::Stopping);
+        if let Err(e) = Self::publish(&self.client, &self.keys, &self.source, &self.announcement, MintStatus::Stopping).await {
+            warn!("Failed to publish stopping announcement: {}", e);
+        }
+    }
+
+    async fn run(
+        client: Client,
+        keys: nostr::Keys,
+        source: MintInfoSource,
+        announcement: MintAnnouncement,
+        mut status: tokio::sync::watch::Receiver<MintStatus>,
+        shutdown: Arc<Notify>,
+    ) {
+        let minimum = std::time::Duration::from_secs(crate::config::MIN_ANNOUNCEMENT_INTERVAL);
+        let mut interval = tokio::time::interval(announcement.interval.max(minimum));
+        // First tick completes immediately; the initial announcement was already published.
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = shutdown.notified() => break,
+                _ = interval.tick() => {}
+                changed = status.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            let current = *status.borrow_and_update();
+            if let Err(e) = Self::publish(&client, &keys, &source, &announcement, current).await {
+                warn!("Failed to publish mint announcement: {}", e);
+            }
+        }
+    }
+
+    async fn publish(
+        client: &Client,
+        keys: &nostr::Keys,
+        source: &MintInfoSource,
+        announcement: &MintAnnouncement,
+        status: MintStatus,
+    ) -> anyhow::Result<nostr::EventId> {
+        let mint_info = source.mint_info().await?;
+
+        let event = build_mint_info_event(
+            &mint_info,
+            keys,
+            &announcement.identifier,
+            &announcement.relays,
+            status.as_str(),
+            Some(announcement.extra_tags()),
+        )
+        .await?;
+
+        let output = client
+            .send_event(&event)
+            .await
+            .map_err(|e| anyhow!("Failed to send mint announcement: {}", e))?;
+        info!(
+            "Published kind {} announcement ({}) to {} relays",
+            KIND_MINT_INFO,
+            status.as_str(),
+            output.success.len()
+        );
+
+        // NIP-87 announcements point at HTTP URLs, so Nostr-only mints skip them.
+        let urls = announcement.urls();
+        if let Some(network) = &announcement.nip87_network {
+            if !urls.is_empty() && status != MintStatus::Stopping {
+                let nip87 = build_nip87_announcement_event(&mint_info, keys, &urls, network).await?;
+                match client.send_event(&nip87).await {
+                    Ok(output) => debug!(
+                        "Published kind {} announcement to {} relays",
+                        KIND_CASHU_MINT_ANNOUNCEMENT,
+                        output.success.len()
+                    ),
+                    Err(e) => warn!("Failed to send NIP-87 announcement: {}", e),
+                }
+            }
+        }
+        Ok(event.id)
+    }
+}
+
+impl Drop for MintAnnouncer {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+// ===== TESTS =====
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_result_status_serde() {
+        let ok = ResultStatus::Success;
+        let err = ResultStatus::Error;
+        let ok_json = serde_json::to_string(&ok).unwrap();
+        let err_json = serde_json::to_string(&err).unwrap();
         assert_eq!(ok_json, "\"success\"");
         assert_eq!(err_json, "\"error\"");
         assert_eq!(serde_json::from_str::<ResultStatus>(&ok_json).unwrap(), ok);
@@ -460,6 +2698,23 @@ mod tests {
         assert_eq!(d, OperationMethod::Mint);
     }
 
+    #[test]
+    fn test_operation_method_nut_variants_serde() {
+        let cases = [
+            (OperationMethod::Swap, "\"swap\""),
+            (OperationMethod::CheckState, "\"check_state\""),
+            (OperationMethod::Restore, "\"restore\""),
+            (OperationMethod::Keys, "\"keys\""),
+            (OperationMethod::Keysets, "\"keysets\""),
+            (OperationMethod::Batch, "\"batch\""),
+        ];
+        for (method, expected) in cases {
+            let s = serde_json::to_string(&method).unwrap();
+            assert_eq!(s, expected);
+            assert_eq!(serde_json::from_str::<OperationMethod>(&s).unwrap(), method);
+        }
+    }
+
     #[test]
     fn test_operation_request_roundtrip() {
         let req = OperationRequest {
@@ -492,11 +2747,107 @@ mod tests {
 
     #[test]
     fn test_result_error_serde() {
-        let err = ResultError { code: "fail".into(), message: "fail msg".into() };
+        let err = ResultError { code: "fail".into(), message: "fail msg".into(), cashu_code: None };
         let s = serde_json::to_string(&err).unwrap();
         let de: ResultError = serde_json::from_str(&s).unwrap();
         assert_eq!(de.code, "fail");
         assert_eq!(de.message, "fail msg");
+        assert_eq!(de.error_code(), ErrorCode::Unknown);
+    }
+
+    #[test]
+    fn test_error_code_cashu_mapping() {
+        for code in [11001u16, 11006, 12002, 20001, 20007] {
+            assert_eq!(ErrorCode::from_cashu_code(code).cashu_code(), Some(code));
+        }
+        assert_eq!(ErrorCode::from_cashu_code(11001), ErrorCode::TokenAlreadySpent);
+        assert_eq!(ErrorCode::from_cashu_code(20001), ErrorCode::QuoteNotPaid);
+        assert_eq!(ErrorCode::from_cashu_code(12002), ErrorCode::KeysetInactive);
+        assert_eq!(ErrorCode::from_cashu_code(11006), ErrorCode::AmountOutOfRange);
+        assert_eq!(ErrorCode::from_cashu_code(1), ErrorCode::InternalError);
+
+        let err = ResultError::new(ErrorCode::QuoteNotPaid, "quote not paid");
+        assert_eq!(err.code, "quote_not_paid");
+        assert_eq!(err.cashu_code, Some(20001));
+        assert_eq!(err.error_code(), ErrorCode::QuoteNotPaid);
+        assert_eq!(serde_json::to_string(&ErrorCode::QuoteNotPaid).unwrap(), "\"quote_not_paid\"");
+    }
+
+    #[test]
+    fn test_proxy_routes() {
+        let route = DefaultRequestHandler::route(&OperationMethod::CheckMintQuote, &json!("abc-123")).unwrap();
+        assert_eq!(route, MintRoute::get("/v1/mint/quote/bolt11/abc-123"));
+
+        let route =
+            DefaultRequestHandler::route(&OperationMethod::CheckMeltQuote, &json!({"quote": "q1"})).unwrap();
+        assert_eq!(route, MintRoute::get("/v1/melt/quote/bolt11/q1"));
+
+        let route = DefaultRequestHandler::route(&OperationMethod::CheckMintQuoteBolt12, &json!("abc-123")).unwrap();
+        assert_eq!(route, MintRoute::get("/v1/mint/quote/bolt12/abc-123"));
+
+        let route = DefaultRequestHandler::route(&OperationMethod::MeltBolt12, &Value::Null).unwrap();
+        assert_eq!(route, MintRoute::post("/v1/melt/bolt12"));
+
+        let route = DefaultRequestHandler::route(&OperationMethod::Swap, &Value::Null).unwrap();
+        assert_eq!(route, MintRoute::post("/v1/swap"));
+
+        let route = DefaultRequestHandler::route(&OperationMethod::Keys, &json!({"keyset_id": "009a1f293253e41e"}))
+            .unwrap();
+        assert_eq!(route, MintRoute::get("/v1/keys/009a1f293253e41e"));
+
+        let err = DefaultRequestHandler::route(&OperationMethod::CheckMintQuote, &json!("../keys")).unwrap_err();
+        assert_eq!(err.error_code(), ErrorCode::InvalidRequest);
+    }
+
+    #[tokio::test]
+    async fn test_run_batch() {
+        let requests = vec![
+            OperationRequest::new(OperationMethod::Info, None),
+            OperationRequest::new(OperationMethod::Swap, None),
+            OperationRequest::new(OperationMethod::Keysets, None),
+        ];
+        let data = run_batch(json!(requests), |method, _| async move { Ok(json!(method)) })
+            .await
+            .unwrap();
+
+        let results: Vec<OperationResult> = serde_json::from_value(data).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].request_id, requests[0].request_id);
+        assert_eq!(results[0].data, Some(json!("info")));
+        assert_eq!(results[1].error.as_ref().unwrap().error_code(), ErrorCode::InvalidRequest);
+        assert_eq!(results[2].data, Some(json!("keysets")));
+
+        let oversized = vec![OperationRequest::new(OperationMethod::Info, None); MAX_BATCH_SIZE + 1];
+        let err = run_batch(json!(oversized), |_, _| async move { Ok(Value::Null) })
+            .await
+            .unwrap_err();
+        assert_eq!(err.error_code(), ErrorCode::InvalidRequest);
+        assert_eq!(
+            DefaultRequestHandler::route(&OperationMethod::Batch, &Value::Null).unwrap_err(),
+            nested_batch()
+        );
+    }
+
+    #[test]
+    fn test_proxy_base_url() {
+        let handler = DefaultRequestHandler::with_base_url("https://mint.example.com/", None).unwrap();
+        assert_eq!(handler.base_url(), "https://mint.example.com");
+
+        assert!(DefaultRequestHandler::with_base_url("http://mintabc.onion", None).is_err());
+        assert!(DefaultRequestHandler::with_base_url("http://mintabc.onion", Some("socks5h://127.0.0.1:9050")).is_ok());
+    }
+
+    #[test]
+    fn test_error_from_mintd_response() {
+        let err = DefaultRequestHandler::error_from_response(
+            reqwest::StatusCode::BAD_REQUEST,
+            r#"{"code": 11001, "detail": "Token already spent"}"#,
+        );
+        assert_eq!(err.error_code(), ErrorCode::TokenAlreadySpent);
+        assert_eq!(err.message, "Token already spent");
+
+        let err = DefaultRequestHandler::error_from_response(reqwest::StatusCode::BAD_GATEWAY, "upstream down");
+        assert_eq!(err.error_code(), ErrorCode::BackendUnavailable);
     }
 
     #[test]
@@ -543,4 +2894,913 @@ mod tests {
         assert_eq!(event.kind, nostr::Kind::from(27402u16));
         assert_eq!(event.pubkey, author);
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_build_mint_info_event_tags() {
+        let keys = nostr::Keys::generate();
+        let mint_info = cdk::nuts::MintInfo::default().name("test-mint");
+        let announcement = MintAnnouncement {
+            identifier: keys.public_key().to_hex(),
+            relays: vec![nostr::RelayUrl::parse("wss://relay.example.com").unwrap()],
+            clearnet_url: Some("https://mint.example.com".to_string()),
+            onion_url: Some("http://example.onion".to_string()),
+            min_pow: 16,
+            nip87_network: Some("mainnet".to_string()),
+            interval: std::time::Duration::from_secs(3600),
+        };
+
+        let event = build_mint_info_event(
+            &mint_info,
+            &keys,
+            &announcement.identifier,
+            &announcement.relays,
+            MintStatus::Maintenance.as_str(),
+            Some(announcement.extra_tags()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(event.kind, nostr::Kind::from(KIND_MINT_INFO));
+        let find = |name: &str| {
+            event
+                .tags
+                .iter()
+                .find(|t| t.as_slice()[0] == name)
+                .map(|t| t.as_slice()[1].clone())
+        };
+        assert_eq!(find("d"), Some(keys.public_key().to_hex()));
+        assert_eq!(find("status"), Some("maintenance".to_string()));
+        assert_eq!(find("url"), Some("https://mint.example.com".to_string()));
+        assert_eq!(find("onion"), Some("http://example.onion".to_string()));
+        assert_eq!(find(POW_TAG), Some("16".to_string()));
+        assert_eq!(required_pow(&event), 16);
+        assert_eq!(announcement.urls().len(), 2);
+
+        let content: cdk::nuts::MintInfo = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(content.name, Some("test-mint".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_build_nip87_announcement_event() {
+        let keys = nostr::Keys::generate();
+        let mut mint_info = cdk::nuts::MintInfo::default().name("test-mint");
+        mint_info.nuts.nut07.supported = true;
+        mint_info.nuts.nut08.supported = false;
+        let urls = vec!["https://mint.example.com".to_string(), "http://example.onion".to_string()];
+
+        let event = build_nip87_announcement_event(&mint_info, &keys, &urls, "mainnet")
+            .await
+            .unwrap();
+
+        assert_eq!(event.kind, nostr::Kind::from(KIND_CASHU_MINT_ANNOUNCEMENT));
+        let values = |name: &str| -> Vec<String> {
+            event
+                .tags
+                .iter()
+                .filter(|t| t.as_slice()[0] == name)
+                .map(|t| t.as_slice()[1].clone())
+                .collect()
+        };
+        assert_eq!(values("d"), vec![keys.public_key().to_hex()]);
+        assert_eq!(values("u"), urls);
+        assert_eq!(values("n"), vec!["mainnet".to_string()]);
+        let nuts = &values("nuts")[0];
+        assert!(nuts.starts_with("1,2,3,"));
+        assert!(nuts.split(',').any(|nut| nut == "7"));
+        assert!(!nuts.split(',').any(|nut| nut == "8"));
+        assert!(!nuts.split(',').any(|nut| nut == "25"));
+
+        // A BOLT12 payment method is announced as NUT-25
+        let mut bolt12_info = mint_info.clone();
+        let mut nuts_json = serde_json::to_value(&bolt12_info.nuts).unwrap();
+        nuts_json["4"]["methods"] = json!([{ "method": "bolt12", "unit": "sat" }]);
+        bolt12_info.nuts = serde_json::from_value(nuts_json).unwrap();
+        assert!(supported_nuts(&bolt12_info).contains(&25));
+
+        let metadata: Value = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(metadata, json!({ "name": "test-mint" }));
+    }
+
+    #[tokio::test]
+    async fn test_operation_request_to_event_with_signer() {
+        let client_keys = nostr::Keys::generate();
+        let mint_keys = nostr::Keys::generate();
+        let request = OperationRequest::new(OperationMethod::Keysets, None);
+
+        let event = request
+            .to_event_with_signer(&client_keys, &mint_keys.public_key(), None)
+            .await
+            .unwrap();
+        assert_eq!(event.kind, nostr::Kind::from(KIND_OPERATION_REQUEST));
+        assert_eq!(event.pubkey, client_keys.public_key());
+
+        let plaintext = nostr::nips::nip44::decrypt(
+            mint_keys.secret_key(),
+            &client_keys.public_key(),
+            &event.content,
+        )
+        .unwrap();
+        let decoded: OperationRequest = serde_json::from_str(&plaintext).unwrap();
+        assert_eq!(decoded.request_id, request.request_id);
+        assert_eq!(decoded.method, OperationMethod::Keysets);
+    }
+
+    #[tokio::test]
+    async fn test_request_events_are_mined() {
+        let client_keys = nostr::Keys::generate();
+        let mint_keys = nostr::Keys::generate();
+        let request = OperationRequest::new(OperationMethod::Info, None);
+
+        let event = request
+            .to_event_with_pow(&client_keys, &mint_keys.public_key(), 8, None)
+            .await
+            .unwrap();
+        assert!(event.id.check_pow(8));
+
+        let (wrap, rumor_id) = request
+            .to_gift_wrap_with_pow(&client_keys, &mint_keys.public_key(), 8, None)
+            .await
+            .unwrap();
+        assert!(wrap.id.check_pow(8));
+
+        // The mined wrap still opens to the original request
+        let unwrapped = nostr::nips::nip59::UnwrappedGift::from_gift_wrap(&mint_keys, &wrap).await.unwrap();
+        assert_eq!(unwrapped.sender, client_keys.public_key());
+        assert_eq!(unwrapped.rumor.id, Some(rumor_id));
+    }
+
+    #[tokio::test]
+    async fn test_references_event() {
+        let keys = nostr::Keys::generate();
+        let request_id = nostr::EventId::all_zeros();
+        let reply = OperationResult {
+            status: ResultStatus::Success,
+            request_id: "reqid".to_string(),
+            data: None,
+            error: None,
+        }
+        .to_event_with_signer(&keys, &keys.public_key(), &keys.public_key(), &request_id, None)
+        .await
+        .unwrap();
+
+        assert!(references_event(&reply, &request_id));
+        assert!(!references_event(&reply, &reply.id));
+    }
+
+    struct EchoHandler;
+
+    #[async_trait]
+    impl RequestHandler for EchoHandler {
+        async fn handle(&self, _ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+            Ok(OperationResult {
+                status: ResultStatus::Success,
+                request_id: req.request_id,
+                data: req.data,
+                error: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_event_roundtrip() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let request = OperationRequest {
+            method: OperationMethod::Info,
+            request_id: new_request_id(),
+            data: Some(serde_json::json!({"ping": true})),
+        };
+        let ciphertext = nostr::nips::nip44::encrypt(
+            client_keys.secret_key(),
+            &mint_keys.public_key(),
+            serde_json::to_string(&request).unwrap(),
+            Default::default(),
+        )
+        .unwrap();
+        let request_event = nostr::EventBuilder::new(nostr::Kind::from(KIND_OPERATION_REQUEST), ciphertext)
+            .tag(nostr::Tag::public_key(mint_keys.public_key()))
+            .sign_with_keys(&client_keys)
+            .unwrap();
+
+        let reply = handle_request_event(&mint_keys, &EchoHandler, None, &request_event, None)
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(reply.kind, nostr::Kind::from(KIND_OPERATION_RESULT));
+        assert_eq!(reply.pubkey, mint_keys.public_key());
+
+        let plaintext = nostr::nips::nip44::decrypt(
+            client_keys.secret_key(),
+            &mint_keys.public_key(),
+            &reply.content,
+        )
+        .unwrap();
+        let result: OperationResult = serde_json::from_str(&plaintext).unwrap();
+        assert_eq!(result.status, ResultStatus::Success);
+        assert_eq!(result.request_id, request.request_id);
+        assert_eq!(result.data.unwrap()["ping"], true);
+    }
+
+    struct CountingHandler(std::sync::atomic::AtomicUsize);
+
+    #[async_trait]
+    impl RequestHandler for CountingHandler {
+        async fn handle(&self, _ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+            let count = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Ok(OperationResult {
+                status: ResultStatus::Success,
+                request_id: req.request_id,
+                data: Some(serde_json::json!({ "count": count })),
+                error: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_event_deduplicates() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let handler = CountingHandler(std::sync::atomic::AtomicUsize::new(0));
+        let store = RequestStore::in_memory(300);
+
+        let request_event = OperationRequest::new(OperationMethod::Mint, None)
+            .to_event_with_signer(&client_keys, &mint_keys.public_key(), None)
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let reply = handle_request_event(&mint_keys, &handler, Some(&store), &request_event, None)
+                .await
+                .unwrap()
+                .remove(0);
+            let plaintext = nostr::nips::nip44::decrypt(
+                client_keys.secret_key(),
+                &mint_keys.public_key(),
+                &reply.content,
+            )
+            .unwrap();
+            let result: OperationResult = serde_json::from_str(&plaintext).unwrap();
+            assert_eq!(result.data.unwrap()["count"], 1);
+        }
+        assert_eq!(handler.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_request_gets_error_reply() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let ciphertext = nostr::nips::nip44::encrypt(
+            client_keys.secret_key(),
+            &mint_keys.public_key(),
+            r#"{"method": "teleport", "request_id": "req-9"}"#,
+            Default::default(),
+        )
+        .unwrap();
+        let request_event = nostr::EventBuilder::new(nostr::Kind::from(KIND_OPERATION_REQUEST), ciphertext)
+            .tag(nostr::Tag::public_key(mint_keys.public_key()))
+            .sign_with_keys(&client_keys)
+            .unwrap();
+
+        let reply = handle_request_event(&mint_keys, &EchoHandler, None, &request_event, None)
+            .await
+            .unwrap()
+            .remove(0);
+        let plaintext = nostr::nips::nip44::decrypt(
+            client_keys.secret_key(),
+            &mint_keys.public_key(),
+            &reply.content,
+        )
+        .unwrap();
+        let result: OperationResult = serde_json::from_str(&plaintext).unwrap();
+        assert_eq!(result.status, ResultStatus::Error);
+        assert_eq!(result.request_id, "req-9");
+        assert_eq!(result.error.unwrap().error_code(), ErrorCode::UnsupportedMethod);
+    }
+
+    #[tokio::test]
+    async fn test_undecryptable_request_gets_decryption_failed() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let request_event = nostr::EventBuilder::new(nostr::Kind::from(KIND_OPERATION_REQUEST), "not encrypted")
+            .tag(nostr::Tag::public_key(mint_keys.public_key()))
+            .sign_with_keys(&client_keys)
+            .unwrap();
+
+        let replies = handle_request_event(&mint_keys, &EchoHandler, None, &request_event, None)
+            .await
+            .unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].pubkey, mint_keys.public_key());
+        assert!(replies[0].tags.event_ids().any(|id| *id == request_event.id));
+        let plaintext = nostr::nips::nip44::decrypt(
+            client_keys.secret_key(),
+            &mint_keys.public_key(),
+            &replies[0].content,
+        )
+        .unwrap();
+        let result: OperationResult = serde_json::from_str(&plaintext).unwrap();
+        assert_eq!(result.status, ResultStatus::Error);
+        assert_eq!(result.request_id, request_event.id.to_hex());
+        assert_eq!(result.error.unwrap().error_code(), ErrorCode::DecryptionFailed);
+    }
+
+    #[tokio::test]
+    async fn test_unopenable_gift_wrap_is_dropped() {
+        let mint_keys = nostr::Keys::generate();
+        let wrap = nostr::EventBuilder::new(nostr::Kind::GiftWrap, "not a seal")
+            .tag(nostr::Tag::public_key(mint_keys.public_key()))
+            .sign_with_keys(&nostr::Keys::generate())
+            .unwrap();
+
+        let replies = handle_request_event(&mint_keys, &EchoHandler, None, &wrap, None)
+            .await
+            .unwrap();
+        assert!(replies.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_request_without_request_id_echoes_event_id() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let ciphertext = nostr::nips::nip44::encrypt(
+            client_keys.secret_key(),
+            &mint_keys.public_key(),
+            r#"{"method": "info"}"#,
+            Default::default(),
+        )
+        .unwrap();
+        let request_event = nostr::EventBuilder::new(nostr::Kind::from(KIND_OPERATION_REQUEST), ciphertext)
+            .tag(nostr::Tag::public_key(mint_keys.public_key()))
+            .sign_with_keys(&client_keys)
+            .unwrap();
+
+        let reply = handle_request_event(&mint_keys, &EchoHandler, None, &request_event, None)
+            .await
+            .unwrap()
+            .remove(0);
+        let plaintext = nostr::nips::nip44::decrypt(
+            client_keys.secret_key(),
+            &mint_keys.public_key(),
+            &reply.content,
+        )
+        .unwrap();
+        let result: OperationResult = serde_json::from_str(&plaintext).unwrap();
+        assert_eq!(result.request_id, request_event.id.to_hex());
+        assert_eq!(result.error.unwrap().error_code(), ErrorCode::InvalidRequest);
+    }
+
+    #[tokio::test]
+    async fn test_gift_wrapped_request_gets_gift_wrapped_reply() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let request = OperationRequest::new(OperationMethod::Info, Some(serde_json::json!({"ping": true})));
+
+        let (wrap, rumor_id) = request
+            .to_gift_wrap_with_signer(&client_keys, &mint_keys.public_key(), None)
+            .await
+            .unwrap();
+        assert_eq!(wrap.kind, nostr::Kind::GiftWrap);
+        assert_ne!(wrap.pubkey, client_keys.public_key());
+
+        let reply = handle_request_event(&mint_keys, &EchoHandler, None, &wrap, None)
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(reply.kind, nostr::Kind::GiftWrap);
+        assert_ne!(reply.pubkey, mint_keys.public_key());
+
+        let (result, reply_to) = OperationResult::from_reply_event(&client_keys, &mint_keys.public_key(), &reply)
+            .await
+            .unwrap();
+        assert_eq!(reply_to, Some(rumor_id));
+        assert_eq!(result.request_id, request.request_id);
+        assert_eq!(result.data.unwrap()["ping"], true);
+    }
+
+    #[tokio::test]
+    async fn test_gift_wrap_for_other_recipient_is_ignored() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let request = OperationRequest::new(OperationMethod::Info, None);
+
+        let (wrap, _) = request
+            .to_gift_wrap_with_signer(&client_keys, &nostr::Keys::generate().public_key(), None)
+            .await
+            .unwrap();
+        let replies = handle_request_event(&mint_keys, &EchoHandler, None, &wrap, None).await.unwrap();
+        assert!(replies.is_empty());
+    }
+
+    #[test]
+    fn test_result_chunks_reassemble_in_any_order() {
+        let result = OperationResult::success("req-1", serde_json::json!({ "signatures": "ü".repeat(100) }));
+        let mut chunks = ResultChunk::split(&result, 32).unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.data.len() <= 32 && c.total == chunks.len() as u32));
+        assert_eq!(ResultChunk::split(&result, MAX_RESULT_SIZE).unwrap().len(), 1);
+
+        chunks.reverse();
+        let last = chunks.pop().unwrap();
+        let mut assembler = ResultAssembler::new();
+        for chunk in chunks.iter().cloned().chain(chunks.first().cloned()) {
+            assert!(assembler.push(chunk).unwrap().is_none());
+        }
+        let assembled = assembler.push(last).unwrap().unwrap();
+        assert_eq!(assembled.request_id, "req-1");
+        assert_eq!(assembled.data, result.data);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_result_is_sent_in_chunks() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let blob = "x".repeat(MAX_RESULT_SIZE * 2);
+        let request = OperationRequest::new(OperationMethod::Restore, Some(serde_json::json!({ "blob": blob })));
+
+        for gift_wrap in [false, true] {
+            let (request_event, request_event_id) = if gift_wrap {
+                request
+                    .to_gift_wrap_with_signer(&client_keys, &mint_keys.public_key(), None)
+                    .await
+                    .unwrap()
+            } else {
+                let event = request
+                    .to_event_with_signer(&client_keys, &mint_keys.public_key(), None)
+                    .await
+                    .unwrap();
+                let id = event.id;
+                (event, id)
+            };
+
+            let replies = handle_request_event(&mint_keys, &EchoHandler, None, &request_event, None)
+                .await
+                .unwrap();
+            assert_eq!(replies.len(), 3);
+
+            let mut assembler = ResultAssembler::new();
+            let mut assembled = None;
+            for reply in &replies {
+                assert!(OperationResult::from_reply_event(&client_keys, &mint_keys.public_key(), reply)
+                    .await
+                    .is_none());
+                let (reply, reply_to) = Reply::from_event(&client_keys, &mint_keys.public_key(), reply)
+                    .await
+                    .unwrap();
+                assert_eq!(reply_to, Some(request_event_id));
+                match reply {
+                    Reply::Chunk(chunk) => assembled = assembler.push(chunk).unwrap(),
+                    Reply::Result(_) => panic!("expected a chunk"),
+                }
+            }
+            let result = assembled.unwrap();
+            assert_eq!(result.request_id, request.request_id);
+            assert_eq!(result.data.unwrap()["blob"], blob);
+        }
+    }
+
+    /// Handler over an in-process mint with a FakeWallet backend for BOLT11 and BOLT12.
+    ///
+    /// The returned directory holds the mint database and must outlive the handler.
+    async fn fake_wallet_handler() -> (DefaultMintHandler, tempfile::TempDir) {
+        use cdk::mint::{MintBuilder, MintMeltLimits};
+        use cdk::nuts::{CurrencyUnit, PaymentMethod};
+        use cdk::types::FeeReserve;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let database = cdk_sqlite::MintSqliteDatabase::new(temp_dir.path().join("mint.db")).await.unwrap();
+        let wallet = Arc::new(cdk_fake_wallet::FakeWallet::new(
+            FeeReserve {
+                min_fee_reserve: 1.into(),
+                percent_fee_reserve: 0.02,
+            },
+            std::collections::HashMap::new(),
+            std::collections::HashSet::new(),
+            1,
+        ));
+        let mut builder = MintBuilder::new()
+            .with_localstore(Arc::new(database.clone()))
+            .with_keystore(Arc::new(database));
+        for method in [PaymentMethod::Bolt11, PaymentMethod::Bolt12] {
+            builder = builder
+                .add_ln_backend(CurrencyUnit::Sat, method, MintMeltLimits::new(1, 100_000), wallet.clone())
+                .await
+                .unwrap();
+        }
+        let handler = DefaultMintHandler::new(builder.with_seed(vec![7; 64]).build().await.unwrap());
+        (handler, temp_dir)
+    }
+
+    /// Keys of the active sat keyset, read through the `keysets` and `keys` methods.
+    async fn active_keys(handler: &DefaultMintHandler) -> (Id, cdk::nuts::Keys) {
+        use cdk::nuts::{CurrencyUnit, KeysResponse};
+
+        let keysets: KeysetResponse =
+            serde_json::from_value(handler.dispatch(&OperationMethod::Keysets, Value::Null).await.unwrap()).unwrap();
+        let keyset = keysets
+            .keysets
+            .into_iter()
+            .find(|keyset| keyset.active && keyset.unit == CurrencyUnit::Sat)
+            .unwrap();
+        let keys: KeysResponse = serde_json::from_value(
+            handler
+                .dispatch(&OperationMethod::Keys, json!({ "keyset_id": keyset.id }))
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let keys = keys.keysets.into_iter().next().unwrap();
+        assert_eq!(keys.id, keyset.id);
+        (keyset.id, keys.keys)
+    }
+
+    /// Mint `amount` sat of proofs through a paid BOLT11 quote.
+    async fn mint_proofs(handler: &DefaultMintHandler, amount: u64) -> cdk::nuts::Proofs {
+        use cdk::amount::SplitTarget;
+        use cdk::nuts::{MintResponse, PreMintSecrets};
+
+        let (keyset_id, keys) = active_keys(handler).await;
+        let quote = handler
+            .dispatch(&OperationMethod::GetMintQuote, json!({ "amount": amount, "unit": "sat" }))
+            .await
+            .unwrap();
+        // The fake wallet settles the invoice after its delay
+        for _ in 0..50 {
+            let checked = handler
+                .dispatch(&OperationMethod::CheckMintQuote, quote["quote"].clone())
+                .await
+                .unwrap();
+            if checked["state"] == "PAID" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        let premint = PreMintSecrets::random(keyset_id, amount.into(), &SplitTarget::default()).unwrap();
+        let response: MintResponse = serde_json::from_value(
+            handler
+                .dispatch(
+                    &OperationMethod::Mint,
+                    json!({ "quote": quote["quote"], "outputs": premint.blinded_messages() }),
+                )
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        cdk::dhke::construct_proofs(response.signatures, premint.rs(), premint.secrets(), &keys).unwrap()
+    }
+
+    /// Swap `proofs` for new outputs of the same amount.
+    async fn swap_proofs(
+        handler: &DefaultMintHandler,
+        proofs: &cdk::nuts::Proofs,
+    ) -> (cdk::nuts::PreMintSecrets, cdk::nuts::SwapResponse) {
+        use cdk::amount::SplitTarget;
+        use cdk::nuts::PreMintSecrets;
+
+        let (keyset_id, _) = active_keys(handler).await;
+        let amount = proofs.iter().map(|proof| proof.amount).fold(cdk::Amount::ZERO, |a, b| a + b);
+        let premint = PreMintSecrets::random(keyset_id, amount, &SplitTarget::default()).unwrap();
+        let request = SwapRequest::new(proofs.clone(), premint.blinded_messages());
+        let response = handler.dispatch(&OperationMethod::Swap, json!(request)).await.unwrap();
+        (premint, serde_json::from_value(response).unwrap())
+    }
+
+    /// Proof states reported by the `check_state` method.
+    async fn proof_states(handler: &DefaultMintHandler, proofs: &cdk::nuts::Proofs) -> Vec<cdk::nuts::State> {
+        let request = CheckStateRequest {
+            ys: proofs.iter().map(|proof| proof.y().unwrap()).collect(),
+        };
+        let response = handler.dispatch(&OperationMethod::CheckState, json!(request)).await.unwrap();
+        let response: cdk::nuts::CheckStateResponse = serde_json::from_value(response).unwrap();
+        response.states.into_iter().map(|state| state.state).collect()
+    }
+
+    #[tokio::test]
+    async fn test_mint_method_must_match_quote() {
+        let (handler, _temp_dir) = fake_wallet_handler().await;
+
+        let offer = handler
+            .dispatch(
+                &OperationMethod::GetMintQuoteBolt12,
+                json!({
+                    "amount": 100,
+                    "unit": "sat",
+                    "pubkey": cdk::nuts::SecretKey::generate().public_key(),
+                }),
+            )
+            .await
+            .unwrap();
+
+        // A BOLT12 quote sent through `mint` would bypass the metering of `mint_bolt12`
+        let error = handler
+            .dispatch(&OperationMethod::Mint, json!({ "quote": offer["quote"], "outputs": [] }))
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_code(), ErrorCode::InvalidRequest);
+
+        let invoice = handler
+            .dispatch(&OperationMethod::GetMintQuote, json!({ "amount": 100, "unit": "sat" }))
+            .await
+            .unwrap();
+        let error = handler
+            .dispatch(&OperationMethod::MintBolt12, json!({ "quote": invoice["quote"], "outputs": [] }))
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_code(), ErrorCode::InvalidRequest);
+    }
+
+    #[tokio::test]
+    async fn test_check_quote_accepts_quote_object() {
+        let (handler, _temp_dir) = fake_wallet_handler().await;
+        let quote = handler
+            .dispatch(&OperationMethod::GetMintQuote, json!({ "amount": 100, "unit": "sat" }))
+            .await
+            .unwrap();
+
+        // Both id forms accepted by the HTTP proxy resolve the same quote
+        for data in [quote["quote"].clone(), json!({ "quote": quote["quote"] })] {
+            let checked = handler.dispatch(&OperationMethod::CheckMintQuote, data).await.unwrap();
+            assert_eq!(checked["quote"], quote["quote"]);
+        }
+
+        let error = handler
+            .dispatch(&OperationMethod::CheckMeltQuote, json!({ "quote": "../keys" }))
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_code(), ErrorCode::InvalidRequest);
+    }
+
+    #[tokio::test]
+    async fn test_keys_match_keysets() {
+        let (handler, _temp_dir) = fake_wallet_handler().await;
+        let (keyset_id, keys) = active_keys(&handler).await;
+        assert!(keys.amount_key(1.into()).is_some());
+
+        // Without a keyset id all active keysets are returned
+        let all: cdk::nuts::KeysResponse =
+            serde_json::from_value(handler.dispatch(&OperationMethod::Keys, Value::Null).await.unwrap()).unwrap();
+        let active = all.keysets.iter().find(|keyset| keyset.id == keyset_id).unwrap();
+        assert_eq!(active.keys, keys);
+    }
+
+    #[tokio::test]
+    async fn test_swap_minted_proofs() {
+        let (handler, _temp_dir) = fake_wallet_handler().await;
+        let (_, keys) = active_keys(&handler).await;
+        let proofs = mint_proofs(&handler, 64).await;
+
+        let (premint, response) = swap_proofs(&handler, &proofs).await;
+        let swapped =
+            cdk::dhke::construct_proofs(response.signatures, premint.rs(), premint.secrets(), &keys).unwrap();
+        let total = |proofs: &cdk::nuts::Proofs| proofs.iter().map(|proof| u64::from(proof.amount)).sum::<u64>();
+        assert_eq!(total(&swapped), 64);
+
+        // Spent inputs cannot be swapped twice, even for fresh outputs
+        let (keyset_id, _) = active_keys(&handler).await;
+        let outputs =
+            cdk::nuts::PreMintSecrets::random(keyset_id, 64.into(), &cdk::amount::SplitTarget::default()).unwrap();
+        let error = handler
+            .dispatch(&OperationMethod::Swap, json!(SwapRequest::new(proofs, outputs.blinded_messages())))
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_code(), ErrorCode::TokenAlreadySpent);
+    }
+
+    #[tokio::test]
+    async fn test_check_state_before_and_after_swap() {
+        use cdk::nuts::State;
+
+        let (handler, _temp_dir) = fake_wallet_handler().await;
+        let proofs = mint_proofs(&handler, 64).await;
+        assert!(proof_states(&handler, &proofs).await.iter().all(|state| *state == State::Unspent));
+
+        swap_proofs(&handler, &proofs).await;
+        let states = proof_states(&handler, &proofs).await;
+        assert_eq!(states.len(), proofs.len());
+        assert!(states.iter().all(|state| *state == State::Spent));
+    }
+
+    #[tokio::test]
+    async fn test_restore_swapped_outputs() {
+        let (handler, _temp_dir) = fake_wallet_handler().await;
+        let proofs = mint_proofs(&handler, 64).await;
+        let (premint, swapped) = swap_proofs(&handler, &proofs).await;
+
+        let request = RestoreRequest {
+            outputs: premint.blinded_messages(),
+        };
+        let response = handler.dispatch(&OperationMethod::Restore, json!(request)).await.unwrap();
+        let restored: cdk::nuts::RestoreResponse = serde_json::from_value(response).unwrap();
+        assert_eq!(restored.outputs, premint.blinded_messages());
+        assert_eq!(restored.signatures, swapped.signatures);
+    }
+
+    /// Websocket URL of `relay` served on a local port.
+    async fn serve_relay(relay: &crate::embedded_relay::EmbeddedRelay) -> String {
+        let base_url = crate::payment::testing::serve(relay.router()).await;
+        format!("{}{}", base_url.replacen("http", "ws", 1), relay.path())
+    }
+
+    /// Embedded relay for NIP-74 traffic, with `mint` exempt from the per-pubkey cap.
+    async fn nip74_relay(mint: &nostr::Keys) -> String {
+        let relay = crate::embedded_relay::EmbeddedRelay::new(
+            crate::config::EmbeddedRelayConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            Some(mint.public_key()),
+        );
+        serve_relay(&relay).await
+    }
+
+    /// NIP-74 service of `mint` answering with `handler` on the relay at `relay_url`.
+    async fn start_service(
+        mint: &nostr::Keys,
+        relay_url: &str,
+        handler: Arc<dyn RequestHandler>,
+        store: Option<Arc<RequestStore>>,
+    ) -> Nip74Service {
+        let relays = RelayConfig {
+            relays: vec![crate::config::RelayEntry::read_write(relay_url)],
+            ..Default::default()
+        };
+        let mut service = Nip74Service::new(mint.clone(), relays, handler);
+        if let Some(store) = store {
+            service.set_request_store(store);
+        }
+        service.start().await.unwrap();
+        service
+    }
+
+    /// Client of `mint` on the relay at `relay_url`, sending each request once.
+    async fn connect_client(mint: &nostr::Keys, relay_url: &str) -> Nip74Client {
+        Nip74Client::connect(nostr::Keys::generate(), mint.public_key(), &[relay_url.to_string()])
+            .await
+            .unwrap()
+            .with_timeout(std::time::Duration::from_secs(5))
+            .with_retries(0)
+    }
+
+    #[tokio::test]
+    async fn test_client_round_trip() {
+        let mint_keys = nostr::Keys::generate();
+        let relay_url = nip74_relay(&mint_keys).await;
+        let mut service = start_service(&mint_keys, &relay_url, Arc::new(EchoHandler), None).await;
+
+        for gift_wrap in [false, true] {
+            let client = connect_client(&mint_keys, &relay_url).await.with_gift_wrap(gift_wrap);
+            let request = OperationRequest::new(OperationMethod::Restore, Some(json!({ "outputs": [] })));
+            let result = client.send(&request).await.unwrap();
+            assert_eq!(result.status, ResultStatus::Success);
+            assert_eq!(result.request_id, request.request_id);
+            assert_eq!(result.data, request.data);
+            client.disconnect().await;
+        }
+        service.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_assembles_chunked_result() {
+        struct BlobHandler;
+
+        #[async_trait]
+        impl RequestHandler for BlobHandler {
+            async fn handle(&self, _ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+                let blob = "x".repeat(MAX_RESULT_SIZE * 2);
+                Ok(OperationResult::success(req.request_id, json!({ "blob": blob })))
+            }
+        }
+
+        let mint_keys = nostr::Keys::generate();
+        let relay_url = nip74_relay(&mint_keys).await;
+        let mut service = start_service(&mint_keys, &relay_url, Arc::new(BlobHandler), None).await;
+
+        let client = connect_client(&mint_keys, &relay_url).await;
+        let result = client.send(&OperationRequest::new(OperationMethod::Keysets, None)).await.unwrap();
+        assert_eq!(result.data.unwrap()["blob"], "x".repeat(MAX_RESULT_SIZE * 2));
+
+        client.disconnect().await;
+        service.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_retries_after_dropped_reply() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        static RESULT_DROPPED: AtomicBool = AtomicBool::new(false);
+
+        /// Accepts every kind but rejects the first result the mint publishes
+        fn drops_first_result(kind: nostr::Kind) -> bool {
+            kind != nostr::Kind::from(KIND_OPERATION_RESULT) || RESULT_DROPPED.swap(true, Ordering::SeqCst)
+        }
+
+        #[derive(Default)]
+        struct CountingHandler(AtomicUsize);
+
+        #[async_trait]
+        impl RequestHandler for CountingHandler {
+            async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                EchoHandler.handle(ctx, req).await
+            }
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(RequestStore::open(temp_dir.path().join("requests.json"), 300).unwrap());
+        let relay = crate::embedded_relay::EmbeddedRelay::accepting(
+            crate::config::EmbeddedRelayConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            drops_first_result,
+        );
+        let relay_url = serve_relay(&relay).await;
+        let mint_keys = nostr::Keys::generate();
+        let handler = Arc::new(CountingHandler::default());
+        let mut service = start_service(&mint_keys, &relay_url, handler.clone(), Some(store)).await;
+
+        let client = connect_client(&mint_keys, &relay_url)
+            .await
+            .with_timeout(std::time::Duration::from_secs(2))
+            .with_retries(1);
+        let request = OperationRequest::new(OperationMethod::Restore, Some(json!({ "outputs": [] })));
+        let result = client.send(&request).await.unwrap();
+        assert!(RESULT_DROPPED.load(Ordering::SeqCst));
+        assert_eq!(result.request_id, request.request_id);
+        assert_eq!(result.data, request.data);
+        // The retry is answered from the request store
+        assert_eq!(handler.0.load(Ordering::SeqCst), 1);
+
+        client.disconnect().await;
+        service.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_times_out_without_reply() {
+        let mint_keys = nostr::Keys::generate();
+        let relay_url = nip74_relay(&mint_keys).await;
+
+        // No service answers for the mint
+        let client = connect_client(&mint_keys, &relay_url)
+            .await
+            .with_timeout(std::time::Duration::from_millis(200))
+            .with_retries(1);
+        let request = OperationRequest::new(OperationMethod::Info, None);
+        match client.send(&request).await {
+            Err(Nip74Error::Timeout(request_id)) => assert_eq!(request_id, request.request_id),
+            other => panic!("expected a timeout, got {:?}", other.map(|result| result.status)),
+        }
+        client.disconnect().await;
+    }
+
+    #[tokio::test]
+    async fn test_requests_beyond_the_concurrency_limit_are_dropped() {
+        struct BlockingHandler {
+            started: Notify,
+            release: Notify,
+        }
+
+        #[async_trait]
+        impl RequestHandler for BlockingHandler {
+            async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+                self.started.notify_one();
+                self.release.notified().await;
+                EchoHandler.handle(ctx, req).await
+            }
+        }
+
+        let mint_keys = nostr::Keys::generate();
+        let relay_url = nip74_relay(&mint_keys).await;
+        let handler = Arc::new(BlockingHandler {
+            started: Notify::new(),
+            release: Notify::new(),
+        });
+        let relays = RelayConfig {
+            relays: vec![crate::config::RelayEntry::read_write(&relay_url)],
+            ..Default::default()
+        };
+        let mut service = Nip74Service::new(mint_keys.clone(), relays, handler.clone());
+        service.set_max_concurrent_requests(1);
+        service.start().await.unwrap();
+
+        let first = connect_client(&mint_keys, &relay_url).await;
+        let pending = tokio::spawn(async move {
+            let result = first.send(&OperationRequest::new(OperationMethod::Info, None)).await;
+            first.disconnect().await;
+            result
+        });
+        handler.started.notified().await;
+
+        // The only slot is taken, so the second request is never answered
+        let second = connect_client(&mint_keys, &relay_url)
+            .await
+            .with_timeout(std::time::Duration::from_millis(500));
+        let error = second.send(&OperationRequest::new(OperationMethod::Info, None)).await.unwrap_err();
+        assert!(matches!(error, Nip74Error::Timeout(_)));
+        second.disconnect().await;
+
+        handler.release.notify_one();
+        assert_eq!(pending.await.unwrap().unwrap().status, ResultStatus::Success);
+        service.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,1114 @@
+//! Nostr Wallet Connect (NIP-47) Lightning backend
+//! Drives a remote wallet through encrypted kind 23194 requests and kind 23195
+//! responses, using the connection secret of a `nostr+walletconnect://` URI,
+//! and follows its kind 23196/23197 payment notifications when it sends them
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use cdk::lightning_invoice::Bolt11Invoice;
+use cdk::types::FeeReserve;
+use cdk_common::nuts::{CurrencyUnit, MeltQuoteState};
+use cdk_common::payment::{
+    self, CreateIncomingPaymentResponse, IncomingPaymentOptions, MakePaymentResponse, MintPayment,
+    OutgoingPaymentOptions, PaymentIdentifier, PaymentQuoteResponse, WaitPaymentResponse,
+};
+use cdk_common::Amount;
+use nostr::event::tag::kind::TagKind;
+use nostr::{Event, EventBuilder, EventId, Filter, Keys, Kind, PublicKey, SecretKey, Tag, Timestamp};
+use nostr_sdk::{Client, RelayPoolNotification};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, warn};
+
+use crate::payment::{
+    FeeCap, InvoiceLookup, InvoiceWatcher, PaymentStream, bolt11_quote, bolt11_settings, check_unit,
+    invoice_expiry, lookup_hash, payment_hash, to_msat,
+};
+use crate::routing::HealthProbe;
+
+/// Replaceable wallet info event listing the supported methods and encryptions
+pub const KIND_NWC_INFO: u16 = 13194;
+/// Encrypted wallet request
+pub const KIND_NWC_REQUEST: u16 = 23194;
+/// Encrypted wallet response
+pub const KIND_NWC_RESPONSE: u16 = 23195;
+/// NIP-04 encrypted wallet notification
+pub const KIND_NWC_NOTIFICATION: u16 = 23196;
+/// NIP-44 encrypted wallet notification
+pub const KIND_NWC_NOTIFICATION_NIP44: u16 = 23197;
+
+/// URI scheme of NWC connection strings
+const URI_SCHEME: &str = "nostr+walletconnect";
+/// How long to wait for a wallet response
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Parsed `nostr+walletconnect://<wallet pubkey>?relay=...&secret=...` URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NwcUri {
+    /// Public key of the wallet service
+    pub wallet_pubkey: PublicKey,
+    /// Relays the wallet service listens on
+    pub relays: Vec<String>,
+    /// Secret key the wallet authorized for this connection
+    pub secret: SecretKey,
+    /// Lightning address of the wallet, if shared
+    pub lud16: Option<String>,
+}
+
+impl FromStr for NwcUri {
+    type Err = anyhow::Error;
+
+    fn from_str(uri: &str) -> Result<Self> {
+        let url = url::Url::parse(uri.trim()).map_err(|e| anyhow!("Invalid NWC URI: {}", e))?;
+        if url.scheme() != URI_SCHEME {
+            return Err(anyhow!("Invalid NWC URI scheme '{}'", url.scheme()));
+        }
+        let wallet = url.host_str().ok_or_else(|| anyhow!("NWC URI is missing the wallet public key"))?;
+        let wallet_pubkey =
+            PublicKey::from_hex(wallet).map_err(|e| anyhow!("Invalid NWC wallet public key: {}", e))?;
+
+        let mut relays = Vec::new();
+        let mut secret = None;
+        let mut lud16 = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "relay" => relays.push(value.into_owned()),
+                "secret" => {
+                    secret = Some(SecretKey::from_hex(&value).map_err(|e| anyhow!("Invalid NWC secret: {}", e))?)
+                }
+                "lud16" => lud16 = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+
+        if relays.is_empty() {
+            return Err(anyhow!("NWC URI has no relay"));
+        }
+        Ok(Self {
+            wallet_pubkey,
+            relays,
+            secret: secret.ok_or_else(|| anyhow!("NWC URI is missing the secret"))?,
+            lud16,
+        })
+    }
+}
+
+/// Payload encryption negotiated with the wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encryption {
+    Nip04,
+    Nip44,
+}
+
+impl Encryption {
+    /// Encryption advertised in the wallet info event; wallets without the tag only speak NIP-04
+    fn from_info(info: Option<&Event>) -> Self {
+        let nip44 = info.is_some_and(|event| {
+            event.tags.iter().any(|tag| match tag.as_slice() {
+                [kind, schemes, ..] => kind == "encryption" && schemes.split_whitespace().any(|s| s == "nip44_v2"),
+                _ => false,
+            })
+        });
+        if nip44 { Encryption::Nip44 } else { Encryption::Nip04 }
+    }
+
+    /// Encryption of a ciphertext: NIP-04 payloads carry an `?iv=` suffix
+    fn of(ciphertext: &str) -> Self {
+        if ciphertext.contains("?iv=") { Encryption::Nip04 } else { Encryption::Nip44 }
+    }
+
+    fn encrypt(self, keys: &Keys, peer: &PublicKey, plaintext: &str) -> Result<String, payment::Error> {
+        let ciphertext = match self {
+            Encryption::Nip04 => nostr::nips::nip04::encrypt(keys.secret_key(), peer, plaintext).map_err(|e| e.to_string()),
+            Encryption::Nip44 => nostr::nips::nip44::encrypt(keys.secret_key(), peer, plaintext, Default::default())
+                .map_err(|e| e.to_string()),
+        };
+        ciphertext.map_err(|e| payment::Error::Custom(format!("Failed to encrypt NWC payload: {}", e)))
+    }
+
+    fn decrypt(keys: &Keys, peer: &PublicKey, ciphertext: &str) -> Result<String, payment::Error> {
+        let plaintext = match Self::of(ciphertext) {
+            Encryption::Nip04 => nostr::nips::nip04::decrypt(keys.secret_key(), peer, ciphertext).map_err(|e| e.to_string()),
+            Encryption::Nip44 => nostr::nips::nip44::decrypt(keys.secret_key(), peer, ciphertext).map_err(|e| e.to_string()),
+        };
+        plaintext.map_err(|e| payment::Error::Custom(format!("Failed to decrypt NWC payload: {}", e)))
+    }
+}
+
+/// Whether the wallet info event advertises `notification`
+fn notifies(info: Option<&Event>, notification: &str) -> bool {
+    info.is_some_and(|event| {
+        event.tags.iter().any(|tag| match tag.as_slice() {
+            [kind, types, ..] => kind == "notifications" && types.split_whitespace().any(|t| t == notification),
+            _ => false,
+        })
+    })
+}
+
+/// Plaintext of a kind 23194 request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NwcRequest {
+    method: String,
+    params: Value,
+}
+
+/// Plaintext of a kind 23195 response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NwcResponse {
+    result_type: String,
+    #[serde(default)]
+    error: Option<NwcError>,
+    #[serde(default)]
+    result: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NwcError {
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+impl NwcResponse {
+    /// Deserialize the result, turning wallet errors into payment errors
+    fn into_result<T: serde::de::DeserializeOwned>(self) -> Result<T, payment::Error> {
+        if let Some(error) = self.error {
+            return Err(payment::Error::Custom(format!(
+                "NWC {} failed: {} ({})",
+                self.result_type, error.message, error.code
+            )));
+        }
+        Ok(serde_json::from_value(self.result.unwrap_or(Value::Null))?)
+    }
+
+    fn is_not_found(&self) -> bool {
+        self.error.as_ref().is_some_and(|error| error.code == "NOT_FOUND")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MakeInvoiceResult {
+    invoice: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PayInvoiceResult {
+    preimage: String,
+    #[serde(default)]
+    fees_paid: u64,
+}
+
+/// `lookup_invoice` result for an incoming or outgoing payment
+#[derive(Debug, Deserialize)]
+struct Transaction {
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    preimage: Option<String>,
+    #[serde(default)]
+    amount: u64,
+    #[serde(default)]
+    fees_paid: u64,
+    #[serde(default)]
+    settled_at: Option<u64>,
+}
+
+impl Transaction {
+    /// Older wallets omit `state` and only set `settled_at` once paid
+    fn state(&self) -> MeltQuoteState {
+        match self.state.as_deref() {
+            Some("settled") => MeltQuoteState::Paid,
+            Some("failed") | Some("expired") => MeltQuoteState::Failed,
+            Some(_) => MeltQuoteState::Pending,
+            None if self.settled_at.is_some() => MeltQuoteState::Paid,
+            None => MeltQuoteState::Pending,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BalanceResult {
+    balance: u64,
+}
+
+/// Plaintext of a kind 23196/23197 notification
+#[derive(Debug, Deserialize)]
+struct NwcNotification {
+    notification_type: String,
+    notification: Value,
+}
+
+/// Transaction of a `payment_received` notification
+#[derive(Debug, Deserialize)]
+struct ReceivedPayment {
+    payment_hash: String,
+    amount: u64,
+}
+
+/// Build the kind 23194 event for `method`, encrypted to the wallet
+fn request_event(
+    keys: &Keys,
+    wallet: &PublicKey,
+    encryption: Encryption,
+    method: &str,
+    params: Value,
+) -> Result<Event, payment::Error> {
+    let request = NwcRequest {
+        method: method.to_string(),
+        params,
+    };
+    let content = encryption.encrypt(keys, wallet, &serde_json::to_string(&request)?)?;
+
+    let mut builder = EventBuilder::new(Kind::from(KIND_NWC_REQUEST), content).tag(Tag::public_key(*wallet));
+    if encryption == Encryption::Nip44 {
+        builder = builder.tag(Tag::custom(TagKind::custom("encryption"), ["nip44_v2"]));
+    }
+    builder
+        .sign_with_keys(keys)
+        .map_err(|e| payment::Error::Custom(format!("Failed to sign NWC request: {}", e)))
+}
+
+/// Decrypt a kind 23195 event from `wallet` answering `request_id`, if it is one
+fn response_to(keys: &Keys, wallet: &PublicKey, request_id: &EventId, event: &Event) -> Option<NwcResponse> {
+    if event.kind != Kind::from(KIND_NWC_RESPONSE) || event.pubkey != *wallet {
+        return None;
+    }
+    let id_hex = request_id.to_hex();
+    let answers = event.tags.iter().any(|tag| match tag.as_slice() {
+        [kind, id, ..] => kind == "e" && *id == id_hex,
+        _ => false,
+    });
+    if !answers {
+        return None;
+    }
+
+    let plaintext = match Encryption::decrypt(keys, wallet, &event.content) {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            warn!("Dropping NWC response {}: {}", event.id, e);
+            return None;
+        }
+    };
+    match serde_json::from_str(&plaintext) {
+        Ok(response) => Some(response),
+        Err(e) => {
+            warn!("Dropping malformed NWC response {}: {}", event.id, e);
+            None
+        }
+    }
+}
+
+/// Decrypt a `payment_received` notification from `wallet` into the payment
+/// hash and msat amount, if `event` is one
+fn payment_received(keys: &Keys, wallet: &PublicKey, event: &Event) -> Option<([u8; 32], u64)> {
+    let is_notification =
+        event.kind == Kind::from(KIND_NWC_NOTIFICATION) || event.kind == Kind::from(KIND_NWC_NOTIFICATION_NIP44);
+    if !is_notification || event.pubkey != *wallet {
+        return None;
+    }
+
+    let plaintext = match Encryption::decrypt(keys, wallet, &event.content) {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            warn!("Dropping NWC notification {}: {}", event.id, e);
+            return None;
+        }
+    };
+    let notification: NwcNotification = match serde_json::from_str(&plaintext) {
+        Ok(notification) => notification,
+        Err(e) => {
+            warn!("Dropping malformed NWC notification {}: {}", event.id, e);
+            return None;
+        }
+    };
+    if notification.notification_type != "payment_received" {
+        return None;
+    }
+    let payment: ReceivedPayment = serde_json::from_value(notification.notification).ok()?;
+    let hash = hex::decode(&payment.payment_hash).ok()?.try_into().ok()?;
+    Some((hash, payment.amount))
+}
+
+/// Wallet reached over Nostr Wallet Connect
+#[derive(Clone)]
+pub struct Nwc {
+    client: Client,
+    keys: Keys,
+    wallet: PublicKey,
+    encryption: Encryption,
+    /// Wallet pushes `payment_received` notifications, so invoices are not polled
+    notifications: bool,
+    fee_reserve: FeeReserve,
+    /// Highest fee the wallet pays, as NIP-47 payments carry no fee limit
+    fee_cap: FeeCap,
+    invoices: InvoiceWatcher,
+}
+
+impl Nwc {
+    /// Connect to the wallet of the `nostr+walletconnect://` `uri`, which pays at most `fee_cap` in fees
+    pub async fn new(uri: &str, fee_reserve: FeeReserve, fee_cap: FeeCap) -> Result<Self> {
+        let NwcUri {
+            wallet_pubkey,
+            relays,
+            secret,
+            ..
+        } = NwcUri::from_str(uri)?;
+        let keys = Keys::new(secret);
+
+        let client = Client::builder().signer(keys.clone()).build();
+        for relay in &relays {
+            client
+                .add_relay(relay.as_str())
+                .await
+                .map_err(|e| anyhow!("Failed to add NWC relay '{}': {}", relay, e))?;
+        }
+        client.connect().await;
+        client.wait_for_connection(Duration::from_secs(5)).await;
+
+        let filter = Filter::new()
+            .kind(Kind::from(KIND_NWC_RESPONSE))
+            .author(wallet_pubkey)
+            .pubkey(keys.public_key())
+            .since(Timestamp::now());
+        client
+            .subscribe(filter, None)
+            .await
+            .map_err(|e| anyhow!("Failed to subscribe to NWC responses: {}", e))?;
+
+        let info_filter = Filter::new().kind(Kind::from(KIND_NWC_INFO)).author(wallet_pubkey);
+        let info = match client.fetch_events(info_filter, Duration::from_secs(10)).await {
+            Ok(events) => events.into_iter().max_by_key(|event| event.created_at),
+            Err(e) => {
+                warn!("Failed to fetch NWC wallet info, assuming NIP-04: {}", e);
+                None
+            }
+        };
+        let encryption = Encryption::from_info(info.as_ref());
+
+        let notifications = notifies(info.as_ref(), "payment_received");
+        if notifications {
+            let filter = Filter::new()
+                .kinds([Kind::from(KIND_NWC_NOTIFICATION), Kind::from(KIND_NWC_NOTIFICATION_NIP44)])
+                .author(wallet_pubkey)
+                .pubkey(keys.public_key())
+                .since(Timestamp::now());
+            client
+                .subscribe(filter, None)
+                .await
+                .map_err(|e| anyhow!("Failed to subscribe to NWC notifications: {}", e))?;
+        }
+
+        info!(
+            "Using NWC wallet {} ({:?}, {})",
+            wallet_pubkey,
+            encryption,
+            if notifications { "payment notifications" } else { "polling invoices" }
+        );
+        Ok(Self {
+            client,
+            keys,
+            wallet: wallet_pubkey,
+            encryption,
+            notifications,
+            fee_reserve,
+            fee_cap,
+            invoices: InvoiceWatcher::default(),
+        })
+    }
+
+    pub(crate) fn invoices(&self) -> &InvoiceWatcher {
+        &self.invoices
+    }
+
+    /// Send `method` to the wallet and wait for its response
+    async fn request(&self, method: &str, params: Value) -> Result<NwcResponse, payment::Error> {
+        let event = request_event(&self.keys, &self.wallet, self.encryption, method, params)?;
+
+        // Subscribe to notifications before publishing so the response cannot be missed.
+        let mut notifications = self.client.notifications();
+        self.client
+            .send_event(&event)
+            .await
+            .map_err(|e| payment::Error::Custom(format!("Failed to send NWC request: {}", e)))?;
+
+        let wait = async {
+            loop {
+                let response = match notifications.recv().await {
+                    Ok(RelayPoolNotification::Event { event: response, .. }) => response,
+                    Ok(RelayPoolNotification::Shutdown) | Err(RecvError::Closed) => {
+                        return Err(payment::Error::Custom("NWC relay pool shut down".to_string()));
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                };
+                if let Some(response) = response_to(&self.keys, &self.wallet, &event.id, &response) {
+                    return Ok(response);
+                }
+            }
+        };
+        tokio::time::timeout(REQUEST_TIMEOUT, wait)
+            .await
+            .map_err(|_| payment::Error::Custom(format!("NWC {} timed out", method)))?
+    }
+
+    /// Look up a transaction, `None` when the wallet does not know it
+    async fn lookup_invoice(&self, payment_hash: [u8; 32]) -> Result<Option<Transaction>, payment::Error> {
+        let response = self
+            .request("lookup_invoice", json!({ "payment_hash": hex::encode(payment_hash) }))
+            .await?;
+        if response.is_not_found() {
+            return Ok(None);
+        }
+        response.into_result().map(Some)
+    }
+
+    /// Spendable balance of the wallet in msat
+    pub async fn balance(&self) -> Result<u64, payment::Error> {
+        let balance: BalanceResult = self.request("get_balance", json!({})).await?.into_result()?;
+        Ok(balance.balance)
+    }
+}
+
+#[async_trait]
+impl InvoiceLookup for Nwc {
+    async fn paid_msat(&self, payment_hash: [u8; 32]) -> Result<Option<u64>, payment::Error> {
+        Ok(self
+            .lookup_invoice(payment_hash)
+            .await?
+            .filter(|transaction| transaction.state() == MeltQuoteState::Paid)
+            .map(|transaction| transaction.amount))
+    }
+}
+
+#[async_trait]
+impl HealthProbe for Nwc {
+    /// Any answer, even an error for an unsupported method, shows the wallet is reachable
+    async fn probe(&self) -> Result<(), payment::Error> {
+        self.request("get_info", json!({})).await.map(|_| ())
+    }
+}
+
+#[async_trait]
+impl MintPayment for Nwc {
+    type Err = payment::Error;
+
+    async fn get_settings(&self) -> Result<Value, Self::Err> {
+        Ok(bolt11_settings())
+    }
+
+    async fn create_incoming_payment_request(
+        &self,
+        unit: &CurrencyUnit,
+        options: IncomingPaymentOptions,
+    ) -> Result<CreateIncomingPaymentResponse, Self::Err> {
+        check_unit(unit)?;
+        let IncomingPaymentOptions::Bolt11(options) = options else {
+            return Err(payment::Error::UnsupportedPaymentOption);
+        };
+
+        let mut params = json!({
+            "amount": to_msat(options.amount, unit)?,
+            "description": options.description.unwrap_or_default(),
+        });
+        if let Some(expiry) = options.unix_expiry {
+            params["expiry"] = json!(expiry.saturating_sub(cdk::util::unix_time()));
+        }
+
+        let result: MakeInvoiceResult = self.request("make_invoice", params).await?.into_result()?;
+        let invoice = Bolt11Invoice::from_str(&result.invoice)
+            .map_err(|e| payment::Error::Custom(format!("NWC wallet returned an invalid invoice: {}", e)))?;
+        let hash = payment_hash(&invoice)?;
+        self.invoices.register(hash, invoice_expiry(&invoice));
+
+        Ok(CreateIncomingPaymentResponse {
+            request_lookup_id: PaymentIdentifier::PaymentHash(hash),
+            request: result.invoice,
+            expiry: invoice.expires_at().map(|expiry| expiry.as_secs()),
+        })
+    }
+
+    async fn get_payment_quote(
+        &self,
+        unit: &CurrencyUnit,
+        options: OutgoingPaymentOptions,
+    ) -> Result<PaymentQuoteResponse, Self::Err> {
+        self.fee_cap.cover(bolt11_quote(unit, options, &self.fee_reserve)?)
+    }
+
+    async fn make_payment(
+        &self,
+        unit: &CurrencyUnit,
+        options: OutgoingPaymentOptions,
+    ) -> Result<MakePaymentResponse, Self::Err> {
+        check_unit(unit)?;
+        let OutgoingPaymentOptions::Bolt11(options) = options else {
+            return Err(payment::Error::UnsupportedPaymentOption);
+        };
+
+        let hash = payment_hash(&options.bolt11)?;
+        let amount_msat = options
+            .bolt11
+            .amount_milli_satoshis()
+            .ok_or(payment::Error::UnsupportedPaymentOption)?;
+
+        // NIP-47 has no fee limit, so the reserve has to cover the wallet's highest fee
+        self.fee_cap.check(amount_msat, options.max_fee_amount, unit)?;
+        let result: PayInvoiceResult = self
+            .request("pay_invoice", json!({ "invoice": options.bolt11.to_string() }))
+            .await?
+            .into_result()?;
+        debug!("Paid {} with NWC ({} msat fees)", hex::encode(hash), result.fees_paid);
+
+        // Only a wallet breaking its fee policy gets here. The payment already went
+        // out, so failing the melt would also hand back the proofs
+        if let Some(max_fee) = options.max_fee_amount {
+            let max_fee_msat = to_msat(max_fee, unit)?;
+            if result.fees_paid > max_fee_msat {
+                error!(
+                    "NWC wallet paid {} msat fees for {}, above the {} msat limit; the mint covers the difference",
+                    result.fees_paid,
+                    hex::encode(hash),
+                    max_fee_msat
+                );
+            }
+        }
+
+        Ok(MakePaymentResponse {
+            payment_lookup_id: PaymentIdentifier::PaymentHash(hash),
+            payment_proof: Some(result.preimage),
+            status: MeltQuoteState::Paid,
+            total_spent: Amount::from(amount_msat + result.fees_paid),
+            unit: CurrencyUnit::Msat,
+        })
+    }
+
+    async fn wait_any_incoming_payment(&self) -> Result<PaymentStream, Self::Err> {
+        if self.notifications {
+            let keys = self.keys.clone();
+            let wallet = self.wallet;
+            let received = futures::stream::unfold(self.client.notifications(), move |mut notifications| {
+                let keys = keys.clone();
+                async move {
+                    loop {
+                        match notifications.recv().await {
+                            Ok(RelayPoolNotification::Event { event, .. }) => {
+                                if let Some(payment) = payment_received(&keys, &wallet, &event) {
+                                    return Some((payment, notifications));
+                                }
+                            }
+                            Ok(RelayPoolNotification::Shutdown) | Err(RecvError::Closed) => return None,
+                            Ok(_) => {}
+                            // Missed payments are still found when the quote is checked
+                            Err(RecvError::Lagged(skipped)) => {
+                                warn!("NWC notification listener lagged, skipped {} notifications", skipped)
+                            }
+                        }
+                    }
+                }
+            });
+            return Ok(self.invoices.push_stream(received));
+        }
+
+        Ok(self.invoices.poll(self))
+    }
+
+    fn is_wait_invoice_active(&self) -> bool {
+        self.invoices.is_active()
+    }
+
+    fn cancel_wait_invoice(&self) {
+        self.invoices.cancel()
+    }
+
+    async fn check_incoming_payment_status(
+        &self,
+        payment_identifier: &PaymentIdentifier,
+    ) -> Result<Vec<WaitPaymentResponse>, Self::Err> {
+        self.incoming_status(payment_identifier).await
+    }
+
+    async fn check_outgoing_payment(
+        &self,
+        payment_identifier: &PaymentIdentifier,
+    ) -> Result<MakePaymentResponse, Self::Err> {
+        let hash = lookup_hash(payment_identifier)?;
+        let Some(transaction) = self.lookup_invoice(hash).await? else {
+            return Ok(MakePaymentResponse {
+                payment_lookup_id: payment_identifier.clone(),
+                payment_proof: None,
+                status: MeltQuoteState::Unknown,
+                total_spent: Amount::ZERO,
+                unit: CurrencyUnit::Msat,
+            });
+        };
+
+        let status = transaction.state();
+        let total_spent = match status {
+            MeltQuoteState::Paid => transaction.amount + transaction.fees_paid,
+            _ => 0,
+        };
+
+        Ok(MakePaymentResponse {
+            payment_lookup_id: payment_identifier.clone(),
+            payment_proof: transaction.preimage.filter(|preimage| !preimage.is_empty()),
+            status,
+            total_spent: Amount::from(total_spent),
+            unit: CurrencyUnit::Msat,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use cdk_common::payment::Bolt11IncomingPaymentOptions;
+    use futures::StreamExt;
+
+    use crate::config::EmbeddedRelayConfig;
+    use crate::embedded_relay::EmbeddedRelay;
+    use crate::payment::testing::{assert_incoming_payment, fee_reserve, pay_options, serve};
+
+    /// Highest fee of the stand-in wallet: 1 sat plus 0.5%
+    const FEE_CAP: FeeCap = FeeCap { base_msat: 1_000, ppm: 5_000 };
+
+    /// Decrypt a request the way the wallet service does
+    fn wallet_reads(wallet: &Keys, event: &Event) -> NwcRequest {
+        let plaintext = Encryption::decrypt(wallet, &event.pubkey, &event.content).unwrap();
+        serde_json::from_str(&plaintext).unwrap()
+    }
+
+    /// Answer `request` from the wallet with `response`
+    fn wallet_replies(wallet: &Keys, request: &Event, encryption: Encryption, response: Value) -> Event {
+        let content = encryption.encrypt(wallet, &request.pubkey, &response.to_string()).unwrap();
+        EventBuilder::new(Kind::from(KIND_NWC_RESPONSE), content)
+            .tag(Tag::public_key(request.pubkey))
+            .tag(Tag::event(request.id))
+            .sign_with_keys(wallet)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_uri() {
+        let wallet = Keys::generate();
+        let secret = Keys::generate();
+        let uri = format!(
+            "nostr+walletconnect://{}?relay=wss%3A%2F%2Frelay.example.com&relay=wss://relay2.example.com&secret={}&lud16=cat%40example.com",
+            wallet.public_key().to_hex(),
+            secret.secret_key().to_secret_hex()
+        );
+
+        let parsed = NwcUri::from_str(&uri).unwrap();
+        assert_eq!(parsed.wallet_pubkey, wallet.public_key());
+        assert_eq!(
+            parsed.relays,
+            vec!["wss://relay.example.com".to_string(), "wss://relay2.example.com".to_string()]
+        );
+        assert_eq!(&parsed.secret, secret.secret_key());
+        assert_eq!(parsed.lud16.as_deref(), Some("cat@example.com"));
+
+        assert!(NwcUri::from_str(&uri.replace("nostr+walletconnect", "https")).is_err());
+        assert!(NwcUri::from_str(uri.split("&secret").next().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_request_response_roundtrip() {
+        let wallet = Keys::generate();
+        let client = Keys::generate();
+
+        for encryption in [Encryption::Nip04, Encryption::Nip44] {
+            let request =
+                request_event(&client, &wallet.public_key(), encryption, "get_balance", json!({})).unwrap();
+            assert_eq!(request.kind, Kind::from(KIND_NWC_REQUEST));
+            assert_eq!(Encryption::of(&request.content), encryption);
+            let decoded = wallet_reads(&wallet, &request);
+            assert_eq!(decoded.method, "get_balance");
+
+            let reply = wallet_replies(
+                &wallet,
+                &request,
+                encryption,
+                json!({ "result_type": "get_balance", "result": { "balance": 21_000 } }),
+            );
+            let response = response_to(&client, &wallet.public_key(), &request.id, &reply).unwrap();
+            let balance: BalanceResult = response.into_result().unwrap();
+            assert_eq!(balance.balance, 21_000);
+
+            // Responses to other requests are ignored
+            assert!(response_to(&client, &wallet.public_key(), &reply.id, &reply).is_none());
+        }
+    }
+
+    #[test]
+    fn test_lookup_invoice_responses() {
+        let wallet = Keys::generate();
+        let client = Keys::generate();
+        let request = request_event(
+            &client,
+            &wallet.public_key(),
+            Encryption::Nip44,
+            "lookup_invoice",
+            json!({ "payment_hash": hex::encode([1u8; 32]) }),
+        )
+        .unwrap();
+
+        let reply = wallet_replies(
+            &wallet,
+            &request,
+            Encryption::Nip44,
+            json!({ "result_type": "lookup_invoice", "error": { "code": "NOT_FOUND", "message": "unknown invoice" } }),
+        );
+        let response = response_to(&client, &wallet.public_key(), &request.id, &reply).unwrap();
+        assert!(response.is_not_found());
+        assert!(response.into_result::<Transaction>().is_err());
+
+        let reply = wallet_replies(
+            &wallet,
+            &request,
+            Encryption::Nip44,
+            json!({
+                "result_type": "lookup_invoice",
+                "result": { "type": "outgoing", "amount": 20_000, "fees_paid": 1_000, "settled_at": 1_700_000_000 }
+            }),
+        );
+        let transaction: Transaction = response_to(&client, &wallet.public_key(), &request.id, &reply)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(transaction.state(), MeltQuoteState::Paid);
+        assert_eq!(transaction.amount + transaction.fees_paid, 21_000);
+    }
+
+    #[test]
+    fn test_payment_received_notification() {
+        let wallet = Keys::generate();
+        let client = Keys::generate();
+        let notification = |kind: u16, encryption: Encryption, notification_type: &str| {
+            let payload = json!({
+                "notification_type": notification_type,
+                "notification": { "type": "incoming", "payment_hash": hex::encode([5u8; 32]), "amount": 21_000 },
+            });
+            let content = encryption.encrypt(&wallet, &client.public_key(), &payload.to_string()).unwrap();
+            EventBuilder::new(Kind::from(kind), content)
+                .tag(Tag::public_key(client.public_key()))
+                .sign_with_keys(&wallet)
+                .unwrap()
+        };
+
+        for (kind, encryption) in [
+            (KIND_NWC_NOTIFICATION, Encryption::Nip04),
+            (KIND_NWC_NOTIFICATION_NIP44, Encryption::Nip44),
+        ] {
+            let event = notification(kind, encryption, "payment_received");
+            assert_eq!(payment_received(&client, &wallet.public_key(), &event), Some(([5u8; 32], 21_000)));
+        }
+
+        let sent = notification(KIND_NWC_NOTIFICATION_NIP44, Encryption::Nip44, "payment_sent");
+        assert!(payment_received(&client, &wallet.public_key(), &sent).is_none());
+        let event = notification(KIND_NWC_NOTIFICATION_NIP44, Encryption::Nip44, "payment_received");
+        assert!(payment_received(&client, &Keys::generate().public_key(), &event).is_none());
+    }
+
+    #[test]
+    fn test_encryption_from_info() {
+        let wallet = Keys::generate();
+        let info = |tags: Vec<Tag>| {
+            EventBuilder::new(Kind::from(KIND_NWC_INFO), "pay_invoice make_invoice lookup_invoice get_balance")
+                .tags(tags)
+                .sign_with_keys(&wallet)
+                .unwrap()
+        };
+
+        assert_eq!(Encryption::from_info(None), Encryption::Nip04);
+        assert_eq!(Encryption::from_info(Some(&info(vec![]))), Encryption::Nip04);
+        let nip44 = info(vec![Tag::custom(TagKind::custom("encryption"), ["nip44_v2 nip04"])]);
+        assert_eq!(Encryption::from_info(Some(&nip44)), Encryption::Nip44);
+
+        assert!(!notifies(Some(&nip44), "payment_received"));
+        let notifications = info(vec![Tag::custom(TagKind::custom("notifications"), ["payment_received payment_sent"])]);
+        assert!(notifies(Some(&notifications), "payment_received"));
+    }
+
+    /// Stand-in wallet service answering NIP-47 requests with a script, over an
+    /// embedded relay accepting any kind
+    struct Wallet {
+        keys: Keys,
+        client: Client,
+        /// Connection secret handed to the mint
+        secret: Keys,
+        uri: String,
+        /// Method and encryption of each request received
+        requests: Arc<Mutex<Vec<(String, Encryption)>>>,
+    }
+
+    impl Wallet {
+        /// Publish a wallet info event with `info_tags` and answer each request with `answer`
+        async fn start<F>(info_tags: Vec<Tag>, answer: F) -> Self
+        where
+            F: Fn(&NwcRequest) -> Value + Send + Sync + 'static,
+        {
+            let relay = EmbeddedRelay::accepting(
+                EmbeddedRelayConfig {
+                    enabled: true,
+                    ..Default::default()
+                },
+                |_| true,
+            );
+            let relay_url = format!("{}{}", serve(relay.router()).await.replacen("http", "ws", 1), relay.path());
+
+            let keys = Keys::generate();
+            let secret = Keys::generate();
+            let client = Client::builder().signer(keys.clone()).build();
+            client.add_relay(relay_url.as_str()).await.unwrap();
+            client.connect().await;
+            client.wait_for_connection(Duration::from_secs(5)).await;
+
+            let info = EventBuilder::new(Kind::from(KIND_NWC_INFO), "pay_invoice make_invoice lookup_invoice")
+                .tags(info_tags)
+                .sign_with_keys(&keys)
+                .unwrap();
+            client.send_event(&info).await.unwrap();
+
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let mut notifications = client.notifications();
+            let filter = Filter::new().kind(Kind::from(KIND_NWC_REQUEST)).pubkey(keys.public_key());
+            client.subscribe(filter, None).await.unwrap();
+            tokio::spawn({
+                let (keys, client, requests) = (keys.clone(), client.clone(), requests.clone());
+                async move {
+                    while let Ok(notification) = notifications.recv().await {
+                        let RelayPoolNotification::Event { event, .. } = notification else {
+                            continue;
+                        };
+                        if event.kind != Kind::from(KIND_NWC_REQUEST) {
+                            continue;
+                        }
+                        let encryption = Encryption::of(&event.content);
+                        let request = wallet_reads(&keys, &event);
+                        requests.lock().unwrap().push((request.method.clone(), encryption));
+                        let reply = wallet_replies(&keys, &event, encryption, answer(&request));
+                        let _ = client.send_event(&reply).await;
+                    }
+                }
+            });
+
+            let uri = format!(
+                "nostr+walletconnect://{}?relay={}&secret={}",
+                keys.public_key().to_hex(),
+                relay_url,
+                secret.secret_key().to_secret_hex()
+            );
+            Self {
+                keys,
+                client,
+                secret,
+                uri,
+                requests,
+            }
+        }
+
+        async fn connect(&self) -> Nwc {
+            Nwc::new(&self.uri, fee_reserve(), FEE_CAP).await.unwrap()
+        }
+
+        fn methods(&self) -> Vec<String> {
+            self.requests.lock().unwrap().iter().map(|(method, _)| method.clone()).collect()
+        }
+
+        /// Push a NIP-44 `payment_received` notification to the mint
+        async fn notify_received(&self, invoice: &Bolt11Invoice) {
+            let payload = json!({
+                "notification_type": "payment_received",
+                "notification": {
+                    "type": "incoming",
+                    "invoice": invoice.to_string(),
+                    "payment_hash": hex::encode(payment_hash(invoice).unwrap()),
+                    "amount": invoice.amount_milli_satoshis().unwrap(),
+                },
+            });
+            let content = Encryption::Nip44
+                .encrypt(&self.keys, &self.secret.public_key(), &payload.to_string())
+                .unwrap();
+            let event = EventBuilder::new(Kind::from(KIND_NWC_NOTIFICATION_NIP44), content)
+                .tag(Tag::public_key(self.secret.public_key()))
+                .sign_with_keys(&self.keys)
+                .unwrap();
+            self.client.send_event(&event).await.unwrap();
+        }
+    }
+
+    fn success(request: &NwcRequest, result: Value) -> Value {
+        json!({ "result_type": request.method, "result": result })
+    }
+
+    fn failure(request: &NwcRequest, code: &str, message: &str) -> Value {
+        json!({ "result_type": request.method, "error": { "code": code, "message": message } })
+    }
+
+    /// Script answering `make_invoice` with a fake invoice and `lookup_invoice`
+    /// with its state, settled once `paid` is set
+    fn invoicing(paid: Arc<Mutex<bool>>) -> impl Fn(&NwcRequest) -> Value + Send + Sync + 'static {
+        let issued: Arc<Mutex<Option<Bolt11Invoice>>> = Arc::default();
+        move |request| match request.method.as_str() {
+            "make_invoice" => {
+                let amount = request.params["amount"].as_u64().unwrap();
+                let description = request.params["description"].as_str().unwrap_or_default().to_string();
+                let invoice = cdk_fake_wallet::create_fake_invoice(amount, description);
+                *issued.lock().unwrap() = Some(invoice.clone());
+                success(request, json!({ "type": "incoming", "invoice": invoice.to_string() }))
+            }
+            "lookup_invoice" => {
+                let known = issued.lock().unwrap().clone().filter(|invoice| {
+                    request.params["payment_hash"] == hex::encode(payment_hash(invoice).unwrap())
+                });
+                match known {
+                    Some(invoice) if *paid.lock().unwrap() => success(
+                        request,
+                        json!({ "type": "incoming", "state": "settled", "amount": invoice.amount_milli_satoshis() }),
+                    ),
+                    Some(invoice) => success(
+                        request,
+                        json!({ "type": "incoming", "state": "pending", "amount": invoice.amount_milli_satoshis() }),
+                    ),
+                    None => failure(request, "NOT_FOUND", "invoice not found"),
+                }
+            }
+            _ => failure(request, "NOT_IMPLEMENTED", "unknown method"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nip04_wallet_is_polled() {
+        let paid = Arc::new(Mutex::new(false));
+        let wallet = Wallet::start(vec![], invoicing(paid.clone())).await;
+        let nwc = wallet.connect().await;
+
+        assert_incoming_payment(&nwc, CurrencyUnit::Sat, 21.into(), || *paid.lock().unwrap() = true).await;
+
+        // Without an encryption tag the wallet only speaks NIP-04
+        let requests = wallet.requests.lock().unwrap().clone();
+        assert!(requests.iter().all(|(_, encryption)| *encryption == Encryption::Nip04));
+        assert!(requests.iter().any(|(method, _)| method == "lookup_invoice"));
+    }
+
+    #[tokio::test]
+    async fn test_notifying_wallet_pushes_payments() {
+        let info_tags = vec![
+            Tag::custom(TagKind::custom("encryption"), ["nip44_v2 nip04"]),
+            Tag::custom(TagKind::custom("notifications"), ["payment_received payment_sent"]),
+        ];
+        let wallet = Wallet::start(info_tags, invoicing(Arc::default())).await;
+        let nwc = wallet.connect().await;
+
+        let options = IncomingPaymentOptions::Bolt11(Bolt11IncomingPaymentOptions {
+            description: Some("purrmint".to_string()),
+            amount: 21.into(),
+            unix_expiry: None,
+        });
+        let response = nwc.create_incoming_payment_request(&CurrencyUnit::Sat, options).await.unwrap();
+        let invoice = Bolt11Invoice::from_str(&response.request).unwrap();
+
+        let mut payments = nwc.wait_any_incoming_payment().await.unwrap();
+        wallet.notify_received(&invoice).await;
+        let payment = tokio::time::timeout(Duration::from_secs(10), payments.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payment.payment_identifier, response.request_lookup_id);
+        assert_eq!(payment.payment_amount, Amount::from(21_000));
+
+        nwc.cancel_wait_invoice();
+        assert!(payments.next().await.is_none());
+
+        // Payments are pushed, so the wallet is never asked about the invoice
+        assert_eq!(wallet.methods(), vec!["make_invoice".to_string()]);
+        assert_eq!(wallet.requests.lock().unwrap()[0].1, Encryption::Nip44);
+    }
+
+    #[tokio::test]
+    async fn test_make_payment() {
+        let wallet = Wallet::start(vec![], |request| match request.method.as_str() {
+            "pay_invoice" => success(request, json!({ "preimage": hex::encode([7u8; 32]), "fees_paid": 1_000 })),
+            _ => failure(request, "NOT_IMPLEMENTED", "unknown method"),
+        })
+        .await;
+        let nwc = wallet.connect().await;
+        let invoice = cdk_fake_wallet::create_fake_invoice(20_000, "melt".to_string());
+
+        // The reserve covers the wallet's highest fee of 1.1 sat
+        let quote = nwc.get_payment_quote(&CurrencyUnit::Sat, pay_options(&invoice)).await.unwrap();
+        assert_eq!(quote.fee, Amount::from(2));
+
+        let payment = nwc.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await.unwrap();
+        assert_eq!(payment.status, MeltQuoteState::Paid);
+        assert_eq!(payment.total_spent, Amount::from(21_000));
+        assert_eq!(payment.payment_proof, Some(hex::encode([7u8; 32])));
+
+        // NIP-47 takes no fee limit: a 10 sat limit below the 11 sat fee cap is refused
+        // without asking the wallet
+        let invoice = cdk_fake_wallet::create_fake_invoice(2_000_000, "melt".to_string());
+        assert!(nwc.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await.is_err());
+        assert_eq!(wallet.methods(), vec!["pay_invoice".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_wallet_error_fails_payment() {
+        let wallet = Wallet::start(vec![], |request| failure(request, "INSUFFICIENT_BALANCE", "not enough sats")).await;
+        let nwc = wallet.connect().await;
+        let invoice = cdk_fake_wallet::create_fake_invoice(20_000, "melt".to_string());
+
+        let payment = nwc.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await;
+        assert!(matches!(payment, Err(payment::Error::Custom(message)) if message.contains("INSUFFICIENT_BALANCE")));
+    }
+
+    #[tokio::test]
+    async fn test_check_outgoing_payment() {
+        let wallet = Wallet::start(vec![], |request| {
+            if request.params["payment_hash"] != hex::encode([9u8; 32]) {
+                return failure(request, "NOT_FOUND", "payment not found");
+            }
+            // Older wallets leave out `state` and only set `settled_at`
+            success(
+                request,
+                json!({
+                    "type": "outgoing",
+                    "preimage": hex::encode([7u8; 32]),
+                    "amount": 20_000,
+                    "fees_paid": 1_000,
+                    "settled_at": 1_700_000_000,
+                }),
+            )
+        })
+        .await;
+        let nwc = wallet.connect().await;
+
+        let paid = nwc.check_outgoing_payment(&PaymentIdentifier::PaymentHash([9; 32])).await.unwrap();
+        assert_eq!(paid.status, MeltQuoteState::Paid);
+        assert_eq!(paid.total_spent, Amount::from(21_000));
+        assert_eq!(paid.payment_proof, Some(hex::encode([7u8; 32])));
+
+        let unknown = nwc.check_outgoing_payment(&PaymentIdentifier::PaymentHash([3; 32])).await.unwrap();
+        assert_eq!(unknown.status, MeltQuoteState::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_balance() {
+        let wallet = Wallet::start(vec![], |request| match request.method.as_str() {
+            "get_balance" => success(request, json!({ "balance": 21_000 })),
+            _ => failure(request, "NOT_IMPLEMENTED", "unknown method"),
+        })
+        .await;
+        let nwc = wallet.connect().await;
+
+        assert_eq!(nwc.balance().await.unwrap(), 21_000);
+        assert_eq!(wallet.methods(), vec!["get_balance".to_string()]);
+
+        let wallet = Wallet::start(vec![], |request| failure(request, "RESTRICTED", "balance is private")).await;
+        let nwc = wallet.connect().await;
+        assert!(nwc.balance().await.is_err());
+    }
+}
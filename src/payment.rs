@@ -0,0 +1,513 @@
+//! Shared plumbing of the in-crate Lightning backends
+//! Fee reserves, amount conversion, BOLT11 quotes and polling of pending
+//! incoming payments
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cdk::lightning_invoice::Bolt11Invoice;
+use cdk::types::FeeReserve;
+use cdk_common::amount::to_unit;
+use cdk_common::nuts::{CurrencyUnit, MeltQuoteState};
+use cdk_common::payment::{
+    self, OutgoingPaymentOptions, PaymentIdentifier, PaymentQuoteResponse, WaitPaymentResponse,
+};
+use cdk_common::Amount;
+use futures::{Stream, StreamExt};
+use serde_json::{Value, json};
+use tokio::sync::watch;
+use tracing::warn;
+
+/// How often pending incoming payments are looked up
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Stream of settled incoming payments handed to the mint
+pub(crate) type PaymentStream = Pin<Box<dyn Stream<Item = WaitPaymentResponse> + Send>>;
+
+/// Backend settings reported to the mint: BOLT11 priced in msat, no MPP or amountless invoices
+pub(crate) fn bolt11_settings() -> Value {
+    json!({
+        "mpp": false,
+        "unit": CurrencyUnit::Msat,
+        "invoice_description": true,
+        "amountless": false,
+        "bolt12": false,
+    })
+}
+
+/// Reject units other than sat and msat
+pub(crate) fn check_unit(unit: &CurrencyUnit) -> Result<(), payment::Error> {
+    match unit {
+        CurrencyUnit::Sat | CurrencyUnit::Msat => Ok(()),
+        _ => Err(payment::Error::UnsupportedUnit),
+    }
+}
+
+/// Convert an msat amount to `unit`
+pub(crate) fn from_msat(amount_msat: u64, unit: &CurrencyUnit) -> Result<Amount, payment::Error> {
+    Ok(to_unit(amount_msat, &CurrencyUnit::Msat, unit)?)
+}
+
+/// Convert an amount in `unit` to msat
+pub(crate) fn to_msat(amount: Amount, unit: &CurrencyUnit) -> Result<u64, payment::Error> {
+    Ok(to_unit(amount, unit, &CurrencyUnit::Msat)?.into())
+}
+
+/// Fee reserve of a payment: the larger of the percentage and the minimum reserve
+pub(crate) fn fee_for(amount: Amount, fee_reserve: &FeeReserve) -> Amount {
+    let percent = (u64::from(amount) as f64 * fee_reserve.percent_fee_reserve as f64).ceil() as u64;
+    Amount::from(percent).max(fee_reserve.min_fee_reserve)
+}
+
+/// Most a node may spend on routing fees: a base fee plus a proportional fee
+///
+/// For nodes that take no per-payment fee limit, quotes reserve at least this
+/// much and payments whose limit is lower are refused before they go out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeCap {
+    pub base_msat: u64,
+    /// Proportional fee in parts per million
+    pub ppm: u64,
+}
+
+impl FeeCap {
+    /// Highest fee for paying `amount_msat`
+    pub(crate) fn fee_msat(&self, amount_msat: u64) -> u64 {
+        self.base_msat
+            .saturating_add(amount_msat.saturating_mul(self.ppm).div_ceil(1_000_000))
+    }
+
+    /// Raise the fee reserve of `quote` to the highest fee
+    pub(crate) fn cover(&self, mut quote: PaymentQuoteResponse) -> Result<PaymentQuoteResponse, payment::Error> {
+        let fee_msat = self.fee_msat(to_msat(quote.amount, &quote.unit)?);
+        // Round up so sat quotes do not reserve less than the fee
+        let fee = from_msat(fee_msat.div_ceil(1_000) * 1_000, &quote.unit)?;
+        quote.fee = quote.fee.max(fee);
+        Ok(quote)
+    }
+
+    /// Refuse paying `amount_msat` when `max_fee` (in `unit`) does not cover the highest fee
+    pub(crate) fn check(
+        &self,
+        amount_msat: u64,
+        max_fee: Option<Amount>,
+        unit: &CurrencyUnit,
+    ) -> Result<(), payment::Error> {
+        let Some(max_fee) = max_fee else {
+            return Ok(());
+        };
+        let max_fee_msat = to_msat(max_fee, unit)?;
+        let fee_msat = self.fee_msat(amount_msat);
+        if max_fee_msat < fee_msat {
+            return Err(payment::Error::Custom(format!(
+                "Fee limit of {} msat is below the {} msat the node may spend",
+                max_fee_msat, fee_msat
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Payment hash of a BOLT11 invoice
+pub(crate) fn payment_hash(invoice: &Bolt11Invoice) -> Result<[u8; 32], payment::Error> {
+    let hash = hex::decode(invoice.payment_hash().to_string())
+        .map_err(|e| payment::Error::Custom(format!("Invalid payment hash: {}", e)))?;
+    hash.try_into()
+        .map_err(|_| payment::Error::Custom("Invalid payment hash length".to_string()))
+}
+
+/// Payment hash of a lookup id, the only identifier the in-crate backends issue
+pub(crate) fn lookup_hash(identifier: &PaymentIdentifier) -> Result<[u8; 32], payment::Error> {
+    match identifier {
+        PaymentIdentifier::PaymentHash(hash) => Ok(*hash),
+        other => Err(payment::Error::Custom(format!("Unsupported payment identifier: {:?}", other))),
+    }
+}
+
+/// Quote for paying a BOLT11 invoice with an amount: the invoice amount and the fee reserve
+pub(crate) fn bolt11_quote(
+    unit: &CurrencyUnit,
+    options: OutgoingPaymentOptions,
+    fee_reserve: &FeeReserve,
+) -> Result<PaymentQuoteResponse, payment::Error> {
+    check_unit(unit)?;
+    let OutgoingPaymentOptions::Bolt11(options) = options else {
+        return Err(payment::Error::UnsupportedPaymentOption);
+    };
+
+    let amount_msat = options
+        .bolt11
+        .amount_milli_satoshis()
+        .ok_or(payment::Error::UnsupportedPaymentOption)?;
+    let amount = from_msat(amount_msat, unit)?;
+
+    Ok(PaymentQuoteResponse {
+        request_lookup_id: PaymentIdentifier::PaymentHash(payment_hash(&options.bolt11)?),
+        amount,
+        fee: fee_for(amount, fee_reserve),
+        unit: unit.clone(),
+        state: MeltQuoteState::Unpaid,
+        options: None,
+    })
+}
+
+/// Node that can tell whether an invoice it issued was paid
+#[async_trait]
+pub(crate) trait InvoiceLookup: Clone + Send + Sync + 'static {
+    /// Amount received for the invoice with `payment_hash`, once paid
+    async fn paid_msat(&self, payment_hash: [u8; 32]) -> Result<Option<u64>, payment::Error>;
+
+    /// Payment received for `identifier`, empty while it is unpaid
+    async fn incoming_status(&self, identifier: &PaymentIdentifier) -> Result<Vec<WaitPaymentResponse>, payment::Error> {
+        let hash = lookup_hash(identifier)?;
+        Ok(self
+            .paid_msat(hash)
+            .await?
+            .map(|amount_msat| settled(hash, amount_msat))
+            .into_iter()
+            .collect())
+    }
+}
+
+/// Settled payment notification for `payment_hash`
+pub(crate) fn settled(payment_hash: [u8; 32], amount_msat: u64) -> WaitPaymentResponse {
+    WaitPaymentResponse {
+        payment_identifier: PaymentIdentifier::PaymentHash(payment_hash),
+        payment_amount: Amount::from(amount_msat),
+        unit: CurrencyUnit::Msat,
+        payment_id: hex::encode(payment_hash),
+    }
+}
+
+/// Unix expiry of `invoice`
+pub(crate) fn invoice_expiry(invoice: &Bolt11Invoice) -> u64 {
+    invoice.expires_at().map_or(u64::MAX, |expiry| expiry.as_secs())
+}
+
+/// Tracks issued invoices until they are paid or expire
+///
+/// Backends register each invoice they create and hand [`InvoiceWatcher::stream`]
+/// a lookup returning the amount paid, or [`InvoiceWatcher::push_stream`] the
+/// payments their node reports.
+/// Clones share the set, so every clone of a backend streams the payments of
+/// invoices any of them issued. The set only lives in memory; the mint service
+/// registers its unpaid quotes again on start.
+#[derive(Clone)]
+pub(crate) struct InvoiceWatcher {
+    /// Payment hash -> unix expiry of the invoice
+    pending: Arc<Mutex<HashMap<[u8; 32], u64>>>,
+    active: Arc<AtomicBool>,
+    cancel: Arc<watch::Sender<bool>>,
+}
+
+impl Default for InvoiceWatcher {
+    fn default() -> Self {
+        Self {
+            pending: Arc::default(),
+            active: Arc::default(),
+            cancel: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl InvoiceWatcher {
+    /// Watch the invoice with `payment_hash` until it is paid or `expires_at` passes
+    pub(crate) fn register(&self, payment_hash: [u8; 32], expires_at: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(payment_hash, expires_at);
+        }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancel.send_replace(true);
+    }
+
+    /// Mark the watcher active and subscribe to cancellation
+    fn start(&self) -> watch::Receiver<bool> {
+        self.cancel.send_replace(false);
+        self.active.store(true, Ordering::SeqCst);
+        self.cancel.subscribe()
+    }
+
+    /// Stream settled payments of watched invoices among the `(payment hash,
+    /// msat amount)` pairs pushed by `received`
+    pub(crate) fn push_stream<S>(&self, received: S) -> PaymentStream
+    where
+        S: Stream<Item = ([u8; 32], u64)> + Send + 'static,
+    {
+        let state = (Box::pin(received), self.start(), self.pending.clone(), self.active.clone());
+        Box::pin(futures::stream::unfold(state, |state| async move {
+            let (mut received, mut cancel, pending, active) = state;
+            loop {
+                let (hash, amount_msat) = tokio::select! {
+                    changed = cancel.changed() => {
+                        if changed.is_err() || *cancel.borrow() {
+                            active.store(false, Ordering::SeqCst);
+                            return None;
+                        }
+                        continue;
+                    }
+                    payment = received.next() => match payment {
+                        Some(payment) => payment,
+                        None => {
+                            active.store(false, Ordering::SeqCst);
+                            return None;
+                        }
+                    },
+                };
+
+                let watched = match pending.lock() {
+                    Ok(mut pending) => {
+                        let watched = pending.remove(&hash).is_some();
+                        let now = cdk::util::unix_time();
+                        pending.retain(|_, expires_at| *expires_at >= now);
+                        watched
+                    }
+                    Err(_) => false,
+                };
+                if watched {
+                    return Some((settled(hash, amount_msat), (received, cancel, pending, active)));
+                }
+            }
+        }))
+    }
+
+    /// Stream settled payments, looking pending invoices up on `node`
+    pub(crate) fn poll<N: InvoiceLookup>(&self, node: &N) -> PaymentStream {
+        let node = node.clone();
+        self.stream(POLL_INTERVAL, move |hash| {
+            let node = node.clone();
+            async move { node.paid_msat(hash).await }
+        })
+    }
+
+    /// Stream settled payments, looking pending invoices up every `interval`
+    ///
+    /// `lookup` returns the msat amount received once an invoice is paid.
+    pub(crate) fn stream<F, Fut>(&self, interval: Duration, lookup: F) -> PaymentStream
+    where
+        F: Fn([u8; 32]) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<u64>, payment::Error>> + Send + 'static,
+    {
+        let state = (
+            VecDeque::new(),
+            self.start(),
+            self.pending.clone(),
+            self.active.clone(),
+            lookup,
+        );
+        Box::pin(futures::stream::unfold(state, move |state| async move {
+            let (mut ready, mut cancel, pending, active, lookup) = state;
+            loop {
+                if let Some(payment) = ready.pop_front() {
+                    return Some((payment, (ready, cancel, pending, active, lookup)));
+                }
+
+                tokio::select! {
+                    changed = cancel.changed() => {
+                        if changed.is_err() || *cancel.borrow() {
+                            active.store(false, Ordering::SeqCst);
+                            return None;
+                        }
+                    }
+                    _ = tokio::time::sleep(interval) => {}
+                }
+
+                let invoices: Vec<([u8; 32], u64)> = match pending.lock() {
+                    Ok(pending) => pending.iter().map(|(hash, expiry)| (*hash, *expiry)).collect(),
+                    Err(_) => Vec::new(),
+                };
+                let now = cdk::util::unix_time();
+                for (hash, expires_at) in invoices {
+                    // Looked up once more after expiry so a last-second payment is not missed
+                    let done = match lookup(hash).await {
+                        Ok(Some(amount_msat)) => {
+                            ready.push_back(settled(hash, amount_msat));
+                            true
+                        }
+                        Ok(None) => expires_at < now,
+                        Err(e) => {
+                            warn!("Failed to look up invoice {}: {}", hex::encode(hash), e);
+                            expires_at < now
+                        }
+                    };
+                    if done {
+                        if let Ok(mut pending) = pending.lock() {
+                            pending.remove(&hash);
+                        }
+                    }
+                }
+            }
+        }))
+    }
+}
+
+/// Helpers shared by the backend tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use std::str::FromStr;
+
+    use cdk_common::payment::{
+        Bolt11IncomingPaymentOptions, Bolt11OutgoingOptions, IncomingPaymentOptions, MintPayment,
+    };
+
+    /// Fee reserve of the test backends: 2%, at least 1 sat
+    pub(crate) fn fee_reserve() -> FeeReserve {
+        FeeReserve {
+            min_fee_reserve: 1.into(),
+            percent_fee_reserve: 0.02,
+        }
+    }
+
+    /// Serve `app` on a random local port and return its base URL
+    pub(crate) async fn serve(app: axum::Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        address
+    }
+
+    /// Options paying `invoice` with a 10 sat fee limit
+    pub(crate) fn pay_options(invoice: &Bolt11Invoice) -> OutgoingPaymentOptions {
+        OutgoingPaymentOptions::Bolt11(Box::new(Bolt11OutgoingOptions {
+            bolt11: invoice.clone(),
+            max_fee_amount: Some(10.into()),
+            timeout_secs: None,
+            melt_options: None,
+        }))
+    }
+
+    /// Issue an invoice for `amount` on `backend`, pay it with `settle` and
+    /// check the payment is streamed until the wait is cancelled
+    pub(crate) async fn assert_incoming_payment<B, F>(backend: &B, unit: CurrencyUnit, amount: Amount, settle: F)
+    where
+        B: MintPayment<Err = payment::Error>,
+        F: FnOnce(),
+    {
+        let options = IncomingPaymentOptions::Bolt11(Bolt11IncomingPaymentOptions {
+            description: Some("purrmint".to_string()),
+            amount,
+            unix_expiry: None,
+        });
+        let response = backend.create_incoming_payment_request(&unit, options).await.unwrap();
+        let amount_msat = to_msat(amount, &unit).unwrap();
+        let invoice = Bolt11Invoice::from_str(&response.request).unwrap();
+        assert_eq!(invoice.amount_milli_satoshis(), Some(amount_msat));
+
+        let status = backend.check_incoming_payment_status(&response.request_lookup_id).await.unwrap();
+        assert!(status.is_empty());
+
+        let mut payments = backend.wait_any_incoming_payment().await.unwrap();
+        settle();
+        let payment = payments.next().await.unwrap();
+        assert_eq!(payment.payment_identifier, response.request_lookup_id);
+        assert_eq!(payment.payment_amount, Amount::from(amount_msat));
+        assert_eq!(payment.unit, CurrencyUnit::Msat);
+
+        backend.cancel_wait_invoice();
+        assert!(payments.next().await.is_none());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_for() {
+        let fee_reserve = FeeReserve {
+            min_fee_reserve: 2.into(),
+            percent_fee_reserve: 0.01,
+        };
+        assert_eq!(fee_for(100.into(), &fee_reserve), Amount::from(2));
+        assert_eq!(fee_for(10_000.into(), &fee_reserve), Amount::from(100));
+        assert_eq!(fee_for(10_050.into(), &fee_reserve), Amount::from(101));
+    }
+
+    #[test]
+    fn test_bolt11_quote() {
+        let invoice = cdk_fake_wallet::create_fake_invoice(20_000, "melt".to_string());
+        let quote = bolt11_quote(&CurrencyUnit::Sat, testing::pay_options(&invoice), &testing::fee_reserve()).unwrap();
+        assert_eq!(quote.request_lookup_id, PaymentIdentifier::PaymentHash(payment_hash(&invoice).unwrap()));
+        assert_eq!(quote.amount, Amount::from(20));
+        assert_eq!(quote.fee, Amount::from(1));
+        assert_eq!(quote.state, MeltQuoteState::Unpaid);
+
+        assert!(bolt11_quote(&CurrencyUnit::Usd, testing::pay_options(&invoice), &testing::fee_reserve()).is_err());
+    }
+
+    #[test]
+    fn test_fee_cap() {
+        let cap = FeeCap { base_msat: 4_000, ppm: 4_000 };
+        assert_eq!(cap.fee_msat(20_000), 4_080);
+
+        // The reserve is raised to the highest fee, in whole sats
+        let invoice = cdk_fake_wallet::create_fake_invoice(20_000, "melt".to_string());
+        let quote = bolt11_quote(&CurrencyUnit::Sat, testing::pay_options(&invoice), &testing::fee_reserve()).unwrap();
+        assert_eq!(cap.cover(quote).unwrap().fee, Amount::from(5));
+
+        assert!(cap.check(20_000, Some(Amount::from(5)), &CurrencyUnit::Sat).is_ok());
+        assert!(cap.check(20_000, Some(Amount::from(4)), &CurrencyUnit::Sat).is_err());
+        assert!(cap.check(20_000, None, &CurrencyUnit::Sat).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_invoice_watcher_streams_paid_invoices() {
+        let watcher = InvoiceWatcher::default();
+        watcher.register([1; 32], u64::MAX);
+        watcher.register([2; 32], u64::MAX);
+
+        let mut stream = watcher.stream(Duration::from_millis(10), |hash| async move {
+            Ok((hash == [2; 32]).then_some(5_000))
+        });
+        let payment = stream.next().await.unwrap();
+        assert_eq!(payment.payment_identifier, PaymentIdentifier::PaymentHash([2; 32]));
+        assert_eq!(payment.payment_amount, Amount::from(5_000));
+        assert!(watcher.is_active());
+
+        watcher.cancel();
+        assert!(stream.next().await.is_none());
+        assert!(!watcher.is_active());
+    }
+
+    #[tokio::test]
+    async fn test_invoice_watcher_drops_expired_invoices() {
+        let watcher = InvoiceWatcher::default();
+        watcher.register([1; 32], 1);
+        watcher.register([2; 32], u64::MAX);
+
+        let mut stream = watcher.stream(Duration::from_millis(10), |_| async move { Ok(None) });
+        let _ = tokio::time::timeout(Duration::from_millis(50), stream.next()).await;
+        assert_eq!(watcher.pending.lock().unwrap().len(), 1);
+        watcher.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_invoice_watcher_streams_pushed_payments() {
+        let watcher = InvoiceWatcher::default();
+        watcher.register([2; 32], u64::MAX);
+
+        // Payments of invoices the mint did not issue are skipped
+        let received = futures::stream::iter(vec![([1; 32], 1_000), ([2; 32], 5_000)]);
+        let mut stream = watcher.push_stream(received);
+        let payment = stream.next().await.unwrap();
+        assert_eq!(payment.payment_identifier, PaymentIdentifier::PaymentHash([2; 32]));
+        assert_eq!(payment.payment_amount, Amount::from(5_000));
+        assert!(watcher.pending.lock().unwrap().is_empty());
+
+        assert!(stream.next().await.is_none());
+        assert!(!watcher.is_active());
+    }
+}
@@ -0,0 +1,592 @@
+//! Phoenixd Lightning backend
+//! Talks to the phoenixd HTTP API, authenticated with its `http-password`
+
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use cdk::lightning_invoice::Bolt11Invoice;
+use cdk::types::FeeReserve;
+use cdk_common::nuts::{CurrencyUnit, MeltQuoteState};
+use cdk_common::payment::{
+    self, CreateIncomingPaymentResponse, IncomingPaymentOptions, MakePaymentResponse, MintPayment,
+    OutgoingPaymentOptions, PaymentIdentifier, PaymentQuoteResponse, WaitPaymentResponse,
+};
+use cdk_common::Amount;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::payment::{
+    FeeCap, InvoiceLookup, InvoiceWatcher, PaymentStream, bolt11_quote, bolt11_settings, check_unit,
+    invoice_expiry, lookup_hash, payment_hash, to_msat,
+};
+use crate::routing::HealthProbe;
+
+/// Trampoline fee phoenixd pays on every payment: 4 sat plus 0.4%
+const TRAMPOLINE_FEE: FeeCap = FeeCap { base_msat: 4_000, ppm: 4_000 };
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateInvoiceResponse {
+    serialized: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IncomingPayment {
+    is_paid: bool,
+    #[serde(default)]
+    received_sat: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PayInvoiceResponse {
+    recipient_amount_sat: u64,
+    routing_fee_sat: u64,
+    payment_preimage: String,
+}
+
+/// Body of a `/payinvoice` that failed, which phoenixd answers with HTTP 200
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PaymentFailed {
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PayInvoiceResult {
+    Sent(PayInvoiceResponse),
+    Failed(PaymentFailed),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OutgoingPayment {
+    is_paid: bool,
+    #[serde(default)]
+    preimage: Option<String>,
+    /// Amount sent in sat
+    #[serde(default)]
+    sent: u64,
+    /// Routing fees in msat
+    #[serde(default)]
+    fees: u64,
+    #[serde(default)]
+    completed_at: Option<u64>,
+}
+
+/// Phoenixd node reached over its HTTP API
+#[derive(Clone)]
+pub struct Phoenixd {
+    client: reqwest::Client,
+    base_url: String,
+    password: String,
+    fee_reserve: FeeReserve,
+    invoices: InvoiceWatcher,
+}
+
+impl Phoenixd {
+    /// Connect to the HTTP API at `api_url` with the `http-password` of phoenix.conf
+    pub fn new(api_url: &str, password: &str, fee_reserve: FeeReserve) -> Result<Self> {
+        let base_url =
+            reqwest::Url::parse(api_url).map_err(|e| anyhow!("Invalid phoenixd API URL '{}': {}", api_url, e))?;
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| anyhow!("Failed to build HTTP client: {}", e))?;
+
+        info!("Using phoenixd at {}", base_url);
+        Ok(Self {
+            client,
+            base_url: base_url.as_str().trim_end_matches('/').to_string(),
+            password: password.to_string(),
+            fee_reserve,
+            invoices: InvoiceWatcher::default(),
+        })
+    }
+
+    pub(crate) fn invoices(&self) -> &InvoiceWatcher {
+        &self.invoices
+    }
+
+    async fn request<T>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        form: Option<&[(&str, String)]>,
+    ) -> Result<T, payment::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path))
+            .basic_auth("", Some(&self.password));
+        if let Some(form) = form {
+            request = request.form(form);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| payment::Error::Custom(format!("phoenixd request failed: {}", e)))?;
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(payment::Error::Custom(format!("phoenixd returned {}: {}", status, text)));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[async_trait]
+impl InvoiceLookup for Phoenixd {
+    async fn paid_msat(&self, payment_hash: [u8; 32]) -> Result<Option<u64>, payment::Error> {
+        let payment: IncomingPayment = self
+            .request(
+                reqwest::Method::GET,
+                &format!("/payments/incoming/{}", hex::encode(payment_hash)),
+                None,
+            )
+            .await?;
+        Ok(payment.is_paid.then(|| payment.received_sat * 1_000))
+    }
+}
+
+#[async_trait]
+impl HealthProbe for Phoenixd {
+    async fn probe(&self) -> Result<(), payment::Error> {
+        self.request::<Value>(reqwest::Method::GET, "/getinfo", None).await.map(|_| ())
+    }
+}
+
+/// Phoenixd prices invoices in whole sats
+fn to_sat(amount: Amount, unit: &CurrencyUnit) -> Result<u64, payment::Error> {
+    let amount_msat = to_msat(amount, unit)?;
+    if amount_msat % 1_000 != 0 {
+        return Err(payment::Error::Custom(format!(
+            "phoenixd only supports whole sat amounts, got {} msat",
+            amount_msat
+        )));
+    }
+    Ok(amount_msat / 1_000)
+}
+
+#[async_trait]
+impl MintPayment for Phoenixd {
+    type Err = payment::Error;
+
+    async fn get_settings(&self) -> Result<Value, Self::Err> {
+        Ok(bolt11_settings())
+    }
+
+    async fn create_incoming_payment_request(
+        &self,
+        unit: &CurrencyUnit,
+        options: IncomingPaymentOptions,
+    ) -> Result<CreateIncomingPaymentResponse, Self::Err> {
+        check_unit(unit)?;
+        let IncomingPaymentOptions::Bolt11(options) = options else {
+            return Err(payment::Error::UnsupportedPaymentOption);
+        };
+
+        let mut form = vec![
+            ("amountSat", to_sat(options.amount, unit)?.to_string()),
+            ("description", options.description.unwrap_or_default()),
+        ];
+        if let Some(expiry) = options.unix_expiry {
+            let now = cdk::util::unix_time();
+            form.push(("expirySeconds", expiry.saturating_sub(now).to_string()));
+        }
+
+        let response: CreateInvoiceResponse = self
+            .request(reqwest::Method::POST, "/createinvoice", Some(form.as_slice()))
+            .await?;
+        let invoice = Bolt11Invoice::from_str(&response.serialized)
+            .map_err(|e| payment::Error::Custom(format!("phoenixd returned an invalid invoice: {}", e)))?;
+        let hash = payment_hash(&invoice)?;
+        self.invoices.register(hash, invoice_expiry(&invoice));
+
+        Ok(CreateIncomingPaymentResponse {
+            request_lookup_id: PaymentIdentifier::PaymentHash(hash),
+            request: response.serialized,
+            expiry: invoice.expires_at().map(|expiry| expiry.as_secs()),
+        })
+    }
+
+    async fn get_payment_quote(
+        &self,
+        unit: &CurrencyUnit,
+        options: OutgoingPaymentOptions,
+    ) -> Result<PaymentQuoteResponse, Self::Err> {
+        TRAMPOLINE_FEE.cover(bolt11_quote(unit, options, &self.fee_reserve)?)
+    }
+
+    async fn make_payment(
+        &self,
+        unit: &CurrencyUnit,
+        options: OutgoingPaymentOptions,
+    ) -> Result<MakePaymentResponse, Self::Err> {
+        check_unit(unit)?;
+        let OutgoingPaymentOptions::Bolt11(options) = options else {
+            return Err(payment::Error::UnsupportedPaymentOption);
+        };
+
+        // Phoenixd takes no per-payment fee limit, so refuse limits below its trampoline fee
+        let amount_msat = options
+            .bolt11
+            .amount_milli_satoshis()
+            .ok_or(payment::Error::UnsupportedPaymentOption)?;
+        TRAMPOLINE_FEE.check(amount_msat, options.max_fee_amount, unit)?;
+        let hash = payment_hash(&options.bolt11)?;
+        let form = [("invoice", options.bolt11.to_string())];
+        let response = match self
+            .request(reqwest::Method::POST, "/payinvoice", Some(&form[..]))
+            .await?
+        {
+            PayInvoiceResult::Sent(response) => response,
+            PayInvoiceResult::Failed(failed) => {
+                warn!("phoenixd failed to pay {}: {}", hex::encode(hash), failed.reason);
+                return Ok(MakePaymentResponse {
+                    payment_lookup_id: PaymentIdentifier::PaymentHash(hash),
+                    payment_proof: None,
+                    status: MeltQuoteState::Failed,
+                    total_spent: Amount::ZERO,
+                    unit: CurrencyUnit::Msat,
+                });
+            }
+        };
+
+        let total_msat = (response.recipient_amount_sat + response.routing_fee_sat) * 1_000;
+        debug!("Paid {} with phoenixd ({} msat)", hex::encode(hash), total_msat);
+
+        Ok(MakePaymentResponse {
+            payment_lookup_id: PaymentIdentifier::PaymentHash(hash),
+            payment_proof: Some(response.payment_preimage),
+            status: MeltQuoteState::Paid,
+            total_spent: Amount::from(total_msat),
+            unit: CurrencyUnit::Msat,
+        })
+    }
+
+    async fn wait_any_incoming_payment(&self) -> Result<PaymentStream, Self::Err> {
+        Ok(self.invoices.poll(self))
+    }
+
+    fn is_wait_invoice_active(&self) -> bool {
+        self.invoices.is_active()
+    }
+
+    fn cancel_wait_invoice(&self) {
+        self.invoices.cancel()
+    }
+
+    async fn check_incoming_payment_status(
+        &self,
+        payment_identifier: &PaymentIdentifier,
+    ) -> Result<Vec<WaitPaymentResponse>, Self::Err> {
+        self.incoming_status(payment_identifier).await
+    }
+
+    async fn check_outgoing_payment(
+        &self,
+        payment_identifier: &PaymentIdentifier,
+    ) -> Result<MakePaymentResponse, Self::Err> {
+        let hash = lookup_hash(payment_identifier)?;
+        let payment: Option<OutgoingPayment> = self
+            .request(
+                reqwest::Method::GET,
+                &format!("/payments/outgoingbyhash/{}", hex::encode(hash)),
+                None,
+            )
+            .await?;
+
+        let Some(payment) = payment else {
+            return Ok(MakePaymentResponse {
+                payment_lookup_id: payment_identifier.clone(),
+                payment_proof: None,
+                status: MeltQuoteState::Unknown,
+                total_spent: Amount::ZERO,
+                unit: CurrencyUnit::Msat,
+            });
+        };
+
+        // Completed payments that are not paid have failed
+        let status = match (payment.is_paid, payment.completed_at) {
+            (true, _) => MeltQuoteState::Paid,
+            (false, Some(_)) => MeltQuoteState::Failed,
+            (false, None) => MeltQuoteState::Pending,
+        };
+        let total_spent = match status {
+            MeltQuoteState::Paid => payment.sent * 1_000 + payment.fees,
+            _ => 0,
+        };
+
+        Ok(MakePaymentResponse {
+            payment_lookup_id: payment_identifier.clone(),
+            payment_proof: payment.preimage.filter(|preimage| !preimage.is_empty()),
+            status,
+            total_spent: Amount::from(total_spent),
+            unit: CurrencyUnit::Msat,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::{Form, Path as UrlPath, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use cdk_common::payment::Bolt11IncomingPaymentOptions;
+    use serde_json::json;
+
+    use crate::payment::testing::{assert_incoming_payment, fee_reserve, pay_options, serve};
+
+    const PASSWORD: &str = "phoenix-secret";
+
+    /// Invoice amount phoenixd cannot find a route for
+    const UNROUTABLE_MSAT: u64 = 30_000;
+
+    /// Stand-in for the phoenixd HTTP API with a single invoice
+    #[derive(Clone, Default)]
+    struct Phoenix {
+        invoice: Arc<Mutex<Option<Bolt11Invoice>>>,
+        paid: Arc<Mutex<bool>>,
+        /// Form fields of the last `/createinvoice`
+        created: Arc<Mutex<HashMap<String, String>>>,
+    }
+
+    /// Phoenixd checks basic auth with an empty user name
+    fn check_password(headers: &HeaderMap) -> Result<(), StatusCode> {
+        let expected = format!("Basic {}", BASE64.encode(format!(":{}", PASSWORD)));
+        match headers.get("authorization") {
+            Some(value) if value.as_bytes() == expected.as_bytes() => Ok(()),
+            _ => Err(StatusCode::UNAUTHORIZED),
+        }
+    }
+
+    async fn create_invoice(
+        State(phoenix): State<Phoenix>,
+        headers: HeaderMap,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Result<Json<Value>, StatusCode> {
+        check_password(&headers)?;
+        let amount_sat: u64 = form["amountSat"].parse().unwrap();
+        let invoice = cdk_fake_wallet::create_fake_invoice(amount_sat * 1_000, form["description"].clone());
+        *phoenix.invoice.lock().unwrap() = Some(invoice.clone());
+        *phoenix.created.lock().unwrap() = form;
+        Ok(Json(json!({
+            "amountSat": amount_sat,
+            "paymentHash": hex::encode(payment_hash(&invoice).unwrap()),
+            "serialized": invoice.to_string(),
+        })))
+    }
+
+    async fn incoming_payment(
+        State(phoenix): State<Phoenix>,
+        headers: HeaderMap,
+        UrlPath(hash): UrlPath<String>,
+    ) -> Result<Json<Value>, StatusCode> {
+        check_password(&headers)?;
+        let invoice = phoenix.invoice.lock().unwrap().clone().ok_or(StatusCode::NOT_FOUND)?;
+        if hex::encode(payment_hash(&invoice).unwrap()) != hash {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        // Unpaid invoices carry no `receivedSat`
+        let mut payment = json!({
+            "paymentHash": hash,
+            "isPaid": false,
+            "fees": 0,
+            "createdAt": 1_700_000_000_000u64,
+        });
+        if *phoenix.paid.lock().unwrap() {
+            payment["isPaid"] = json!(true);
+            payment["receivedSat"] = json!(invoice.amount_milli_satoshis().unwrap() / 1_000);
+        }
+        Ok(Json(payment))
+    }
+
+    /// Failed payments are answered with HTTP 200 and a `reason`
+    async fn pay_invoice(headers: HeaderMap, Form(form): Form<HashMap<String, String>>) -> Result<Json<Value>, StatusCode> {
+        check_password(&headers)?;
+        let invoice = Bolt11Invoice::from_str(&form["invoice"]).unwrap();
+        let payment_hash = hex::encode(payment_hash(&invoice).unwrap());
+        if invoice.amount_milli_satoshis() == Some(UNROUTABLE_MSAT) {
+            return Ok(Json(json!({
+                "paymentId": "8f8d4a5e-0000-0000-0000-000000000001",
+                "paymentHash": payment_hash,
+                "reason": "route not found",
+            })));
+        }
+        Ok(Json(json!({
+            "recipientAmountSat": invoice.amount_milli_satoshis().unwrap() / 1_000,
+            "routingFeeSat": 5,
+            "paymentId": "8f8d4a5e-0000-0000-0000-000000000000",
+            "paymentHash": payment_hash,
+            "paymentPreimage": hex::encode([7u8; 32]),
+        })))
+    }
+
+    /// Unknown payments are answered with HTTP 200 and `null`; `sent` is in
+    /// sat while `fees` is in msat
+    async fn outgoing_payment(headers: HeaderMap, UrlPath(hash): UrlPath<String>) -> Result<Json<Value>, StatusCode> {
+        check_password(&headers)?;
+        let payment = if hash == hex::encode([9u8; 32]) {
+            json!({
+                "paymentHash": hash,
+                "preimage": hex::encode([7u8; 32]),
+                "isPaid": true,
+                "sent": 21,
+                "fees": 4_084,
+                "completedAt": 1_700_000_000_000u64,
+            })
+        } else if hash == hex::encode([8u8; 32]) {
+            json!({ "paymentHash": hash, "isPaid": false, "sent": 0, "fees": 0, "completedAt": 1_700_000_000_000u64 })
+        } else if hash == hex::encode([6u8; 32]) {
+            json!({ "paymentHash": hash, "preimage": "", "isPaid": false, "sent": 0, "fees": 0 })
+        } else {
+            Value::Null
+        };
+        Ok(Json(payment))
+    }
+
+    async fn phoenix() -> (String, Phoenix) {
+        let state = Phoenix::default();
+        let app = Router::new()
+            .route("/createinvoice", post(create_invoice))
+            .route("/payments/incoming/{hash}", get(incoming_payment))
+            .route("/payinvoice", post(pay_invoice))
+            .route("/payments/outgoingbyhash/{hash}", get(outgoing_payment))
+            .with_state(state.clone());
+        (serve(app).await, state)
+    }
+
+    fn phoenixd(address: &str, password: &str) -> Phoenixd {
+        Phoenixd::new(address, password, fee_reserve()).unwrap()
+    }
+
+    fn invoice_options(amount: u64, unix_expiry: Option<u64>) -> IncomingPaymentOptions {
+        IncomingPaymentOptions::Bolt11(Bolt11IncomingPaymentOptions {
+            description: Some("purrmint".to_string()),
+            amount: amount.into(),
+            unix_expiry,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_incoming_payment_in_whole_sats() {
+        let (address, phoenix) = phoenix().await;
+        let phoenixd = phoenixd(&address, PASSWORD);
+        assert_incoming_payment(&phoenixd, CurrencyUnit::Msat, 21_000.into(), || {
+            *phoenix.paid.lock().unwrap() = true;
+        })
+        .await;
+        assert_eq!(phoenix.created.lock().unwrap()["amountSat"], "21");
+
+        // Invoices are priced in sats, so sub-sat amounts are refused before asking phoenixd
+        *phoenix.created.lock().unwrap() = HashMap::new();
+        let options = invoice_options(21_500, None);
+        assert!(phoenixd.create_incoming_payment_request(&CurrencyUnit::Msat, options).await.is_err());
+        assert!(phoenix.created.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invoice_expiry_is_relative() {
+        let (address, phoenix) = phoenix().await;
+        let phoenixd = phoenixd(&address, PASSWORD);
+        let expiry = cdk::util::unix_time() + 600;
+        phoenixd
+            .create_incoming_payment_request(&CurrencyUnit::Sat, invoice_options(21, Some(expiry)))
+            .await
+            .unwrap();
+        let seconds: u64 = phoenix.created.lock().unwrap()["expirySeconds"].parse().unwrap();
+        assert!((599..=600).contains(&seconds));
+    }
+
+    #[tokio::test]
+    async fn test_trampoline_fee() {
+        let (address, _) = phoenix().await;
+        let phoenixd = phoenixd(&address, PASSWORD);
+
+        // 4 sat + 0.4% of 20 sat exceeds the 2% reserve
+        let invoice = cdk_fake_wallet::create_fake_invoice(20_000, "melt".to_string());
+        let quote = phoenixd.get_payment_quote(&CurrencyUnit::Sat, pay_options(&invoice)).await.unwrap();
+        assert_eq!(quote.fee, Amount::from(5));
+
+        // The 10 sat limit does not cover the 12 sat fee of 2000 sat
+        let invoice = cdk_fake_wallet::create_fake_invoice(2_000_000, "melt".to_string());
+        assert!(phoenixd.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pay_invoice() {
+        let (address, _) = phoenix().await;
+        let phoenixd = phoenixd(&address, PASSWORD);
+        let invoice = cdk_fake_wallet::create_fake_invoice(20_000, "melt".to_string());
+
+        let payment = phoenixd.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await.unwrap();
+        assert_eq!(payment.status, MeltQuoteState::Paid);
+        assert_eq!(payment.total_spent, Amount::from(25_000));
+        assert_eq!(payment.payment_proof, Some(hex::encode([7u8; 32])));
+    }
+
+    #[tokio::test]
+    async fn test_failed_payment_answered_with_ok() {
+        let (address, _) = phoenix().await;
+        let phoenixd = phoenixd(&address, PASSWORD);
+        let invoice = cdk_fake_wallet::create_fake_invoice(UNROUTABLE_MSAT, "melt".to_string());
+
+        let payment = phoenixd.make_payment(&CurrencyUnit::Sat, pay_options(&invoice)).await.unwrap();
+        assert_eq!(payment.status, MeltQuoteState::Failed);
+        assert_eq!(payment.total_spent, Amount::ZERO);
+        assert_eq!(payment.payment_proof, None);
+        assert_eq!(
+            payment.payment_lookup_id,
+            PaymentIdentifier::PaymentHash(payment_hash(&invoice).unwrap())
+        );
+    }
+
+    async fn lookup(phoenixd: &Phoenixd, hash: [u8; 32]) -> Result<MakePaymentResponse, payment::Error> {
+        phoenixd.check_outgoing_payment(&PaymentIdentifier::PaymentHash(hash)).await
+    }
+
+    #[tokio::test]
+    async fn test_outgoing_payment_lookup() {
+        let (address, _) = phoenix().await;
+        let phoenixd = phoenixd(&address, PASSWORD);
+
+        // 21 sat sent plus 4084 msat of fees
+        let paid = lookup(&phoenixd, [9; 32]).await.unwrap();
+        assert_eq!(paid.status, MeltQuoteState::Paid);
+        assert_eq!(paid.total_spent, Amount::from(25_084));
+        assert_eq!(paid.payment_proof, Some(hex::encode([7u8; 32])));
+
+        // Completed without being paid means failed, not completed means in flight
+        assert_eq!(lookup(&phoenixd, [8; 32]).await.unwrap().status, MeltQuoteState::Failed);
+        let pending = lookup(&phoenixd, [6; 32]).await.unwrap();
+        assert_eq!(pending.status, MeltQuoteState::Pending);
+        assert_eq!(pending.payment_proof, None);
+
+        assert_eq!(lookup(&phoenixd, [3; 32]).await.unwrap().status, MeltQuoteState::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_password() {
+        let (address, _) = phoenix().await;
+        let phoenixd = phoenixd(&address, "wrong");
+        let status = phoenixd
+            .check_incoming_payment_status(&PaymentIdentifier::PaymentHash([1; 32]))
+            .await;
+        assert!(status.is_err());
+    }
+}
@@ -0,0 +1,469 @@
+//! Quote state push notifications for NIP-74 requesters
+//! Remembers which pubkey created each mint/melt quote and sends an
+//! unsolicited kind 27402 update once cdk reports the quote as paid or settled,
+//! the NIP-74 counterpart of NUT-17 WebSocket subscriptions
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use async_trait::async_trait;
+use cdk::mint::Mint;
+use cdk::nuts::nut17::{Kind, NotificationPayload, Params, SubId};
+use cdk::nuts::{MeltQuoteState, MintQuoteState};
+use nostr_sdk::Client;
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::middleware::Layer;
+use crate::nip74_service::{
+    Envelope, Nip74Result, OperationMethod, OperationRequest, OperationResult, RequestContext, RequestHandler,
+    ResultStatus,
+};
+
+/// Hashtag marking unsolicited quote updates
+pub const QUOTE_UPDATE_TAG: &str = "quote_update";
+
+/// Longest time a quote is watched when it carries no expiry
+const DEFAULT_WATCH_SECS: u64 = 3600;
+
+/// Most quotes watched at once; requesters can still check unwatched quotes
+const MAX_WATCHES: usize = 1_000;
+/// Most quotes watched at once for one requester, whose oldest watch makes room
+const MAX_WATCHES_PER_AUTHOR: usize = 20;
+
+/// Requester of a watched quote
+#[derive(Debug, Clone)]
+struct Subscriber {
+    author: nostr::PublicKey,
+    event_id: nostr::EventId,
+    request_id: String,
+    envelope: Envelope,
+}
+
+/// Running watch task of a quote
+struct Watch {
+    author: nostr::PublicKey,
+    /// Start order, to find the oldest watch of an author
+    started: u64,
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// Watches by quote id, bounded by [`MAX_WATCHES`] and [`MAX_WATCHES_PER_AUTHOR`]
+#[derive(Default)]
+struct Watches {
+    tasks: HashMap<String, Watch>,
+    started: u64,
+}
+
+impl Watches {
+    /// Make room for watching `quote_id` for `author`; false when the notifier is full
+    fn admit(&mut self, quote_id: &str, author: &nostr::PublicKey) -> bool {
+        self.tasks.retain(|_, watch| !watch.task.is_finished());
+        if let Some(previous) = self.tasks.remove(quote_id) {
+            previous.task.abort();
+        }
+
+        let own = self.tasks.iter().filter(|(_, watch)| watch.author == *author);
+        if own.clone().count() >= MAX_WATCHES_PER_AUTHOR {
+            let oldest = own.min_by_key(|(_, watch)| watch.started).map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest.and_then(|id| self.tasks.remove(&id)) {
+                oldest.task.abort();
+            }
+            return true;
+        }
+        self.tasks.len() < MAX_WATCHES
+    }
+
+    fn insert(&mut self, quote_id: String, author: nostr::PublicKey, task: tokio::task::JoinHandle<()>) {
+        self.started += 1;
+        let watch = Watch {
+            author,
+            started: self.started,
+            task,
+        };
+        if let Some(previous) = self.tasks.insert(quote_id, watch) {
+            previous.task.abort();
+        }
+    }
+
+    fn clear(&mut self) {
+        for (_, watch) in self.tasks.drain() {
+            watch.task.abort();
+        }
+    }
+}
+
+struct Inner {
+    mint: Arc<Mint>,
+    keys: nostr::Keys,
+    client: RwLock<Option<Client>>,
+    watches: Mutex<Watches>,
+}
+
+/// Watches quotes created over NIP-74 and pushes their state changes
+#[derive(Clone)]
+pub struct QuoteNotifier {
+    inner: Arc<Inner>,
+}
+
+impl QuoteNotifier {
+    /// Create a notifier for quotes of `mint`, signing updates with `keys`
+    pub fn new(mint: Arc<Mint>, keys: nostr::Keys) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                mint,
+                keys,
+                client: RwLock::new(None),
+                watches: Mutex::new(Watches::default()),
+            }),
+        }
+    }
+
+    /// Layer recording the requester of every quote created through the wrapped handler
+    pub fn layer(&self) -> QuoteNotifyLayer {
+        QuoteNotifyLayer { notifier: self.clone() }
+    }
+
+    /// Publish updates through `client`
+    pub fn attach(&self, client: Client) {
+        if let Ok(mut current) = self.inner.client.write() {
+            *current = Some(client);
+        }
+    }
+
+    /// Stop publishing and drop all watched quotes
+    pub fn detach(&self) {
+        if let Ok(mut current) = self.inner.client.write() {
+            *current = None;
+        }
+        let mut watches = match self.inner.watches.lock() {
+            Ok(watches) => watches,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        watches.clear();
+    }
+
+    /// Number of quotes currently watched
+    pub fn watched_count(&self) -> usize {
+        match self.inner.watches.lock() {
+            Ok(mut watches) => {
+                watches.tasks.retain(|_, watch| !watch.task.is_finished());
+                watches.tasks.len()
+            }
+            Err(_) => 0,
+        }
+    }
+
+    /// Start watching the quote returned in `data` for `subscriber`
+    fn watch(&self, kind: Kind, data: &Value, subscriber: Subscriber) {
+        let Some(quote_id) = data.get("quote").and_then(Value::as_str).map(str::to_string) else {
+            return;
+        };
+
+        let now = nostr::Timestamp::now().as_u64();
+        let watch_secs = data
+            .get("expiry")
+            .and_then(Value::as_u64)
+            .map(|expiry| expiry.saturating_sub(now))
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_WATCH_SECS)
+            .min(DEFAULT_WATCH_SECS * 24);
+
+        let mut watches = match self.inner.watches.lock() {
+            Ok(watches) => watches,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if !watches.admit(&quote_id, &subscriber.author) {
+            debug!("Not watching quote {}: {} quotes are watched already", quote_id, MAX_WATCHES);
+            return;
+        }
+
+        let author = subscriber.author;
+        let task = tokio::spawn(Self::run_watch(
+            self.inner.clone(),
+            kind,
+            quote_id.clone(),
+            subscriber,
+            Duration::from_secs(watch_secs),
+        ));
+        watches.insert(quote_id, author, task);
+    }
+
+    async fn run_watch(inner: Arc<Inner>, kind: Kind, quote_id: String, subscriber: Subscriber, ttl: Duration) {
+        let params = Params {
+            kind,
+            filters: vec![quote_id.clone()],
+            id: Arc::new(SubId::from(format!("nip74-{}", quote_id))),
+        };
+        let mut subscription = match inner.mint.pubsub_manager().try_subscribe(params).await {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                warn!("Failed to watch quote {}: {}", quote_id, e);
+                return;
+            }
+        };
+        debug!("Watching quote {} for {}", quote_id, subscriber.author);
+
+        let deadline = tokio::time::sleep(ttl);
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                _ = &mut deadline => {
+                    debug!("Stopped watching quote {}: expired", quote_id);
+                    break;
+                }
+                payload = subscription.recv() => {
+                    let Some(payload) = payload else {
+                        break;
+                    };
+                    if let Some(data) = settled_state(&payload) {
+                        Self::publish(&inner, &subscriber, data).await;
+                        // Offers are reusable, so every payment is pushed until the watch expires
+                        if !matches!(kind, Kind::Bolt12MintQuote) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn publish(inner: &Inner, subscriber: &Subscriber, data: Value) {
+        let client = match inner.client.read() {
+            Ok(client) => client.clone(),
+            Err(_) => None,
+        };
+        let Some(client) = client else {
+            return;
+        };
+
+        let event = match update_event(&inner.keys, subscriber, data).await {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Failed to build quote update for {}: {}", subscriber.request_id, e);
+                return;
+            }
+        };
+
+        match client.send_event(&event).await {
+            Ok(_) => info!("Pushed quote update for request {} to {}", subscriber.request_id, subscriber.author),
+            Err(e) => warn!("Failed to push quote update for {}: {}", subscriber.request_id, e),
+        }
+    }
+}
+
+/// Unsolicited kind 27402 reply to the quote request of `subscriber`, tagged
+/// with [`QUOTE_UPDATE_TAG`]
+async fn update_event(keys: &nostr::Keys, subscriber: &Subscriber, data: Value) -> Nip74Result<nostr::Event> {
+    OperationResult::success(subscriber.request_id.clone(), data)
+        .to_envelope_with_signer(
+            keys,
+            subscriber.envelope,
+            &subscriber.author,
+            &subscriber.event_id,
+            Some(vec![nostr::Tag::hashtag(QUOTE_UPDATE_TAG)]),
+        )
+        .await
+}
+
+/// Quote payload to push, once the quote reached a state worth notifying
+fn settled_state<T>(payload: &NotificationPayload<T>) -> Option<Value>
+where
+    T: serde::Serialize,
+{
+    match payload {
+        NotificationPayload::MintQuoteBolt11Response(quote)
+            if matches!(quote.state, MintQuoteState::Paid | MintQuoteState::Issued) =>
+        {
+            serde_json::to_value(quote).ok()
+        }
+        // Offers have no state: notify while a payment is waiting to be minted
+        NotificationPayload::MintQuoteBolt12Response(quote) if quote.amount_paid > quote.amount_issued => {
+            serde_json::to_value(quote).ok()
+        }
+        NotificationPayload::MeltQuoteBolt11Response(quote)
+            if matches!(quote.state, MeltQuoteState::Paid | MeltQuoteState::Failed) =>
+        {
+            serde_json::to_value(quote).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Subscription kind for quotes created by `method`
+fn watch_kind(method: &OperationMethod) -> Option<Kind> {
+    match method {
+        OperationMethod::GetMintQuote => Some(Kind::Bolt11MintQuote),
+        OperationMethod::GetMintQuoteBolt12 => Some(Kind::Bolt12MintQuote),
+        // cdk publishes BOLT12 melt quotes in the BOLT11 melt quote format
+        OperationMethod::GetMeltQuote | OperationMethod::GetMeltQuoteBolt12 => Some(Kind::Bolt11MeltQuote),
+        _ => None,
+    }
+}
+
+/// Records quote requesters for a [`QuoteNotifier`]
+#[derive(Clone)]
+pub struct QuoteNotifyLayer {
+    notifier: QuoteNotifier,
+}
+
+impl Layer for QuoteNotifyLayer {
+    fn layer(&self, inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+        Arc::new(QuoteNotify { inner, notifier: self.notifier.clone() })
+    }
+}
+
+struct QuoteNotify {
+    inner: Arc<dyn RequestHandler>,
+    notifier: QuoteNotifier,
+}
+
+#[async_trait]
+impl RequestHandler for QuoteNotify {
+    async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        let kind = watch_kind(&req.method);
+        let request_id = req.request_id.clone();
+
+        let result = self.inner.handle(ctx, req).await?;
+        if let (Some(kind), ResultStatus::Success, Some(data)) = (kind, result.status, &result.data) {
+            self.notifier.watch(
+                kind,
+                data,
+                Subscriber {
+                    author: ctx.author,
+                    event_id: ctx.event_id,
+                    request_id,
+                    envelope: ctx.envelope,
+                },
+            );
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::nip74_service::KIND_OPERATION_RESULT;
+
+    fn mint_quote(state: &str) -> NotificationPayload<String> {
+        NotificationPayload::MintQuoteBolt11Response(
+            serde_json::from_value(json!({ "quote": "q1", "request": "lnbc1", "state": state })).unwrap(),
+        )
+    }
+
+    fn melt_quote(state: &str) -> NotificationPayload<String> {
+        NotificationPayload::MeltQuoteBolt11Response(
+            serde_json::from_value(json!({
+                "quote": "q2",
+                "amount": 21,
+                "fee_reserve": 1,
+                "state": state,
+                "expiry": 0,
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn offer(amount_paid: u64, amount_issued: u64) -> NotificationPayload<String> {
+        NotificationPayload::MintQuoteBolt12Response(
+            serde_json::from_value(json!({
+                "quote": "q3",
+                "request": "lno1",
+                "unit": "sat",
+                "pubkey": cdk::nuts::SecretKey::generate().public_key().to_hex(),
+                "amount_paid": amount_paid,
+                "amount_issued": amount_issued,
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_watch_kind() {
+        assert!(matches!(watch_kind(&OperationMethod::GetMintQuote), Some(Kind::Bolt11MintQuote)));
+        assert!(matches!(watch_kind(&OperationMethod::GetMintQuoteBolt12), Some(Kind::Bolt12MintQuote)));
+        assert!(matches!(watch_kind(&OperationMethod::GetMeltQuote), Some(Kind::Bolt11MeltQuote)));
+        assert!(matches!(watch_kind(&OperationMethod::GetMeltQuoteBolt12), Some(Kind::Bolt11MeltQuote)));
+        assert!(watch_kind(&OperationMethod::CheckMintQuote).is_none());
+        assert!(watch_kind(&OperationMethod::Swap).is_none());
+    }
+
+    #[test]
+    fn test_settled_state() {
+        assert!(settled_state(&mint_quote("UNPAID")).is_none());
+        let paid = settled_state(&mint_quote("PAID")).unwrap();
+        assert_eq!(paid["quote"], "q1");
+        assert_eq!(paid["state"], "PAID");
+        assert!(settled_state(&mint_quote("ISSUED")).is_some());
+
+        assert!(settled_state(&melt_quote("PENDING")).is_none());
+        assert!(settled_state(&melt_quote("PAID")).is_some());
+        assert_eq!(settled_state(&melt_quote("FAILED")).unwrap()["state"], "FAILED");
+
+        assert!(settled_state(&offer(0, 0)).is_none());
+        assert_eq!(settled_state(&offer(100, 0)).unwrap()["amount_paid"], 100);
+        assert!(settled_state(&offer(100, 100)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_event_answers_original_request() {
+        let mint_keys = nostr::Keys::generate();
+        let client_keys = nostr::Keys::generate();
+        let request_event_id = nostr::EventId::from_slice(&[4u8; 32]).unwrap();
+        let subscriber = Subscriber {
+            author: client_keys.public_key(),
+            event_id: request_event_id,
+            request_id: "req-7".to_string(),
+            envelope: Envelope::Direct,
+        };
+
+        let event = update_event(&mint_keys, &subscriber, json!({ "quote": "q1", "state": "PAID" }))
+            .await
+            .unwrap();
+        assert_eq!(event.kind, nostr::Kind::from(KIND_OPERATION_RESULT));
+        assert_eq!(event.tags.event_ids().next(), Some(&request_event_id));
+        assert!(event
+            .tags
+            .iter()
+            .any(|t| t.as_slice()[0] == "t" && t.as_slice()[1] == QUOTE_UPDATE_TAG));
+
+        let plaintext =
+            nostr::nips::nip44::decrypt(client_keys.secret_key(), &mint_keys.public_key(), &event.content).unwrap();
+        let update: OperationResult = serde_json::from_str(&plaintext).unwrap();
+        assert_eq!(update.request_id, "req-7");
+        assert_eq!(update.status, ResultStatus::Success);
+        assert_eq!(update.data.unwrap()["state"], "PAID");
+    }
+
+    #[tokio::test]
+    async fn test_watches_are_bounded() {
+        let mut watches = Watches::default();
+        let spawn = || tokio::spawn(std::future::pending::<()>());
+
+        // A busy requester gives up its oldest watch
+        let busy = nostr::Keys::generate().public_key();
+        for i in 0..MAX_WATCHES_PER_AUTHOR {
+            assert!(watches.admit(&format!("busy-{}", i), &busy));
+            watches.insert(format!("busy-{}", i), busy, spawn());
+        }
+        assert!(watches.admit("busy-new", &busy));
+        watches.insert("busy-new".to_string(), busy, spawn());
+        assert_eq!(watches.tasks.len(), MAX_WATCHES_PER_AUTHOR);
+        assert!(!watches.tasks.contains_key("busy-0"));
+        assert!(watches.tasks.contains_key("busy-1"));
+
+        // Once full, quotes of other requesters are not watched
+        while watches.tasks.len() < MAX_WATCHES {
+            let quote_id = format!("other-{}", watches.tasks.len());
+            watches.insert(quote_id, nostr::Keys::generate().public_key(), spawn());
+        }
+        assert!(!watches.admit("late", &nostr::Keys::generate().public_key()));
+        assert!(watches.admit("busy-late", &busy));
+
+        watches.clear();
+        assert!(watches.tasks.is_empty());
+    }
+}
@@ -0,0 +1,599 @@
+//! Per-author rate limiting for NIP-74 operations
+//! Token buckets per (author, method) and a daily mint amount cap, persisted
+//! so a restart or reconnect does not reset an author's budget
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use cdk::nuts::CurrencyUnit;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::{debug, info, warn};
+
+use crate::config::{RateLimit, RateLimitConfig};
+use crate::middleware::Layer;
+use crate::nip74_service::{
+    ErrorCode, Nip74Result, OperationMethod, OperationRequest, OperationResult, RequestContext, RequestHandler,
+    ResultError, ResultStatus,
+};
+use crate::payment::to_msat;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Minimum interval between writes of the limiter state in seconds
+const PERSIST_INTERVAL: u64 = 5;
+
+/// Outcome of a rate limit check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateDecision {
+    /// Request may proceed
+    Allowed,
+    /// Token bucket is empty; retry after the given number of seconds
+    Limited { retry_after: u64 },
+    /// Daily mint amount cap would be exceeded; `remaining` is in sat
+    QuotaExceeded { remaining: u64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Bucket {
+    tokens: f64,
+    updated_at: u64,
+}
+
+impl Bucket {
+    fn full(limit: &RateLimit, now: u64) -> Self {
+        Self { tokens: limit.capacity as f64, updated_at: now }
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: u64) {
+        let elapsed = now.saturating_sub(self.updated_at) as f64;
+        self.tokens = (self.tokens + elapsed / limit.refill_secs.max(1) as f64).min(limit.capacity as f64);
+        self.updated_at = now;
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AuthorState {
+    buckets: HashMap<OperationMethod, Bucket>,
+    /// UTC day (days since epoch) `minted_msat` refers to
+    day: u64,
+    /// Amount in msat metered on `day`
+    #[serde(default)]
+    minted_msat: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LimiterFile {
+    authors: HashMap<String, AuthorState>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    authors: HashMap<String, AuthorState>,
+    dirty: bool,
+    persisted_at: u64,
+}
+
+/// Per-author token bucket rate limiter
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    /// Unit of each keyset, to price the outputs of BOLT12 mints
+    keyset_units: HashMap<String, CurrencyUnit>,
+    path: Option<PathBuf>,
+    inner: Mutex<Inner>,
+}
+
+impl RateLimiter {
+    /// Create a limiter that only keeps state in memory
+    pub fn in_memory(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            keyset_units: HashMap::new(),
+            path: None,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Open (or create) a limiter whose state is persisted as JSON at `path`
+    pub fn open(path: impl AsRef<Path>, config: RateLimitConfig) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut inner = Inner::default();
+
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("Failed to read rate limiter state: {}", e))?;
+            match serde_json::from_str::<LimiterFile>(&content) {
+                Ok(file) => inner.authors = file.authors,
+                Err(e) => warn!("Ignoring corrupt rate limiter state {:?}: {}", path, e),
+            }
+        }
+
+        Ok(Self {
+            config,
+            keyset_units: HashMap::new(),
+            path: Some(path),
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Price outputs of the given keysets in their unit; outputs of unknown keysets count as sat
+    pub fn with_keyset_units(mut self, keysets: impl IntoIterator<Item = (String, CurrencyUnit)>) -> Self {
+        self.keyset_units = keysets.into_iter().collect();
+        self
+    }
+
+    /// Rate limit configuration
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Check and consume the budget of `author` for `method`.
+    /// `amount` is the amount in msat counted against the daily mint amount.
+    pub fn check(&self, author: &nostr::PublicKey, method: &OperationMethod, amount: u64) -> RateDecision {
+        let decision = self.check_at(author, method, amount, nostr::Timestamp::now().as_u64());
+        self.persist_if_due();
+        decision
+    }
+
+    fn check_at(&self, author: &nostr::PublicKey, method: &OperationMethod, amount: u64, now: u64) -> RateDecision {
+        if !self.config.enabled {
+            return RateDecision::Allowed;
+        }
+
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        inner.dirty = true;
+        let state = inner.authors.entry(author.to_hex()).or_default();
+
+        // Check the quota before spending a token so rejected quotes stay free
+        let today = now / SECONDS_PER_DAY;
+        if state.day != today {
+            state.day = today;
+            state.minted_msat = 0;
+        }
+        if let (true, Some(cap)) = (is_metered(method), self.config.daily_mint_amount) {
+            let cap_msat = cap.saturating_mul(1000);
+            if state.minted_msat.saturating_add(amount) > cap_msat {
+                return RateDecision::QuotaExceeded { remaining: cap_msat.saturating_sub(state.minted_msat) / 1000 };
+            }
+        }
+
+        if let Some(limit) = self.config.limit_for(method) {
+            let bucket = state
+                .buckets
+                .entry(method.clone())
+                .or_insert_with(|| Bucket::full(&limit, now));
+            bucket.refill(&limit, now);
+            if bucket.tokens < 1.0 {
+                let retry_after = ((1.0 - bucket.tokens) * limit.refill_secs.max(1) as f64).ceil() as u64;
+                return RateDecision::Limited { retry_after };
+            }
+            bucket.tokens -= 1.0;
+        }
+
+        if is_metered(method) {
+            state.minted_msat = state.minted_msat.saturating_add(amount);
+        }
+        RateDecision::Allowed
+    }
+
+    /// Give back quota (msat) of a metered request that failed
+    pub fn refund(&self, author: &nostr::PublicKey, amount: u64) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(state) = inner.authors.get_mut(&author.to_hex()) {
+            state.minted_msat = state.minted_msat.saturating_sub(amount);
+        }
+    }
+
+    /// Amount in msat of a metered request: the quote amount, or the outputs of a BOLT12 mint
+    fn mint_amount(&self, req: &OperationRequest) -> u64 {
+        let Some(data) = req.data.as_ref() else {
+            return 0;
+        };
+        match req.method {
+            OperationMethod::GetMintQuote => {
+                let amount = data.get("amount").and_then(Value::as_u64).unwrap_or(0);
+                let unit = data
+                    .get("unit")
+                    .and_then(Value::as_str)
+                    .and_then(|unit| CurrencyUnit::from_str(unit).ok())
+                    .unwrap_or(CurrencyUnit::Sat);
+                msat(amount, &unit)
+            }
+            OperationMethod::MintBolt12 => data
+                .get("outputs")
+                .and_then(Value::as_array)
+                .map(|outputs| {
+                    outputs
+                        .iter()
+                        .filter_map(|output| {
+                            let amount = output.get("amount").and_then(Value::as_u64)?;
+                            let unit = output
+                                .get("id")
+                                .and_then(Value::as_str)
+                                .and_then(|id| self.keyset_units.get(id))
+                                .unwrap_or(&CurrencyUnit::Sat);
+                            Some(msat(amount, unit))
+                        })
+                        .fold(0u64, u64::saturating_add)
+                })
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Write the limiter state to disk
+    pub fn flush(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let now = nostr::Timestamp::now().as_u64();
+        let file = {
+            let mut inner = match self.inner.lock() {
+                Ok(inner) => inner,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            Self::prune(&mut inner, &self.config, now);
+            inner.dirty = false;
+            inner.persisted_at = now;
+            LimiterFile { authors: inner.authors.clone() }
+        };
+
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(&file)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        debug!("Persisted rate limiter state for {} authors", file.authors.len());
+        Ok(())
+    }
+
+    fn persist_if_due(&self) {
+        let due = match self.inner.lock() {
+            Ok(inner) => {
+                inner.dirty && nostr::Timestamp::now().as_u64() >= inner.persisted_at + PERSIST_INTERVAL
+            }
+            Err(_) => false,
+        };
+        if due {
+            if let Err(e) = self.flush() {
+                warn!("Failed to persist rate limiter state: {}", e);
+            }
+        }
+    }
+
+    /// Drop authors whose buckets are full again and whose daily quota has reset
+    fn prune(inner: &mut Inner, config: &RateLimitConfig, now: u64) {
+        let today = now / SECONDS_PER_DAY;
+        inner.authors.retain(|_, state| {
+            let quota_active = state.day == today && state.minted_msat > 0;
+            let buckets_active = state.buckets.iter().any(|(method, bucket)| {
+                let refill_secs = config.limit_for(method).map(|l| l.refill_secs.max(1)).unwrap_or(1);
+                let capacity = config.limit_for(method).map(|l| l.capacity as f64).unwrap_or(0.0);
+                let refilled = bucket.tokens + now.saturating_sub(bucket.updated_at) as f64 / refill_secs as f64;
+                refilled < capacity
+            });
+            quota_active || buckets_active
+        });
+    }
+
+    /// Layer enforcing this limiter
+    pub fn layer(self: &Arc<Self>) -> RateLimitLayer {
+        RateLimitLayer { limiter: self.clone() }
+    }
+}
+
+impl Drop for RateLimiter {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Failed to persist rate limiter state: {}", e);
+        }
+    }
+}
+
+/// Methods counted against the daily mint amount
+///
+/// BOLT11 quotes are metered when created, as each can be paid once for its
+/// amount. A BOLT12 offer may be paid any number of times, with or without an
+/// amount, so its minting is metered instead; the mint handler rejects BOLT12
+/// quotes sent through `mint`, which would otherwise skip the meter.
+fn is_metered(method: &OperationMethod) -> bool {
+    matches!(method, OperationMethod::GetMintQuote | OperationMethod::MintBolt12)
+}
+
+/// Amount in msat of `amount` in `unit`; units without a conversion count as sat
+fn msat(amount: u64, unit: &CurrencyUnit) -> u64 {
+    to_msat(amount.into(), unit).unwrap_or_else(|_| amount.saturating_mul(1000))
+}
+
+/// Answers requests over an author's budget with `rate_limited`
+///
+/// Each sub-request of a batch is charged under its own method as well, and
+/// those over the budget are answered with `rate_limited` in their slot.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl Layer for RateLimitLayer {
+    fn layer(&self, inner: Arc<dyn RequestHandler>) -> Arc<dyn RequestHandler> {
+        Arc::new(Limit { inner, limiter: self.limiter.clone() })
+    }
+}
+
+struct Limit {
+    inner: Arc<dyn RequestHandler>,
+    limiter: Arc<RateLimiter>,
+}
+
+/// Reason a request over the budget is refused, or `None` when it may proceed
+fn refusal(method: &OperationMethod, decision: RateDecision) -> Option<String> {
+    match decision {
+        RateDecision::Allowed => None,
+        RateDecision::Limited { retry_after } => {
+            Some(format!("Too many {:?} requests, retry in {}s", method, retry_after))
+        }
+        RateDecision::QuotaExceeded { remaining } => {
+            Some(format!("Daily mint limit reached, {} remaining today", remaining))
+        }
+    }
+}
+
+impl Limit {
+    /// Charge the sub-requests of a batch and run the ones within budget as one batch
+    async fn handle_batch(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        // Malformed batches are rejected by the handler
+        let Some(Ok(requests)) = req.data.clone().map(serde_json::from_value::<Vec<OperationRequest>>) else {
+            return self.inner.handle(ctx, req).await;
+        };
+
+        // Slots of refused sub-requests hold their result; the others run
+        let mut slots = Vec::with_capacity(requests.len());
+        let mut allowed = Vec::new();
+        for request in requests {
+            // Methods that cannot be batched are refused by the handler without running
+            let decision = match request.method.is_batchable() {
+                true => self.limiter.check(&ctx.author, &request.method, self.limiter.mint_amount(&request)),
+                false => RateDecision::Allowed,
+            };
+            match refusal(&request.method, decision) {
+                Some(message) => {
+                    info!(
+                        "Rate limited {:?} request {} in batch {} from {}",
+                        request.method, request.request_id, req.request_id, ctx.author
+                    );
+                    slots.push(Some(OperationResult::failure(
+                        request.request_id,
+                        ResultError::new(ErrorCode::RateLimited, message),
+                    )));
+                }
+                None => {
+                    slots.push(None);
+                    allowed.push(request);
+                }
+            }
+        }
+        if allowed.len() == slots.len() {
+            return self.inner.handle(ctx, req).await;
+        }
+
+        let batch = OperationRequest { data: Some(json!(allowed)), ..req };
+        let mut result = self.inner.handle(ctx, batch).await?;
+        if result.status != ResultStatus::Success {
+            return Ok(result);
+        }
+        let mut answered = result
+            .data
+            .take()
+            .and_then(|data| serde_json::from_value::<Vec<OperationResult>>(data).ok())
+            .unwrap_or_default()
+            .into_iter();
+        let results: Vec<OperationResult> = slots.into_iter().filter_map(|slot| slot.or_else(|| answered.next())).collect();
+        result.data = Some(json!(results));
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl RequestHandler for Limit {
+    async fn handle(&self, ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+        let amount = self.limiter.mint_amount(&req);
+        let decision = self.limiter.check(&ctx.author, &req.method, amount);
+        let Some(message) = refusal(&req.method, decision) else {
+            if req.method == OperationMethod::Batch {
+                return self.handle_batch(ctx, req).await;
+            }
+            let result = self.inner.handle(ctx, req).await;
+            if amount > 0 && !matches!(&result, Ok(r) if r.status == ResultStatus::Success) {
+                self.limiter.refund(&ctx.author, amount);
+            }
+            return result;
+        };
+
+        info!("Rate limited {:?} request {} from {}", req.method, req.request_id, ctx.author);
+        Ok(OperationResult::failure(
+            req.request_id,
+            ResultError::new(ErrorCode::RateLimited, message),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: true,
+            default_limit: None,
+            methods: HashMap::from([(OperationMethod::GetMintQuote, RateLimit::new(2, 10))]),
+            daily_mint_amount: Some(1000),
+        }
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::in_memory(config());
+        let author = nostr::Keys::generate().public_key();
+        let method = OperationMethod::GetMintQuote;
+
+        assert_eq!(limiter.check_at(&author, &method, 1, 1000), RateDecision::Allowed);
+        assert_eq!(limiter.check_at(&author, &method, 1, 1000), RateDecision::Allowed);
+        assert_eq!(limiter.check_at(&author, &method, 1, 1001), RateDecision::Limited { retry_after: 9 });
+        assert_eq!(limiter.check_at(&author, &method, 1, 1011), RateDecision::Allowed);
+
+        // Other authors and unlimited methods are unaffected
+        let other = nostr::Keys::generate().public_key();
+        assert_eq!(limiter.check_at(&other, &method, 1, 1010), RateDecision::Allowed);
+        assert_eq!(limiter.check_at(&author, &OperationMethod::Swap, 0, 1010), RateDecision::Allowed);
+    }
+
+    #[test]
+    fn test_daily_mint_amount() {
+        let limiter = RateLimiter::in_memory(RateLimitConfig { methods: HashMap::new(), ..config() });
+        let author = nostr::Keys::generate().public_key();
+        let method = OperationMethod::GetMintQuote;
+        let day = SECONDS_PER_DAY * 100;
+
+        assert_eq!(limiter.check_at(&author, &method, 800_000, day), RateDecision::Allowed);
+        assert_eq!(
+            limiter.check_at(&author, &method, 300_000, day + 1),
+            RateDecision::QuotaExceeded { remaining: 200 }
+        );
+        limiter.refund(&author, 800_000);
+        assert_eq!(limiter.check_at(&author, &method, 300_000, day + 2), RateDecision::Allowed);
+
+        // Quota resets on the next day
+        assert_eq!(limiter.check_at(&author, &method, 1_000_000, day + SECONDS_PER_DAY), RateDecision::Allowed);
+    }
+
+    #[test]
+    fn test_mint_amount_in_msat() {
+        let limiter = RateLimiter::in_memory(config());
+        let quote = |unit: &str| {
+            OperationRequest::new(
+                OperationMethod::GetMintQuote,
+                Some(serde_json::json!({ "amount": 500, "unit": unit })),
+            )
+        };
+
+        // The cap is in sat whatever the unit of the quote
+        assert_eq!(limiter.mint_amount(&quote("sat")), 500_000);
+        assert_eq!(limiter.mint_amount(&quote("msat")), 500);
+    }
+
+    #[test]
+    fn test_bolt12_mints_are_metered() {
+        let limiter = RateLimiter::in_memory(RateLimitConfig { methods: HashMap::new(), ..config() })
+            .with_keyset_units([("00ffd48b8f5ecf80".to_string(), CurrencyUnit::Msat)]);
+        let author = nostr::Keys::generate().public_key();
+
+        // Offers are free to create, amountless or not
+        assert!(!is_metered(&OperationMethod::GetMintQuoteBolt12));
+
+        let mint = OperationRequest::new(
+            OperationMethod::MintBolt12,
+            Some(serde_json::json!({
+                "quote": "offer-1",
+                "outputs": [
+                    { "amount": 512, "id": "009a1f293253e41e", "B_": "02" },
+                    { "amount": 256, "id": "009a1f293253e41e", "B_": "02" },
+                ],
+            })),
+        );
+        assert_eq!(limiter.mint_amount(&mint), 768_000);
+        assert_eq!(limiter.check_at(&author, &mint.method, 768_000, 0), RateDecision::Allowed);
+        assert_eq!(
+            limiter.check_at(&author, &mint.method, 768_000, 1),
+            RateDecision::QuotaExceeded { remaining: 232 }
+        );
+
+        // Outputs of msat keysets are priced in msat
+        let mint = OperationRequest::new(
+            OperationMethod::MintBolt12,
+            Some(serde_json::json!({
+                "quote": "offer-2",
+                "outputs": [{ "amount": 4096, "id": "00ffd48b8f5ecf80", "B_": "02" }],
+            })),
+        );
+        assert_eq!(limiter.mint_amount(&mint), 4096);
+    }
+
+    /// Handler answering each sub-request of a batch with its method
+    struct Batches;
+
+    #[async_trait]
+    impl RequestHandler for Batches {
+        async fn handle(&self, _ctx: &RequestContext, req: OperationRequest) -> Nip74Result<OperationResult> {
+            let requests: Vec<OperationRequest> = serde_json::from_value(req.data.unwrap_or_default()).unwrap();
+            let results: Vec<OperationResult> = requests
+                .into_iter()
+                .map(|request| OperationResult::success(request.request_id, json!(request.method)))
+                .collect();
+            Ok(OperationResult::success(req.request_id, json!(results)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_sub_requests_are_charged() {
+        let limiter = Arc::new(RateLimiter::in_memory(RateLimitConfig {
+            methods: HashMap::from([(OperationMethod::CheckMintQuote, RateLimit::new(2, 60))]),
+            ..config()
+        }));
+        let handler = limiter.layer().layer(Arc::new(Batches));
+        let ctx = RequestContext {
+            author: nostr::Keys::generate().public_key(),
+            event_id: nostr::EventId::all_zeros(),
+            created_at: nostr::Timestamp::now(),
+            relay_url: None,
+            envelope: crate::nip74_service::Envelope::Direct,
+        };
+
+        let mut requests: Vec<OperationRequest> =
+            (0..3).map(|_| OperationRequest::new(OperationMethod::CheckMintQuote, None)).collect();
+        requests.push(OperationRequest::new(OperationMethod::Keysets, None));
+        let batch = OperationRequest::batch(requests.clone());
+        let result = handler.handle(&ctx, batch).await.unwrap();
+        assert_eq!(result.status, ResultStatus::Success);
+
+        // The third quote check is over its limit; the slots keep their order
+        let results: Vec<OperationResult> = serde_json::from_value(result.data.unwrap()).unwrap();
+        let statuses: Vec<ResultStatus> = results.iter().map(|result| result.status).collect();
+        assert_eq!(
+            statuses,
+            vec![ResultStatus::Success, ResultStatus::Success, ResultStatus::Error, ResultStatus::Success]
+        );
+        assert_eq!(results[2].request_id, requests[2].request_id);
+        assert_eq!(results[2].error.as_ref().unwrap().code, ErrorCode::RateLimited.as_str());
+        assert_eq!(results[3].data, Some(json!(OperationMethod::Keysets)));
+
+        // Quote checks stay limited outside batches too
+        let check = OperationRequest::new(OperationMethod::CheckMintQuote, None);
+        let result = handler.handle(&ctx, check).await.unwrap();
+        assert_eq!(result.error.unwrap().code, ErrorCode::RateLimited.as_str());
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("rate_limits.json");
+        let author = nostr::Keys::generate().public_key();
+        let method = OperationMethod::GetMintQuote;
+
+        let limiter = RateLimiter::open(&path, config()).unwrap();
+        assert_eq!(limiter.check(&author, &method, 1), RateDecision::Allowed);
+        assert_eq!(limiter.check(&author, &method, 1), RateDecision::Allowed);
+        drop(limiter);
+
+        let reopened = RateLimiter::open(&path, config()).unwrap();
+        assert!(matches!(reopened.check(&author, &method, 1), RateDecision::Limited { .. }));
+    }
+}
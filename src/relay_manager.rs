@@ -0,0 +1,296 @@
+//! Relay connection management for the NIP-74 runtime
+//! Connects to the configured relay set, tracks per-relay health and
+//! reconnects dropped relays with a capped exponential backoff
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use anyhow::{Result, anyhow};
+use nostr::{RelayUrl, Timestamp};
+use nostr_sdk::{Client, RelayOptions, RelayStatus};
+use serde::Serialize;
+use tokio::sync::Notify;
+use tracing::{info, warn, debug};
+
+use crate::config::RelayConfig;
+
+/// Health snapshot of a single relay
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayHealth {
+    pub url: String,
+    pub read: bool,
+    pub write: bool,
+    pub connected: bool,
+    pub status: String,
+    /// Consecutive failed reconnect attempts
+    pub failures: u32,
+    pub last_connected_at: Option<u64>,
+    pub last_checked_at: Option<u64>,
+    pub last_attempt_at: Option<u64>,
+    pub next_retry_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+impl RelayHealth {
+    fn new(url: &RelayUrl, read: bool, write: bool) -> Self {
+        Self {
+            url: url.to_string(),
+            read,
+            write,
+            connected: false,
+            status: "initialized".to_string(),
+            failures: 0,
+            last_connected_at: None,
+            last_checked_at: None,
+            last_attempt_at: None,
+            next_retry_at: None,
+            last_error: None,
+        }
+    }
+
+    /// Record an observed connection state at `now` (unix seconds).
+    /// Returns `true` when the relay is down and its reconnect attempt is due.
+    fn observe(&mut self, status: RelayStatus, now: u64, config: &RelayConfig) -> bool {
+        self.status = status.to_string();
+        self.last_checked_at = Some(now);
+
+        if status == RelayStatus::Connected {
+            if !self.connected {
+                self.last_connected_at = Some(now);
+            }
+            self.connected = true;
+            self.failures = 0;
+            self.next_retry_at = None;
+            self.last_error = None;
+            return false;
+        }
+
+        self.connected = false;
+        // A relay still connecting is given time before it counts as down
+        if !matches!(status, RelayStatus::Disconnected | RelayStatus::Terminated) {
+            return false;
+        }
+        match self.next_retry_at {
+            Some(next_retry_at) if now < next_retry_at => false,
+            _ => {
+                self.last_attempt_at = Some(now);
+                self.next_retry_at = Some(now + config.backoff_delay(self.failures).as_secs());
+                self.failures = self.failures.saturating_add(1);
+                true
+            }
+        }
+    }
+}
+
+/// Manages the relay connections of a NIP-74 client
+pub struct RelayManager {
+    client: Client,
+    config: RelayConfig,
+    health: Arc<RwLock<HashMap<RelayUrl, RelayHealth>>>,
+    shutdown: Arc<Notify>,
+    supervisor: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl RelayManager {
+    /// Create a new relay manager for the given client and relay configuration
+    pub fn new(client: Client, config: RelayConfig) -> Self {
+        Self {
+            client,
+            config,
+            health: Arc::new(RwLock::new(HashMap::new())),
+            shutdown: Arc::new(Notify::new()),
+            supervisor: None,
+        }
+    }
+
+    /// Get the relay configuration
+    pub fn config(&self) -> &RelayConfig {
+        &self.config
+    }
+
+    /// Add the configured relays to the client, connect and start the health supervisor
+    pub async fn connect(&mut self) -> Result<()> {
+        if self.config.read_relays().next().is_none() {
+            return Err(anyhow!("No read relays configured"));
+        }
+
+        for entry in &self.config.relays {
+            let url = RelayUrl::parse(&entry.url)
+                .map_err(|e| anyhow!("Invalid relay URL '{}': {}", entry.url, e))?;
+
+            if !entry.read && !entry.write {
+                warn!("Relay {} is neither read nor write, skipping", url);
+                continue;
+            }
+
+            // Reconnects are left to the supervisor so they follow the configured backoff
+            let opts = RelayOptions::new()
+                .read(entry.read)
+                .write(entry.write)
+                .reconnect(false);
+            self.client
+                .pool()
+                .add_relay(url.clone(), opts)
+                .await
+                .map_err(|e| anyhow!("Failed to add relay '{}': {}", url, e))?;
+
+            if let Ok(mut health) = self.health.write() {
+                health.insert(url.clone(), RelayHealth::new(&url, entry.read, entry.write));
+            }
+        }
+
+        self.client.connect().await;
+        info!("Connecting to {} relays", self.config.relays.len());
+
+        let supervisor = tokio::spawn(Self::supervise(
+            self.client.clone(),
+            self.config.clone(),
+            self.health.clone(),
+            self.shutdown.clone(),
+        ));
+        self.supervisor = Some(supervisor);
+        Ok(())
+    }
+
+    /// Stop the health supervisor
+    pub async fn disconnect(&mut self) {
+        self.shutdown.notify_waiters();
+        if let Some(supervisor) = self.supervisor.take() {
+            supervisor.abort();
+            let _ = supervisor.await;
+        }
+    }
+
+    /// Current health of every managed relay, sorted by URL
+    pub fn health(&self) -> Vec<RelayHealth> {
+        let mut relays: Vec<RelayHealth> = match self.health.read() {
+            Ok(health) => health.values().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        relays.sort_by(|a, b| a.url.cmp(&b.url));
+        relays
+    }
+
+    /// Number of currently connected relays
+    pub fn connected_count(&self) -> usize {
+        self.health().iter().filter(|r| r.connected).count()
+    }
+
+    async fn supervise(
+        client: Client,
+        config: RelayConfig,
+        health: Arc<RwLock<HashMap<RelayUrl, RelayHealth>>>,
+        shutdown: Arc<Notify>,
+    ) {
+        let interval = config.health_check_interval.max(1);
+        // Relays are still connecting right after `connect`, so wait before the first check
+        let mut wait = interval;
+
+        loop {
+            tokio::select! {
+                _ = shutdown.notified() => break,
+                _ = tokio::time::sleep(std::time::Duration::from_secs(wait)) => {}
+            }
+
+            let relays = client.relays().await;
+            let now = Timestamp::now().as_u64();
+
+            let mut to_reconnect = Vec::new();
+            if let Ok(mut health) = health.write() {
+                for (url, relay) in relays.iter() {
+                    if let Some(entry) = health.get_mut(url) {
+                        let was_connected = entry.connected;
+                        if entry.observe(relay.status(), now, &config) {
+                            to_reconnect.push((url.clone(), entry.failures));
+                        }
+                        if was_connected && !entry.connected {
+                            warn!("Relay {} disconnected", url);
+                        }
+                    }
+                }
+            }
+
+            for (url, failures) in to_reconnect {
+                debug!("Reconnecting relay {} (attempt {})", url, failures);
+                if let Err(e) = client.connect_relay(url.as_str()).await {
+                    warn!("Failed to reconnect relay {}: {}", url, e);
+                    if let Ok(mut health) = health.write() {
+                        if let Some(entry) = health.get_mut(&url) {
+                            entry.last_error = Some(e.to_string());
+                        }
+                    }
+                }
+            }
+
+            // Wake up for the next health check or the earliest due reconnect
+            wait = match health.read() {
+                Ok(health) => health
+                    .values()
+                    .filter_map(|entry| entry.next_retry_at)
+                    .map(|next_retry_at| next_retry_at.saturating_sub(now))
+                    .fold(interval, u64::min)
+                    .max(1),
+                Err(_) => interval,
+            };
+        }
+
+        debug!("Relay supervisor stopped");
+    }
+}
+
+impl Drop for RelayManager {
+    fn drop(&mut self) {
+        if let Some(supervisor) = self.supervisor.take() {
+            supervisor.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> RelayConfig {
+        RelayConfig {
+            relays: Vec::new(),
+            reconnect_initial_delay: 2,
+            reconnect_max_delay: 60,
+            health_check_interval: 1,
+        }
+    }
+
+    #[test]
+    fn test_relay_health_backoff() {
+        let config = test_config();
+        let url = RelayUrl::parse("wss://relay.example.com").unwrap();
+        let mut health = RelayHealth::new(&url, true, true);
+
+        // A relay still connecting is not retried
+        assert!(!health.observe(RelayStatus::Connecting, 99, &config));
+
+        // First observation while disconnected triggers an attempt
+        assert!(health.observe(RelayStatus::Disconnected, 100, &config));
+        assert_eq!(health.failures, 1);
+        assert_eq!(health.next_retry_at, Some(102));
+
+        // No attempt until the backoff delay elapses
+        assert!(!health.observe(RelayStatus::Terminated, 101, &config));
+        assert!(health.observe(RelayStatus::Terminated, 102, &config));
+        assert_eq!(health.failures, 2);
+        assert_eq!(health.next_retry_at, Some(106));
+        assert_eq!(health.last_checked_at, Some(102));
+    }
+
+    #[test]
+    fn test_relay_health_reset_on_connect() {
+        let config = test_config();
+        let url = RelayUrl::parse("wss://relay.example.com").unwrap();
+        let mut health = RelayHealth::new(&url, true, false);
+
+        assert!(health.observe(RelayStatus::Disconnected, 100, &config));
+        assert!(!health.observe(RelayStatus::Connected, 101, &config));
+        assert!(health.connected);
+        assert_eq!(health.failures, 0);
+        assert_eq!(health.last_connected_at, Some(101));
+        assert!(health.next_retry_at.is_none());
+    }
+}
@@ -0,0 +1,453 @@
+//! Seen-request store for NIP-74 replay protection
+//! Remembers processed request events and their results so that relay
+//! re-deliveries and replays are answered from cache instead of re-executed.
+//! Results are persisted with their requests, so retries after a restart get
+//! them too; both are dropped once outside the freshness window.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::nip74_service::{OperationMethod, OperationResult};
+
+/// Minimum interval between writes of the store in seconds
+const PERSIST_INTERVAL: u64 = 5;
+
+/// Most requests remembered; the oldest are forgotten first
+const MAX_REQUESTS: usize = 10_000;
+
+/// Outcome of checking an incoming request against the store
+#[derive(Debug, Clone)]
+pub enum RequestCheck {
+    /// First time this request is seen; it is now reserved for processing
+    New,
+    /// Request was already processed; contains the cached result
+    Duplicate(OperationResult),
+    /// Request was processed but its result was not stored, as in stores
+    /// written before results were persisted
+    Processed,
+    /// Request id was already used by the author for another method
+    MethodMismatch(OperationMethod),
+    /// Request is currently being processed
+    InProgress,
+    /// `created_at` is outside the freshness window
+    Expired,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SeenRequest {
+    event_id: String,
+    author: String,
+    request_id: String,
+    method: OperationMethod,
+    created_at: u64,
+    completed: bool,
+    /// Written next to the mint database, which holds the same blind signatures
+    #[serde(default)]
+    result: Option<OperationResult>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreFile {
+    requests: Vec<SeenRequest>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Entries keyed by event id
+    by_event: HashMap<String, SeenRequest>,
+    /// (author, request_id) -> event id
+    by_request: HashMap<(String, String), String>,
+    /// Event ids in insertion order, so pruning and eviction start at the oldest;
+    /// ids of removed entries are skipped when they reach the front
+    order: VecDeque<String>,
+    dirty: bool,
+    persisted_at: u64,
+}
+
+impl Inner {
+    fn insert(&mut self, entry: SeenRequest) {
+        self.by_request.insert(
+            (entry.author.clone(), entry.request_id.clone()),
+            entry.event_id.clone(),
+        );
+        self.order.push_back(entry.event_id.clone());
+        self.by_event.insert(entry.event_id.clone(), entry);
+
+        while self.by_event.len() > MAX_REQUESTS {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+
+    fn remove(&mut self, event_id: &str) {
+        if let Some(entry) = self.by_event.remove(event_id) {
+            self.by_request.remove(&(entry.author, entry.request_id));
+        }
+    }
+
+    /// Entry of the event or the author's request id, unless it expired
+    fn find(&self, event_id: &str, author: &str, request_id: &str, now: u64, window: u64) -> Option<&SeenRequest> {
+        self.by_event
+            .get(event_id)
+            .or_else(|| {
+                self.by_request
+                    .get(&(author.to_string(), request_id.to_string()))
+                    .and_then(|id| self.by_event.get(id))
+            })
+            .filter(|e| e.created_at.saturating_add(window) >= now)
+    }
+
+    /// Drop the oldest entries while their events are outside the freshness window
+    ///
+    /// Only the front of the insertion order is looked at, so each entry is
+    /// visited about once; an expired entry queued behind a fresh one waits at
+    /// most a window longer, and [`Inner::find`] never returns it.
+    fn prune(&mut self, now: u64, window: u64) {
+        while let Some(oldest) = self.order.front() {
+            let expired = self
+                .by_event
+                .get(oldest)
+                .is_none_or(|e| e.created_at.saturating_add(window) < now);
+            if !expired {
+                break;
+            }
+            if let Some(oldest) = self.order.pop_front() {
+                self.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Persistent store of processed NIP-74 requests
+pub struct RequestStore {
+    path: Option<PathBuf>,
+    window: u64,
+    inner: Mutex<Inner>,
+}
+
+impl RequestStore {
+    /// Create a store that only lives in memory
+    pub fn in_memory(window_secs: u64) -> Self {
+        Self {
+            path: None,
+            window: window_secs,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Open (or create) a store persisted as JSON at `path`
+    pub fn open(path: impl AsRef<Path>, window_secs: u64) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut inner = Inner::default();
+
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("Failed to read request store: {}", e))?;
+            match serde_json::from_str::<StoreFile>(&content) {
+                Ok(mut file) => {
+                    file.requests.sort_by_key(|e| e.created_at);
+                    for entry in file.requests.into_iter().filter(|e| e.completed) {
+                        inner.insert(entry);
+                    }
+                }
+                Err(e) => warn!("Ignoring corrupt request store {:?}: {}", path, e),
+            }
+        }
+        inner.prune(nostr::Timestamp::now().as_u64(), window_secs);
+
+        Ok(Self {
+            path: Some(path),
+            window: window_secs,
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Freshness window in seconds
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// Check an incoming request and reserve it for processing if it is new
+    pub fn check(
+        &self,
+        event_id: &nostr::EventId,
+        author: &nostr::PublicKey,
+        request_id: &str,
+        method: &OperationMethod,
+        created_at: nostr::Timestamp,
+    ) -> RequestCheck {
+        let now = nostr::Timestamp::now().as_u64();
+        self.check_at(event_id, author, request_id, method, created_at.as_u64(), now)
+    }
+
+    fn check_at(
+        &self,
+        event_id: &nostr::EventId,
+        author: &nostr::PublicKey,
+        request_id: &str,
+        method: &OperationMethod,
+        created_at: u64,
+        now: u64,
+    ) -> RequestCheck {
+        if now.saturating_sub(created_at) > self.window || created_at.saturating_sub(now) > self.window {
+            return RequestCheck::Expired;
+        }
+
+        let event_id = event_id.to_hex();
+        let author = author.to_hex();
+
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        inner.prune(now, self.window);
+
+        if let Some(entry) = inner.find(&event_id, &author, request_id, now, self.window) {
+            if entry.method != *method {
+                return RequestCheck::MethodMismatch(entry.method.clone());
+            }
+            return match (&entry.result, entry.completed) {
+                (Some(result), _) => RequestCheck::Duplicate(result.clone()),
+                (None, true) => RequestCheck::Processed,
+                (None, false) => RequestCheck::InProgress,
+            };
+        }
+
+        inner.insert(SeenRequest {
+            event_id,
+            author,
+            request_id: request_id.to_string(),
+            method: method.clone(),
+            created_at,
+            completed: false,
+            result: None,
+        });
+        RequestCheck::New
+    }
+
+    /// Record the result of a reserved request; the store is persisted at most
+    /// every few seconds
+    pub fn complete(&self, event_id: &nostr::EventId, result: &OperationResult) {
+        {
+            let mut inner = match self.inner.lock() {
+                Ok(inner) => inner,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if let Some(entry) = inner.by_event.get_mut(&event_id.to_hex()) {
+                entry.completed = true;
+                entry.result = Some(result.clone());
+                inner.dirty = true;
+            }
+        }
+
+        self.persist_if_due();
+    }
+
+    /// Drop the reservation of a request that could not be processed
+    pub fn release(&self, event_id: &nostr::EventId) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        inner.remove(&event_id.to_hex());
+    }
+
+    /// Write the completed requests and their results to disk
+    pub fn flush(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let file = {
+            let mut inner = match self.inner.lock() {
+                Ok(inner) => inner,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            inner.dirty = false;
+            inner.persisted_at = nostr::Timestamp::now().as_u64();
+            StoreFile {
+                requests: inner
+                    .by_event
+                    .values()
+                    .filter(|e| e.completed)
+                    .cloned()
+                    .collect(),
+            }
+        };
+
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(&file)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        debug!("Persisted {} seen requests to {:?}", file.requests.len(), path);
+        Ok(())
+    }
+
+    fn persist_if_due(&self) {
+        let due = match self.inner.lock() {
+            Ok(inner) => {
+                inner.dirty && nostr::Timestamp::now().as_u64() >= inner.persisted_at + PERSIST_INTERVAL
+            }
+            Err(_) => false,
+        };
+        if due {
+            if let Err(e) = self.flush() {
+                warn!("Failed to persist request store: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    use crate::nip74_service::ResultStatus;
+
+    const METHOD: OperationMethod = OperationMethod::CheckMintQuote;
+
+    fn result(request_id: &str) -> OperationResult {
+        OperationResult {
+            status: ResultStatus::Success,
+            request_id: request_id.to_string(),
+            data: Some(serde_json::json!({"paid": true})),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_duplicate_returns_cached_result() {
+        let store = RequestStore::in_memory(300);
+        let author = nostr::Keys::generate().public_key();
+        let event_id = nostr::EventId::all_zeros();
+
+        assert!(matches!(store.check_at(&event_id, &author, "req-1", &METHOD, 1000, 1000), RequestCheck::New));
+        assert!(matches!(store.check_at(&event_id, &author, "req-1", &METHOD, 1000, 1001), RequestCheck::InProgress));
+
+        store.complete(&event_id, &result("req-1"));
+        match store.check_at(&event_id, &author, "req-1", &METHOD, 1000, 1002) {
+            RequestCheck::Duplicate(cached) => assert_eq!(cached.request_id, "req-1"),
+            other => panic!("expected duplicate, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_same_request_id_in_new_event_is_duplicate() {
+        let store = RequestStore::in_memory(300);
+        let author = nostr::Keys::generate().public_key();
+        let first = nostr::EventId::all_zeros();
+        let second = nostr::EventId::from_slice(&[1u8; 32]).unwrap();
+
+        assert!(matches!(store.check_at(&first, &author, "req-1", &METHOD, 1000, 1000), RequestCheck::New));
+        store.complete(&first, &result("req-1"));
+        assert!(matches!(store.check_at(&second, &author, "req-1", &METHOD, 1010, 1010), RequestCheck::Duplicate(_)));
+
+        // The same request id from another author is a different request
+        let other = nostr::Keys::generate().public_key();
+        assert!(matches!(store.check_at(&second, &other, "req-1", &METHOD, 1010, 1010), RequestCheck::New));
+    }
+
+    #[test]
+    fn test_expired_requests_rejected() {
+        let store = RequestStore::in_memory(300);
+        let author = nostr::Keys::generate().public_key();
+        let event_id = nostr::EventId::all_zeros();
+
+        assert!(matches!(store.check_at(&event_id, &author, "old", &METHOD, 1000, 1301), RequestCheck::Expired));
+        assert!(matches!(store.check_at(&event_id, &author, "future", &METHOD, 2000, 1000), RequestCheck::Expired));
+    }
+
+    #[test]
+    fn test_release_allows_retry() {
+        let store = RequestStore::in_memory(300);
+        let author = nostr::Keys::generate().public_key();
+        let event_id = nostr::EventId::all_zeros();
+
+        assert!(matches!(store.check_at(&event_id, &author, "req-1", &METHOD, 1000, 1000), RequestCheck::New));
+        store.release(&event_id);
+        assert!(matches!(store.check_at(&event_id, &author, "req-1", &METHOD, 1000, 1000), RequestCheck::New));
+    }
+
+    #[test]
+    fn test_same_request_id_with_other_method_is_rejected() {
+        let store = RequestStore::in_memory(300);
+        let author = nostr::Keys::generate().public_key();
+        let first = nostr::EventId::all_zeros();
+        let second = nostr::EventId::from_slice(&[1u8; 32]).unwrap();
+
+        assert!(matches!(store.check_at(&first, &author, "req-1", &METHOD, 1000, 1000), RequestCheck::New));
+        store.complete(&first, &result("req-1"));
+        match store.check_at(&second, &author, "req-1", &OperationMethod::Swap, 1010, 1010) {
+            RequestCheck::MethodMismatch(method) => assert_eq!(method, METHOD),
+            other => panic!("expected method mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_answered_after_reload() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("requests.json");
+        let author = nostr::Keys::generate().public_key();
+        let event_id = nostr::EventId::all_zeros();
+        let retry = nostr::EventId::from_slice(&[1u8; 32]).unwrap();
+        let now = nostr::Timestamp::now();
+
+        let store = RequestStore::open(&path, 300).unwrap();
+        assert!(matches!(store.check(&event_id, &author, "req-1", &METHOD, now), RequestCheck::New));
+        store.complete(&event_id, &result("req-1"));
+        store.flush().unwrap();
+        drop(store);
+
+        // A client retrying after the restart, with the same or a new event, gets the result
+        let reopened = RequestStore::open(&path, 300).unwrap();
+        for event_id in [event_id, retry] {
+            match reopened.check(&event_id, &author, "req-1", &METHOD, now) {
+                RequestCheck::Duplicate(cached) => assert_eq!(cached.data, result("req-1").data),
+                other => panic!("expected duplicate, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_store_is_bounded() {
+        let store = RequestStore::in_memory(300);
+        let author = nostr::Keys::generate().public_key();
+
+        for n in 0..=MAX_REQUESTS {
+            let mut id = [0u8; 32];
+            id[..8].copy_from_slice(&(n as u64).to_be_bytes());
+            let event_id = nostr::EventId::from_slice(&id).unwrap();
+            let request_id = n.to_string();
+            assert!(matches!(store.check_at(&event_id, &author, &request_id, &METHOD, 1000, 1000), RequestCheck::New));
+            store.complete(&event_id, &result(&request_id));
+        }
+
+        let inner = store.inner.lock().unwrap();
+        assert_eq!(inner.by_event.len(), MAX_REQUESTS);
+        assert_eq!(inner.by_request.len(), MAX_REQUESTS);
+        assert!(!inner.by_request.contains_key(&(author.to_hex(), "0".to_string())));
+    }
+
+    #[test]
+    fn test_expired_entries_are_pruned() {
+        let store = RequestStore::in_memory(300);
+        let author = nostr::Keys::generate().public_key();
+        let first = nostr::EventId::all_zeros();
+        let second = nostr::EventId::from_slice(&[1u8; 32]).unwrap();
+
+        assert!(matches!(store.check_at(&first, &author, "req-1", &METHOD, 1000, 1000), RequestCheck::New));
+        store.complete(&first, &result("req-1"));
+        assert!(matches!(store.check_at(&second, &author, "req-2", &METHOD, 1400, 1400), RequestCheck::New));
+
+        let inner = store.inner.lock().unwrap();
+        assert_eq!(inner.by_event.len(), 1);
+        assert_eq!(inner.order.len(), 1);
+    }
+}
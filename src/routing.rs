@@ -0,0 +1,1147 @@
+//! Lightning backend routing
+//! Maps each currency unit and payment method to a chain of backends and fails
+//! over to the next backend of the chain while the primary fails its health probe
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use cdk::nuts::{CurrencyUnit, PaymentMethod};
+use cdk_common::nuts::MeltQuoteState;
+use cdk_common::payment::{
+    self, CreateIncomingPaymentResponse, IncomingPaymentOptions, MakePaymentResponse, MintPayment,
+    OutgoingPaymentOptions, PaymentIdentifier, PaymentQuoteResponse, WaitPaymentResponse,
+};
+use futures::StreamExt;
+use serde_json::Value;
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::config::{LnBackend, RoutingConfig};
+use crate::payment::{PaymentStream, payment_hash};
+
+/// Lightning backend as registered with the mint
+pub type DynBackend = Arc<dyn MintPayment<Err = payment::Error> + Send + Sync>;
+
+/// Check of whether a backend's node is reachable and working
+#[async_trait]
+pub trait HealthProbe: Send + Sync {
+    async fn probe(&self) -> Result<(), payment::Error>;
+}
+
+/// Probe GETting an HTTP endpoint that answers with a success status while the node is up
+pub struct HttpProbe {
+    client: reqwest::Client,
+    url: String,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl HttpProbe {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            url: url.into(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Send `name: value` with every probe
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+#[async_trait]
+impl HealthProbe for HttpProbe {
+    async fn probe(&self) -> Result<(), payment::Error> {
+        let mut request = self.client.get(&self.url);
+        for (name, value) in &self.headers {
+            request = request.header(*name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| payment::Error::Custom(format!("{} unreachable: {}", self.url, e)))?;
+        if !response.status().is_success() {
+            return Err(payment::Error::Custom(format!("{} returned {}", self.url, response.status())));
+        }
+        Ok(())
+    }
+}
+
+/// Probe calling `getinfo` over the JSON-RPC socket of a Core Lightning node
+pub struct ClnProbe {
+    rpc_path: std::path::PathBuf,
+}
+
+impl ClnProbe {
+    pub fn new(rpc_path: impl Into<std::path::PathBuf>) -> Self {
+        Self { rpc_path: rpc_path.into() }
+    }
+
+    async fn getinfo(&self) -> Result<Value, payment::Error> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let path = self.rpc_path.display();
+        let mut socket = tokio::net::UnixStream::connect(&self.rpc_path)
+            .await
+            .map_err(|e| payment::Error::Custom(format!("{} unreachable: {}", path, e)))?;
+        let request = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "getinfo", "params": {} });
+        socket
+            .write_all(request.to_string().as_bytes())
+            .await
+            .map_err(|e| payment::Error::Custom(format!("{} write failed: {}", path, e)))?;
+
+        // The node answers with one JSON object and keeps the socket open
+        let mut response = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = socket
+                .read(&mut buffer)
+                .await
+                .map_err(|e| payment::Error::Custom(format!("{} read failed: {}", path, e)))?;
+            if read == 0 {
+                return Err(payment::Error::Custom(format!("{} closed the connection", path)));
+            }
+            response.extend_from_slice(&buffer[..read]);
+            match serde_json::from_slice::<Value>(&response) {
+                Ok(value) => return Ok(value),
+                Err(e) if e.is_eof() => continue,
+                Err(e) => return Err(payment::Error::Custom(format!("{} sent an invalid reply: {}", path, e))),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl HealthProbe for ClnProbe {
+    async fn probe(&self) -> Result<(), payment::Error> {
+        let response = tokio::time::timeout(Duration::from_secs(10), self.getinfo())
+            .await
+            .map_err(|_| payment::Error::Custom(format!("{} timed out", self.rpc_path.display())))??;
+        match response.get("error") {
+            Some(error) => Err(payment::Error::Custom(format!("getinfo failed: {}", error))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Backend built from the settings and the units and methods it can serve
+#[derive(Clone)]
+pub struct ConfiguredBackend {
+    pub kind: LnBackend,
+    pub units: Vec<CurrencyUnit>,
+    pub methods: Vec<PaymentMethod>,
+    pub backend: DynBackend,
+    /// Health probe used in fallback chains; backends without one count as healthy
+    pub probe: Option<Arc<dyn HealthProbe>>,
+}
+
+impl ConfiguredBackend {
+    fn serves(&self, unit: &CurrencyUnit, method: &PaymentMethod) -> bool {
+        self.units.contains(unit) && self.methods.contains(method)
+    }
+}
+
+/// Payment processor to register for one unit and payment method
+#[derive(Clone)]
+pub struct Registration {
+    pub unit: CurrencyUnit,
+    pub method: PaymentMethod,
+    pub backend: DynBackend,
+    /// Backends behind the processor, primary first
+    pub chain: Vec<LnBackend>,
+}
+
+/// Turn the routing table into the processors to register
+///
+/// Without routes every backend is registered for each unit and method it
+/// serves; a unit and method served by several backends needs a route to pick
+/// one. Routes only register the listed pairs and wrap chains of several
+/// backends in a [`FailoverBackend`].
+pub fn resolve_routes(routing: &RoutingConfig, backends: &[ConfiguredBackend]) -> Result<Vec<Registration>> {
+    if routing.routes.is_empty() {
+        let mut registrations: Vec<Registration> = Vec::new();
+        for configured in backends {
+            for unit in &configured.units {
+                for method in &configured.methods {
+                    if let Some(other) = registrations.iter().find(|r| r.unit == *unit && r.method == *method) {
+                        return Err(anyhow!(
+                            "{} {} is served by both {:?} and {:?}; add a route to choose the backend",
+                            unit,
+                            method,
+                            other.chain[0],
+                            configured.kind
+                        ));
+                    }
+                    registrations.push(Registration {
+                        unit: unit.clone(),
+                        method: method.clone(),
+                        backend: configured.backend.clone(),
+                        chain: vec![configured.kind.clone()],
+                    });
+                }
+            }
+        }
+        return Ok(registrations);
+    }
+
+    // Routes with the same chain share one failover backend, and so its health
+    let mut failovers: HashMap<Vec<LnBackend>, DynBackend> = HashMap::new();
+    let mut registrations = Vec::new();
+    for route in &routing.routes {
+        let chain = route.chain();
+        let mut members = Vec::new();
+        for kind in &chain {
+            let configured = backends
+                .iter()
+                .find(|configured| configured.kind == *kind)
+                .ok_or_else(|| anyhow!("Route for {} {} uses {:?}, which is not configured", route.unit, route.method, kind))?;
+            if !configured.serves(&route.unit, &route.method) {
+                return Err(anyhow!("{:?} does not support {} {}", kind, route.unit, route.method));
+            }
+            members.push(Member {
+                kind: kind.clone(),
+                backend: configured.backend.clone(),
+                probe: configured.probe.clone(),
+            });
+        }
+
+        let backend = match members.as_slice() {
+            [member] => member.backend.clone(),
+            _ => failovers
+                .entry(chain.clone())
+                .or_insert_with(|| {
+                    Arc::new(FailoverBackend::new(members, Duration::from_secs(routing.retry_interval))) as DynBackend
+                })
+                .clone(),
+        };
+        info!("Routing {} {} to {:?}", route.unit, route.method, chain);
+        registrations.push(Registration {
+            unit: route.unit.clone(),
+            method: route.method.clone(),
+            backend,
+            chain,
+        });
+    }
+    Ok(registrations)
+}
+
+/// Errors caused by the request rather than by the backend
+fn is_request_error(error: &payment::Error) -> bool {
+    matches!(error, payment::Error::UnsupportedUnit | payment::Error::UnsupportedPaymentOption)
+}
+
+/// Most invoice and payment owners a chain remembers; the oldest are forgotten first
+const MAX_OWNERS: usize = 10_000;
+
+/// Backend index by lookup id, bounded to the `MAX_OWNERS` most recent
+///
+/// Each entry carries the generation it was inserted with, so removing one only
+/// touches the index and leaves a stale entry in `recent` that is skipped later.
+#[derive(Default)]
+struct Owners {
+    index: HashMap<String, (usize, u64)>,
+    recent: VecDeque<(String, u64)>,
+    generation: u64,
+}
+
+impl Owners {
+    fn get(&self, key: &str) -> Option<usize> {
+        self.index.get(key).map(|(index, _)| *index)
+    }
+
+    fn insert(&mut self, key: String, index: usize) {
+        if let Some(entry) = self.index.get_mut(&key) {
+            entry.0 = index;
+            return;
+        }
+        self.generation += 1;
+        self.index.insert(key.clone(), (index, self.generation));
+        self.recent.push_back((key, self.generation));
+
+        while self.index.len() > MAX_OWNERS {
+            let Some((oldest, generation)) = self.recent.pop_front() else {
+                break;
+            };
+            if self.is_current(&oldest, generation) {
+                self.index.remove(&oldest);
+            }
+        }
+        // Drop stale entries of removed owners once they make up half of `recent`
+        if self.recent.len() > 2 * MAX_OWNERS {
+            let index = &self.index;
+            self.recent
+                .retain(|(key, generation)| index.get(key).is_some_and(|(_, current)| current == generation));
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.index.remove(key);
+    }
+
+    fn is_current(&self, key: &str, generation: u64) -> bool {
+        self.index.get(key).is_some_and(|(_, current)| *current == generation)
+    }
+}
+
+/// Keep the optional features of `settings` that `other` supports too
+///
+/// Features are the boolean settings; without `other`, as for a backend that
+/// could not be asked, none are kept. Other settings are left as they are.
+fn intersect_settings(mut settings: Value, other: Option<&Value>) -> Value {
+    if let Value::Object(fields) = &mut settings {
+        for (name, value) in fields.iter_mut() {
+            if value.is_boolean() {
+                let supported = other.and_then(|other| other.get(name)).and_then(Value::as_bool);
+                *value = Value::Bool(value.as_bool() == Some(true) && supported == Some(true));
+            }
+        }
+    }
+    settings
+}
+
+/// Backend of a fallback chain
+pub struct Member {
+    pub kind: LnBackend,
+    pub backend: DynBackend,
+    pub probe: Option<Arc<dyn HealthProbe>>,
+}
+
+/// Last health probe of a backend
+#[derive(Debug, Clone, Copy)]
+struct Health {
+    checked_at: Instant,
+    healthy: bool,
+}
+
+/// Backend chain that moves on to the next backend when one fails its health probe
+///
+/// Each backend is probed at most once per `probe_interval`, when a request
+/// needs the chain. Healthy backends are tried in chain order, then the
+/// unhealthy ones. Errors of live calls move that call on to the next backend
+/// but never mark a backend unhealthy, as clients can provoke them. Quotes and
+/// payments stay on the backend that created them; payments are never retried
+/// on another backend. Owners are kept in memory until a payment settles, so
+/// after a restart a melt is paid on the first backend whose quote fits its fee
+/// limit. The chain reports the settings all of its backends support, so what
+/// the mint advertises does not depend on which node was up when it started.
+pub struct FailoverBackend {
+    backends: Vec<Member>,
+    probe_interval: Duration,
+    health: Mutex<Vec<Option<Health>>>,
+    /// Backend that created each recent invoice, quote and payment
+    owners: Mutex<Owners>,
+    /// Ends the chain's own payment stream; members may also be registered
+    /// directly for other units, so their waits are left alone
+    cancel: watch::Sender<bool>,
+    active: Arc<AtomicBool>,
+}
+
+impl FailoverBackend {
+    /// Chain of `backends`, primary first
+    pub fn new(backends: Vec<Member>, probe_interval: Duration) -> Self {
+        let health = vec![None; backends.len()];
+        Self {
+            backends,
+            probe_interval,
+            health: Mutex::new(health),
+            owners: Mutex::new(Owners::default()),
+            cancel: watch::channel(false).0,
+            active: Arc::default(),
+        }
+    }
+
+    /// Whether the backend at `index` passed its last health probe
+    pub fn is_healthy(&self, index: usize) -> bool {
+        let health = self.health.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        health.get(index).copied().flatten().is_none_or(|health| health.healthy)
+    }
+
+    /// Probe the backends whose last probe is older than the probe interval
+    async fn refresh(&self) {
+        let due: Vec<usize> = {
+            let mut health = self.health.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let now = Instant::now();
+            (0..self.backends.len())
+                .filter(|index| self.backends[*index].probe.is_some())
+                .filter(|index| match &mut health[*index] {
+                    Some(last) if now.duration_since(last.checked_at) < self.probe_interval => false,
+                    // Claim the probe so concurrent requests do not run it again
+                    Some(last) => {
+                        last.checked_at = now;
+                        true
+                    }
+                    None => {
+                        health[*index] = Some(Health { checked_at: now, healthy: true });
+                        true
+                    }
+                })
+                .collect()
+        };
+
+        let probes = due.iter().map(|index| async move {
+            let result = match &self.backends[*index].probe {
+                Some(probe) => probe.probe().await,
+                None => Ok(()),
+            };
+            (*index, result)
+        });
+        for (index, result) in futures::future::join_all(probes).await {
+            let kind = &self.backends[index].kind;
+            let mut health = self.health.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let Some(last) = health[index].as_mut() else {
+                continue;
+            };
+            match result {
+                Ok(()) => {
+                    if !last.healthy {
+                        info!("{:?} passed its health probe again", kind);
+                    }
+                    last.healthy = true;
+                }
+                Err(e) => {
+                    if last.healthy {
+                        warn!("{:?} failed its health probe, failing over: {}", kind, e);
+                    }
+                    last.healthy = false;
+                }
+            }
+        }
+    }
+
+    /// Healthy backends in chain order, then the unhealthy ones as a last resort
+    async fn order(&self) -> Vec<usize> {
+        self.refresh().await;
+        let (healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..self.backends.len()).partition(|index| self.is_healthy(*index));
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    fn owner_key(identifier: &PaymentIdentifier) -> String {
+        format!("{:?}", identifier)
+    }
+
+    fn remember(&self, identifier: &PaymentIdentifier, index: usize) {
+        let mut owners = self.owners.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        owners.insert(Self::owner_key(identifier), index);
+    }
+
+    fn forget(&self, identifier: &PaymentIdentifier) {
+        let mut owners = self.owners.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        owners.remove(&Self::owner_key(identifier));
+    }
+
+    fn owner(&self, identifier: &PaymentIdentifier) -> Option<usize> {
+        let owners = self.owners.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        owners.get(&Self::owner_key(identifier))
+    }
+
+    /// Lookup id of the quote for the invoice paid by `options`
+    fn quote_id(options: &OutgoingPaymentOptions) -> Option<PaymentIdentifier> {
+        match options {
+            OutgoingPaymentOptions::Bolt11(options) => {
+                payment_hash(&options.bolt11).ok().map(PaymentIdentifier::PaymentHash)
+            }
+            _ => None,
+        }
+    }
+
+    /// Backend that quoted the invoice paid by `options`, if known
+    fn quote_owner(&self, options: &OutgoingPaymentOptions) -> Option<usize> {
+        self.owner(&Self::quote_id(options)?)
+    }
+
+    /// Backend to pay `options` on when the quoting backend is unknown, as after a restart
+    ///
+    /// The wallet was charged the fee reserve of the original quote, so the payment goes
+    /// to the first backend whose own quote fits the fee limit of the melt.
+    async fn requote(&self, unit: &CurrencyUnit, options: &OutgoingPaymentOptions) -> Result<usize, payment::Error> {
+        let order = self.order().await;
+        let max_fee = match options {
+            OutgoingPaymentOptions::Bolt11(options) => options.max_fee_amount,
+            _ => None,
+        };
+        let Some(max_fee) = max_fee else {
+            return Ok(order[0]);
+        };
+
+        for index in order {
+            let kind = &self.backends[index].kind;
+            match self.backends[index].backend.get_payment_quote(unit, options.clone()).await {
+                Ok(quote) if quote.fee <= max_fee => return Ok(index),
+                Ok(quote) => debug!("{:?} quotes a fee of {} above the limit of {}", kind, quote.fee, max_fee),
+                Err(e) => debug!("{:?} failed to quote: {}", kind, e),
+            }
+        }
+        Err(payment::Error::Custom(format!("No backend can pay within the fee limit of {} {}", max_fee, unit)))
+    }
+
+    /// Run `call` on the first backend that succeeds, moving on after backend errors
+    async fn first_success<T, F, Fut>(&self, call: F) -> Result<(usize, T), payment::Error>
+    where
+        F: Fn(DynBackend) -> Fut,
+        Fut: Future<Output = Result<T, payment::Error>>,
+    {
+        let mut last_error = None;
+        for index in self.order().await {
+            match call(self.backends[index].backend.clone()).await {
+                Ok(value) => return Ok((index, value)),
+                Err(e) if is_request_error(&e) => return Err(e),
+                Err(e) => {
+                    debug!("{:?} failed, trying the next backend: {}", self.backends[index].kind, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or(payment::Error::UnsupportedPaymentOption))
+    }
+}
+
+#[async_trait]
+impl MintPayment for FailoverBackend {
+    type Err = payment::Error;
+
+    /// Settings every backend of the chain supports, whichever of them is up
+    async fn get_settings(&self) -> Result<Value, Self::Err> {
+        let answers =
+            futures::future::join_all(self.backends.iter().map(|member| member.backend.get_settings())).await;
+        let mut merged: Option<Value> = None;
+        let mut unanswered = None;
+        for (member, answer) in self.backends.iter().zip(answers) {
+            match answer {
+                Ok(settings) => {
+                    merged = Some(match merged {
+                        Some(merged) => intersect_settings(merged, Some(&settings)),
+                        None => settings,
+                    });
+                }
+                Err(e) => {
+                    warn!("{:?} did not report its settings: {}", member.kind, e);
+                    unanswered = Some(e);
+                }
+            }
+        }
+
+        match (merged, unanswered) {
+            (Some(merged), None) => Ok(merged),
+            (Some(merged), Some(_)) => Ok(intersect_settings(merged, None)),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(payment::Error::UnsupportedPaymentOption),
+        }
+    }
+
+    async fn create_incoming_payment_request(
+        &self,
+        unit: &CurrencyUnit,
+        options: IncomingPaymentOptions,
+    ) -> Result<CreateIncomingPaymentResponse, Self::Err> {
+        let (index, response) = self
+            .first_success(|backend| {
+                let options = options.clone();
+                async move { backend.create_incoming_payment_request(unit, options).await }
+            })
+            .await?;
+        self.remember(&response.request_lookup_id, index);
+        Ok(response)
+    }
+
+    async fn get_payment_quote(
+        &self,
+        unit: &CurrencyUnit,
+        options: OutgoingPaymentOptions,
+    ) -> Result<PaymentQuoteResponse, Self::Err> {
+        let (index, quote) = self
+            .first_success(|backend| {
+                let options = options.clone();
+                async move { backend.get_payment_quote(unit, options).await }
+            })
+            .await?;
+        self.remember(&quote.request_lookup_id, index);
+        Ok(quote)
+    }
+
+    async fn make_payment(
+        &self,
+        unit: &CurrencyUnit,
+        options: OutgoingPaymentOptions,
+    ) -> Result<MakePaymentResponse, Self::Err> {
+        // The fee reserve was quoted by the owner, so pay there even if another backend is preferred now.
+        // A failed payment may still be in flight, so it is not retried elsewhere
+        let index = match self.quote_owner(&options) {
+            Some(index) => index,
+            None => self.requote(unit, &options).await?,
+        };
+        let quote_id = Self::quote_id(&options);
+        let response = self.backends[index].backend.make_payment(unit, options).await?;
+        match response.status {
+            MeltQuoteState::Paid | MeltQuoteState::Failed => {
+                if let Some(quote_id) = quote_id {
+                    self.forget(&quote_id);
+                }
+                self.forget(&response.payment_lookup_id);
+            }
+            _ => self.remember(&response.payment_lookup_id, index),
+        }
+        Ok(response)
+    }
+
+    async fn wait_any_incoming_payment(&self) -> Result<PaymentStream, Self::Err> {
+        let mut streams = Vec::new();
+        let mut last_error = None;
+        for member in &self.backends {
+            match member.backend.wait_any_incoming_payment().await {
+                Ok(stream) => streams.push(stream),
+                Err(e) => {
+                    warn!("Not waiting for {:?} payments: {}", member.kind, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        if let Some(e) = last_error.filter(|_| streams.is_empty()) {
+            return Err(e);
+        }
+
+        self.cancel.send_replace(false);
+        self.active.store(true, Ordering::SeqCst);
+        let state = (futures::stream::select_all(streams), self.cancel.subscribe(), self.active.clone());
+        Ok(Box::pin(futures::stream::unfold(state, |state| async move {
+            let (mut streams, mut cancel, active) = state;
+            loop {
+                tokio::select! {
+                    changed = cancel.changed() => {
+                        if changed.is_err() || *cancel.borrow() {
+                            active.store(false, Ordering::SeqCst);
+                            return None;
+                        }
+                    }
+                    payment = streams.next() => match payment {
+                        Some(payment) => return Some((payment, (streams, cancel, active))),
+                        None => {
+                            active.store(false, Ordering::SeqCst);
+                            return None;
+                        }
+                    },
+                }
+            }
+        })))
+    }
+
+    fn is_wait_invoice_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// End the chain's stream, which drops its member streams, without cancelling
+    /// the members' waits for the other units they are registered for
+    fn cancel_wait_invoice(&self) {
+        self.cancel.send_replace(true);
+    }
+
+    async fn check_incoming_payment_status(
+        &self,
+        payment_identifier: &PaymentIdentifier,
+    ) -> Result<Vec<WaitPaymentResponse>, Self::Err> {
+        if let Some(index) = self.owner(payment_identifier) {
+            return self.backends[index].backend.check_incoming_payment_status(payment_identifier).await;
+        }
+
+        // Owners are not persisted: ask every backend after a restart
+        let mut result = None;
+        for member in &self.backends {
+            match member.backend.check_incoming_payment_status(payment_identifier).await {
+                Ok(payments) if !payments.is_empty() => return Ok(payments),
+                Ok(payments) => result = Some(Ok(payments)),
+                Err(e) => {
+                    if result.is_none() {
+                        result = Some(Err(e));
+                    }
+                }
+            }
+        }
+        result.unwrap_or_else(|| Ok(Vec::new()))
+    }
+
+    async fn check_outgoing_payment(
+        &self,
+        payment_identifier: &PaymentIdentifier,
+    ) -> Result<MakePaymentResponse, Self::Err> {
+        if let Some(index) = self.owner(payment_identifier) {
+            let response = self.backends[index].backend.check_outgoing_payment(payment_identifier).await?;
+            if matches!(response.status, MeltQuoteState::Paid | MeltQuoteState::Failed) {
+                self.forget(payment_identifier);
+            }
+            return Ok(response);
+        }
+
+        let mut result = None;
+        for member in &self.backends {
+            match member.backend.check_outgoing_payment(payment_identifier).await {
+                Ok(response) if response.status != MeltQuoteState::Unknown => return Ok(response),
+                Ok(response) => result = Some(Ok(response)),
+                Err(e) => {
+                    if result.is_none() {
+                        result = Some(Err(e));
+                    }
+                }
+            }
+        }
+        result.unwrap_or_else(|| Err(payment::Error::Custom(format!("Unknown payment {:?}", payment_identifier))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    use std::str::FromStr;
+
+    use cdk::lightning_invoice::Bolt11Invoice;
+    use cdk::types::FeeReserve;
+    use cdk_common::Amount;
+    use cdk_common::payment::Bolt11IncomingPaymentOptions;
+
+    use crate::config::BackendRoute;
+    use crate::payment::InvoiceWatcher;
+    use crate::payment::testing::pay_options;
+
+    /// Backend whose calls all fail and whose invoices are never paid, with a
+    /// health probe passing while `up` is set
+    #[derive(Default)]
+    struct Down {
+        calls: AtomicUsize,
+        probes: AtomicUsize,
+        up: AtomicBool,
+        invoices: InvoiceWatcher,
+    }
+
+    impl Down {
+        fn fail<T>(&self) -> Result<T, payment::Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(payment::Error::Custom("connection refused".to_string()))
+        }
+    }
+
+    #[async_trait]
+    impl HealthProbe for Down {
+        async fn probe(&self) -> Result<(), payment::Error> {
+            self.probes.fetch_add(1, Ordering::SeqCst);
+            match self.up.load(Ordering::SeqCst) {
+                true => Ok(()),
+                false => Err(payment::Error::Custom("connection refused".to_string())),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl MintPayment for Down {
+        type Err = payment::Error;
+
+        async fn get_settings(&self) -> Result<Value, Self::Err> {
+            self.fail()
+        }
+
+        async fn create_incoming_payment_request(
+            &self,
+            _unit: &CurrencyUnit,
+            _options: IncomingPaymentOptions,
+        ) -> Result<CreateIncomingPaymentResponse, Self::Err> {
+            self.fail()
+        }
+
+        async fn get_payment_quote(
+            &self,
+            _unit: &CurrencyUnit,
+            _options: OutgoingPaymentOptions,
+        ) -> Result<PaymentQuoteResponse, Self::Err> {
+            self.fail()
+        }
+
+        async fn make_payment(
+            &self,
+            _unit: &CurrencyUnit,
+            _options: OutgoingPaymentOptions,
+        ) -> Result<MakePaymentResponse, Self::Err> {
+            self.fail()
+        }
+
+        async fn wait_any_incoming_payment(&self) -> Result<PaymentStream, Self::Err> {
+            Ok(self.invoices.stream(Duration::from_millis(10), |_| async { Ok(None) }))
+        }
+
+        fn is_wait_invoice_active(&self) -> bool {
+            self.invoices.is_active()
+        }
+
+        fn cancel_wait_invoice(&self) {
+            self.invoices.cancel();
+        }
+
+        async fn check_incoming_payment_status(
+            &self,
+            _payment_identifier: &PaymentIdentifier,
+        ) -> Result<Vec<WaitPaymentResponse>, Self::Err> {
+            self.fail()
+        }
+
+        async fn check_outgoing_payment(
+            &self,
+            _payment_identifier: &PaymentIdentifier,
+        ) -> Result<MakePaymentResponse, Self::Err> {
+            self.fail()
+        }
+    }
+
+    fn fake_wallet() -> DynBackend {
+        let fee_reserve = FeeReserve {
+            min_fee_reserve: 1.into(),
+            percent_fee_reserve: 0.02,
+        };
+        Arc::new(cdk_fake_wallet::FakeWallet::new(
+            fee_reserve,
+            HashMap::new(),
+            std::collections::HashSet::new(),
+            1,
+        ))
+    }
+
+    fn configured(kind: LnBackend, backend: DynBackend) -> ConfiguredBackend {
+        ConfiguredBackend {
+            kind,
+            units: vec![CurrencyUnit::Sat, CurrencyUnit::Msat],
+            methods: vec![PaymentMethod::Bolt11],
+            backend,
+            probe: None,
+        }
+    }
+
+    /// Chain of the probed `primary` and a fake wallet without probe
+    fn chain(primary: &Arc<Down>, probe_interval: Duration) -> FailoverBackend {
+        FailoverBackend::new(
+            vec![
+                Member {
+                    kind: LnBackend::LNbits,
+                    backend: primary.clone(),
+                    probe: Some(primary.clone()),
+                },
+                Member {
+                    kind: LnBackend::FakeWallet,
+                    backend: fake_wallet(),
+                    probe: None,
+                },
+            ],
+            probe_interval,
+        )
+    }
+
+    fn invoice_options() -> IncomingPaymentOptions {
+        IncomingPaymentOptions::Bolt11(Bolt11IncomingPaymentOptions {
+            description: None,
+            amount: 21.into(),
+            unix_expiry: None,
+        })
+    }
+
+    #[test]
+    fn test_resolve_routes() {
+        let backends = vec![
+            configured(LnBackend::LNbits, Arc::new(Down::default())),
+            configured(LnBackend::FakeWallet, fake_wallet()),
+        ];
+
+        // Without routes a single backend is registered for every pair it serves
+        let registrations = resolve_routes(&RoutingConfig::default(), &backends[1..]).unwrap();
+        assert_eq!(registrations.len(), 2);
+        assert_eq!(registrations[0].chain, vec![LnBackend::FakeWallet]);
+
+        let route = |unit: CurrencyUnit, fallbacks: Vec<LnBackend>| BackendRoute {
+            unit,
+            method: PaymentMethod::Bolt11,
+            primary: LnBackend::LNbits,
+            fallbacks,
+        };
+        let routing = RoutingConfig {
+            routes: vec![
+                route(CurrencyUnit::Sat, vec![LnBackend::FakeWallet, LnBackend::LNbits]),
+                route(CurrencyUnit::Msat, vec![]),
+            ],
+            ..Default::default()
+        };
+        let registrations = resolve_routes(&routing, &backends).unwrap();
+        assert_eq!(registrations.len(), 2);
+        assert_eq!(registrations[0].chain, vec![LnBackend::LNbits, LnBackend::FakeWallet]);
+        assert_eq!(registrations[1].chain, vec![LnBackend::LNbits]);
+
+        // Routes may only use configured backends
+        let routing = RoutingConfig {
+            routes: vec![route(CurrencyUnit::Sat, vec![LnBackend::Lnd])],
+            ..Default::default()
+        };
+        assert!(resolve_routes(&routing, &backends).is_err());
+    }
+
+    #[test]
+    fn test_unrouted_pair_served_twice_is_rejected() {
+        let backends = vec![
+            configured(LnBackend::LNbits, Arc::new(Down::default())),
+            configured(LnBackend::FakeWallet, fake_wallet()),
+        ];
+
+        // Neither backend silently wins: the error names both and asks for a route
+        let error = resolve_routes(&RoutingConfig::default(), &backends)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("LNbits"));
+        assert!(error.contains("FakeWallet"));
+        assert!(error.contains("route"));
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_healthy_backend() {
+        let primary = Arc::new(Down::default());
+        let failover = chain(&primary, Duration::from_secs(60));
+
+        let response = failover
+            .create_incoming_payment_request(&CurrencyUnit::Sat, invoice_options())
+            .await
+            .unwrap();
+        assert_eq!(primary.probes.load(Ordering::SeqCst), 1);
+        assert!(!failover.is_healthy(0));
+
+        // The unhealthy primary is skipped and not probed again until the probe interval passed
+        failover
+            .create_incoming_payment_request(&CurrencyUnit::Sat, invoice_options())
+            .await
+            .unwrap();
+        assert_eq!(primary.probes.load(Ordering::SeqCst), 1);
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 0);
+
+        // Invoices are checked on the backend that created them
+        failover
+            .check_incoming_payment_status(&response.request_lookup_id)
+            .await
+            .unwrap();
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_primary_recovers_after_probe_interval() {
+        let primary = Arc::new(Down::default());
+        let failover = chain(&primary, Duration::ZERO);
+
+        failover
+            .create_incoming_payment_request(&CurrencyUnit::Sat, invoice_options())
+            .await
+            .unwrap();
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 0);
+
+        // Probed again once the probe interval passed, and preferred once it passes
+        primary.up.store(true, Ordering::SeqCst);
+        failover
+            .create_incoming_payment_request(&CurrencyUnit::Sat, invoice_options())
+            .await
+            .unwrap();
+        assert_eq!(primary.probes.load(Ordering::SeqCst), 2);
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 1);
+        assert!(failover.is_healthy(0));
+    }
+
+    #[tokio::test]
+    async fn test_call_errors_do_not_fail_over() {
+        let primary = Arc::new(Down::default());
+        primary.up.store(true, Ordering::SeqCst);
+        let failover = chain(&primary, Duration::from_secs(60));
+
+        // Failing calls move on to the next backend, but the primary stays first in line
+        for _ in 0..2 {
+            failover
+                .create_incoming_payment_request(&CurrencyUnit::Sat, invoice_options())
+                .await
+                .unwrap();
+        }
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(primary.probes.load(Ordering::SeqCst), 1);
+        assert!(failover.is_healthy(0));
+    }
+
+    #[tokio::test]
+    async fn test_payment_stays_on_quoting_backend() {
+        let primary = Arc::new(Down::default());
+        primary.up.store(true, Ordering::SeqCst);
+        let failover = chain(&primary, Duration::ZERO);
+
+        let invoice = fake_wallet()
+            .create_incoming_payment_request(&CurrencyUnit::Sat, invoice_options())
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&invoice.request).unwrap();
+        failover
+            .get_payment_quote(&CurrencyUnit::Sat, pay_options(&invoice))
+            .await
+            .unwrap();
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 1);
+
+        // The primary is preferred, but the payment goes to the backend that quoted it
+        assert!(failover.is_healthy(0));
+        failover
+            .make_payment(&CurrencyUnit::Sat, pay_options(&invoice))
+            .await
+            .unwrap();
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 1);
+
+        // Settled payments are forgotten
+        assert!(failover.quote_owner(&pay_options(&invoice)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_payment_requoted_without_owner() {
+        let primary = Arc::new(Down::default());
+        primary.up.store(true, Ordering::SeqCst);
+        // A fresh chain, as after a restart, knows no quote owners
+        let failover = chain(&primary, Duration::from_secs(60));
+
+        let invoice = fake_wallet()
+            .create_incoming_payment_request(&CurrencyUnit::Sat, invoice_options())
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&invoice.request).unwrap();
+
+        // No backend quotes a fee within a zero limit
+        let OutgoingPaymentOptions::Bolt11(mut options) = pay_options(&invoice) else {
+            unreachable!();
+        };
+        options.max_fee_amount = Some(Amount::ZERO);
+        assert!(failover
+            .make_payment(&CurrencyUnit::Sat, OutgoingPaymentOptions::Bolt11(options))
+            .await
+            .is_err());
+
+        // The primary fails to quote, so the fake wallet pays
+        let response = failover
+            .make_payment(&CurrencyUnit::Sat, pay_options(&invoice))
+            .await
+            .unwrap();
+        assert_eq!(response.status, MeltQuoteState::Paid);
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_leaves_shared_members_waiting() {
+        let shared = Arc::new(Down::default());
+        let failover = FailoverBackend::new(
+            vec![Member {
+                kind: LnBackend::Lnd,
+                backend: shared.clone(),
+                probe: None,
+            }],
+            Duration::from_secs(60),
+        );
+
+        // The member also serves another unit on its own
+        let _direct = shared.wait_any_incoming_payment().await.unwrap();
+        let mut chained = failover.wait_any_incoming_payment().await.unwrap();
+        assert!(failover.is_wait_invoice_active());
+
+        failover.cancel_wait_invoice();
+        assert!(chained.next().await.is_none());
+        assert!(!failover.is_wait_invoice_active());
+        assert!(shared.is_wait_invoice_active());
+    }
+
+    #[test]
+    fn test_owners_are_bounded() {
+        let mut owners = Owners::default();
+        for n in 0..=MAX_OWNERS {
+            owners.insert(n.to_string(), n % 2);
+        }
+        assert_eq!(owners.index.len(), MAX_OWNERS);
+        assert_eq!(owners.get("0"), None);
+        assert_eq!(owners.get("1"), Some(1));
+
+        owners.remove("1");
+        assert_eq!(owners.get("1"), None);
+        assert_eq!(owners.index.len(), MAX_OWNERS - 1);
+
+        // Churn of settled payments keeps both the index and the insertion order bounded
+        for n in 0..3 * MAX_OWNERS {
+            let key = format!("settled-{}", n);
+            owners.insert(key.clone(), 0);
+            owners.remove(&key);
+        }
+        assert_eq!(owners.index.len(), MAX_OWNERS - 1);
+        assert!(owners.recent.len() <= 2 * MAX_OWNERS);
+        assert_eq!(owners.get("2"), Some(0));
+    }
+
+    #[test]
+    fn test_intersect_settings() {
+        let primary = serde_json::json!({ "mpp": true, "amountless": true, "unit": "msat" });
+        let fallback = serde_json::json!({ "mpp": true, "amountless": false, "unit": "sat" });
+
+        let merged = intersect_settings(primary.clone(), Some(&fallback));
+        assert_eq!(merged, serde_json::json!({ "mpp": true, "amountless": false, "unit": "msat" }));
+
+        // A backend that did not answer supports no optional feature
+        let merged = intersect_settings(primary, None);
+        assert_eq!(merged, serde_json::json!({ "mpp": false, "amountless": false, "unit": "msat" }));
+    }
+
+    #[tokio::test]
+    async fn test_settings_do_not_depend_on_health() {
+        let primary = Arc::new(Down::default());
+        let failover = chain(&primary, Duration::from_secs(60));
+
+        // The fake wallet answers, but features the primary could not confirm are not advertised
+        let settings = failover.get_settings().await.unwrap();
+        let fake_settings = fake_wallet().get_settings().await.unwrap();
+        assert_eq!(settings, intersect_settings(fake_settings, None));
+    }
+
+    /// Answer one connection on a CLN-style RPC socket with `reply` to its `getinfo`
+    fn serve_cln_rpc(listener: tokio::net::UnixListener, reply: Value) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 4096];
+            let read = socket.read(&mut buffer).await.unwrap();
+            let request: Value = serde_json::from_slice(&buffer[..read]).unwrap();
+            assert_eq!(request["method"], "getinfo");
+            // Split the reply to exercise reassembly
+            let reply = format!("{}\n\n", reply);
+            let (head, tail) = reply.split_at(reply.len() / 2);
+            socket.write_all(head.as_bytes()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            socket.write_all(tail.as_bytes()).await.unwrap();
+        });
+    }
+
+    #[tokio::test]
+    async fn test_cln_probe() {
+        let dir = tempfile::tempdir().unwrap();
+        let rpc_path = dir.path().join("lightning-rpc");
+        let probe = ClnProbe::new(&rpc_path);
+        assert!(probe.probe().await.is_err());
+
+        let listener = tokio::net::UnixListener::bind(&rpc_path).unwrap();
+        serve_cln_rpc(listener, serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": { "id": "node" } }));
+        probe.probe().await.unwrap();
+
+        std::fs::remove_file(&rpc_path).unwrap();
+        let listener = tokio::net::UnixListener::bind(&rpc_path).unwrap();
+        serve_cln_rpc(
+            listener,
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32601, "message": "warming up" } }),
+        );
+        assert!(probe.probe().await.is_err());
+    }
+}